@@ -0,0 +1,138 @@
+//! How fast `check_repo` gets through a directory of repositories, with fetching skipped so only
+//! the local work (branch lookups, ancestry checks, status) is measured.
+//!
+//! `CHECK_GITS_BENCH_REPOS` sets how many local+remote pairs to generate (20 by default). Their
+//! history depths and branch counts cycle through `DEPTHS` and `BRANCH_COUNTS`.
+
+use check_gits::check::{check_repo, CheckOptions};
+use check_gits::credentials::Credentials;
+use check_gits::printer::{Printer, Verbosity};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use git2::{BranchType, Oid, Repository, RepositoryInitOptions, Signature};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+const DEPTHS: [usize; 3] = [10, 100, 1000];
+const BRANCH_COUNTS: [usize; 3] = [1, 4, 16];
+
+struct Pairs {
+    dir: TempDir,
+    locals: Vec<PathBuf>,
+}
+
+impl Pairs {
+    fn generate(count: usize) -> Self {
+        let dir = TempDir::new().unwrap();
+        let locals = (0..count)
+            .map(|i| {
+                let depth = DEPTHS[i % DEPTHS.len()];
+                let branches = BRANCH_COUNTS[i / DEPTHS.len() % BRANCH_COUNTS.len()];
+                generate_pair(&dir.path().join(format!("pair-{i}")), depth, branches)
+            })
+            .collect();
+        Self { dir, locals }
+    }
+
+    /// Every remote counts as qualifying, and as fetched a moment ago so none is fetched again
+    fn options(&self) -> CheckOptions {
+        let mut options = CheckOptions::new(Credentials::default());
+        options.qualifying_url_prefixes = vec![self.dir.path().to_str().unwrap().to_owned()];
+        options.fetch_cooldown = Duration::MAX;
+        for local in &self.locals {
+            let git_dir = Repository::open(local).unwrap().path().to_path_buf();
+            options.fetch_cache.record(&git_dir, "origin");
+        }
+        options
+    }
+}
+
+/// A bare remote with `depth` commits on each of `branches` branches, and a clone of it where
+/// every other branch has a commit of its own on top
+fn generate_pair(dir: &Path, depth: usize, branches: usize) -> PathBuf {
+    let remote = Repository::init_opts(
+        dir.join("remote.git"),
+        RepositoryInitOptions::new().bare(true).initial_head("main"),
+    )
+    .unwrap();
+    let mut tip = None;
+    for n in 0..depth {
+        tip = Some(commit(&remote, tip, &format!("commit {n}")));
+    }
+    let tip = remote.find_commit(tip.unwrap()).unwrap();
+    remote.branch("main", &tip, true).unwrap();
+    for n in 1..branches {
+        let branch_tip = commit(&remote, Some(tip.id()), &format!("branch {n}"));
+        remote
+            .branch(
+                &format!("branch-{n}"),
+                &remote.find_commit(branch_tip).unwrap(),
+                true,
+            )
+            .unwrap();
+    }
+
+    let local_path = dir.join("local");
+    let local = Repository::clone(remote.path().to_str().unwrap(), &local_path).unwrap();
+    for n in 1..branches {
+        let name = format!("branch-{n}");
+        let upstream = local
+            .find_branch(&format!("origin/{name}"), BranchType::Remote)
+            .unwrap()
+            .get()
+            .peel_to_commit()
+            .unwrap();
+        let mut branch = local.branch(&name, &upstream, true).unwrap();
+        branch
+            .set_upstream(Some(&format!("origin/{name}")))
+            .unwrap();
+        if n % 2 == 0 {
+            let ahead = commit(
+                &local,
+                Some(upstream.id()),
+                &format!("local work on {name}"),
+            );
+            branch.get_mut().set_target(ahead, "ahead").unwrap();
+        }
+    }
+    local_path
+}
+
+fn commit(repo: &Repository, parent: Option<Oid>, message: &str) -> Oid {
+    let signature = Signature::now("Bench", "bench@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+        .unwrap();
+    let parents: Vec<_> = parent
+        .map(|parent| repo.find_commit(parent).unwrap())
+        .into_iter()
+        .collect();
+    let parents: Vec<_> = parents.iter().collect();
+    repo.commit(None, &signature, &signature, message, &tree, &parents)
+        .unwrap()
+}
+
+fn check_repos(c: &mut Criterion) {
+    let count = std::env::var("CHECK_GITS_BENCH_REPOS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(20);
+    let pairs = Pairs::generate(count);
+    let options = pairs.options();
+
+    let mut group = c.benchmark_group("check_repo");
+    group.throughput(Throughput::Elements(count as u64));
+    group.bench_function(format!("{count} repos"), |b| {
+        b.iter(|| {
+            for local in &pairs.locals {
+                // The messages are still formatted, but never handed to a reporter
+                let mut printer = Printer::new(Verbosity::Normal);
+                check_repo(local, &options, &mut printer).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, check_repos);
+criterion_main!(benches);
@@ -0,0 +1,1793 @@
+use crate::credentials::{AuthMethod, Credentials};
+use crate::error::{CheckError, Result};
+use crate::fetch_cache::FetchCache;
+use crate::fetch_limit::FetchLimit;
+use crate::printer::{Printer, Theme};
+use crate::report::{
+    BranchReport, BranchStatus, Category, Finding, LastCommit, RepoReport, SeverityMap,
+};
+use crate::ssh_config;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, TimeZone};
+use git2::{Remote, Repository};
+use log::{debug, trace};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Url prefixes of remotes that are considered safe places for work to live.
+// TODO: support more urls / make them configurable
+pub const DEFAULT_QUALIFYING_URL_PREFIXES: [&str; 2] = ["https://github.com/", "git@github.com:"];
+
+/// Url schemes that never qualify by default but can be opted into
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExtraScheme {
+    /// The unauthenticated git protocol, still used by some legacy hosts
+    Git,
+    /// Local mirrors, which fetch quickly and work offline
+    File,
+}
+
+impl ExtraScheme {
+    pub fn url_prefix(self) -> &'static str {
+        match self {
+            ExtraScheme::Git => "git://",
+            ExtraScheme::File => "file://",
+        }
+    }
+}
+
+/// How recently a repository must have been worked in to be checked
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModifiedSince {
+    /// This long before the repository is checked
+    Ago(Duration),
+    At(DateTime<Local>),
+}
+
+impl ModifiedSince {
+    /// Parses a duration like `7d`, `12h`, `30m`, `2w` or `90s`, a date like `2024-05-01`, or
+    /// an RFC 3339 time like `2024-05-01T09:00:00+02:00`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            // The start of that day where the scan runs
+            return Local
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                .earliest()
+                .map(ModifiedSince::At)
+                .ok_or_else(|| format!("'{}' has no midnight in the local time zone", value));
+        }
+        if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+            return Ok(ModifiedSince::At(time.with_timezone(&Local)));
+        }
+        let unit_start = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (count, unit) = value.split_at(unit_start);
+        let seconds_per_unit = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            "w" => 7 * 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "expected a duration like 7d or a date like 2024-05-01, got '{}'",
+                    value
+                ))
+            }
+        };
+        let count: u64 = count
+            .parse()
+            .map_err(|_| format!("expected a number before '{}' in '{}'", unit, value))?;
+        Ok(ModifiedSince::Ago(Duration::from_secs(
+            count.saturating_mul(seconds_per_unit),
+        )))
+    }
+
+    /// The time before which a repository counts as untouched
+    pub fn cutoff(self, now: DateTime<Local>) -> DateTime<Local> {
+        match self {
+            ModifiedSince::Ago(duration) => chrono::Duration::from_std(duration)
+                .ok()
+                .and_then(|duration| now.checked_sub_signed(duration))
+                .unwrap_or(DateTime::<Local>::MIN_UTC.into()),
+            ModifiedSince::At(time) => time,
+        }
+    }
+}
+
+const FETCH_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// How often a running fetch reports its progress
+const FETCH_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct CheckOptions {
+    /// Shared by every fetch of the run
+    pub credentials: Arc<Credentials>,
+    pub qualifying_url_prefixes: Vec<String>,
+    /// Urls matching any of these qualify too, whatever their prefix
+    pub qualifying_url_patterns: Vec<regex::Regex>,
+    /// Give up on a remote's fetch after this long. `None` waits indefinitely.
+    pub fetch_timeout: Option<Duration>,
+    /// How many times to retry a fetch that failed with a transient (network) error
+    pub fetch_retries: u32,
+    /// Count a branch as synced if the same-named branch on any fetched remote contains it,
+    /// instead of only consulting the configured upstream
+    pub any_remote: bool,
+    /// Branches matching any of these may lack an upstream without it being a problem.
+    /// Branches that do have an upstream are checked as usual.
+    pub allowed_local_branches: Vec<glob::Pattern>,
+    /// Only check the branch HEAD points at, or HEAD's commit itself when it's detached
+    pub current_branch_only: bool,
+    /// Point out branches on the remotes that no local branch tracks and that haven't been merged
+    pub show_unfetched_remote_branches: bool,
+    /// How many of a repository's remotes to fetch at once
+    pub fetch_jobs: usize,
+    /// How many of a repository's branches to check at once
+    pub branch_jobs: usize,
+    /// How many fetches may run at once over the whole run, whatever `fetch_jobs` allows
+    pub fetch_limit: Arc<FetchLimit>,
+    /// Report on long fetches while they're still running
+    pub show_fetch_progress: bool,
+    /// For the fetch progress lines, which are printed straight away rather than by the `Printer`
+    pub theme: Theme,
+    /// Point out synced branches whose upstream has commits that haven't been pulled yet
+    pub show_behind: bool,
+    /// Branches that are ahead of their upstream only get a warning. Diverged ones are still errors.
+    pub allow_ahead: bool,
+    /// Overrides of the built-in severities. Ignored verdicts are left out of the report.
+    pub severity_map: SeverityMap,
+    /// Don't fetch remotes that `fetch_cache` says were fetched less than this long ago.
+    /// Zero always fetches.
+    pub fetch_cooldown: Duration,
+    /// When remotes were last fetched. Every successful fetch is recorded here.
+    pub fetch_cache: Arc<FetchCache>,
+    /// List what pushing each branch that's ahead would send
+    pub push_preview: bool,
+    /// Only fetch and check these remotes. `None` checks all of them.
+    pub selected_remotes: Option<Vec<String>>,
+    /// Only say which remotes would be fetched, and stop there without touching the network
+    pub dry_run: bool,
+    /// Proxy url for fetches. `None` uses git's own proxy config and environment variables.
+    pub proxy: Option<String>,
+    /// Also compare every branch with the same-named branch on this remote, whatever its upstream
+    pub compare_remote: Option<String>,
+    /// Point out notes that no fetched remote has
+    pub check_notes: bool,
+    /// Point out tags whose commits no fetched remote has
+    pub check_tags: bool,
+    /// Make sure the remote's default branch has a local branch that's in sync with it
+    pub check_default_branch: bool,
+    /// Look for uncommitted changes and untracked files. Without it only the branches are
+    /// checked, which is much faster in huge checkouts but misses work that was never committed.
+    pub verify_working_tree: bool,
+    /// Check branches without an upstream against the same-named branch on this remote, if it
+    /// has one
+    pub preferred_remote: Option<String>,
+    /// Skip repositories that haven't been committed to or staged in since then
+    pub modified_since: Option<ModifiedSince>,
+}
+
+impl CheckOptions {
+    pub fn new(credentials: Credentials) -> Self {
+        Self {
+            credentials: Arc::new(credentials),
+            qualifying_url_prefixes: DEFAULT_QUALIFYING_URL_PREFIXES
+                .iter()
+                .map(|prefix| prefix.to_string())
+                .collect(),
+            qualifying_url_patterns: Vec::new(),
+            fetch_timeout: None,
+            fetch_retries: 2,
+            any_remote: false,
+            allowed_local_branches: Vec::new(),
+            current_branch_only: false,
+            show_unfetched_remote_branches: false,
+            fetch_jobs: 1,
+            branch_jobs: 1,
+            fetch_limit: Arc::default(),
+            show_fetch_progress: false,
+            theme: Theme::default(),
+            show_behind: false,
+            proxy: None,
+            allow_ahead: false,
+            severity_map: SeverityMap::default(),
+            dry_run: false,
+            selected_remotes: None,
+            fetch_cooldown: Duration::ZERO,
+            fetch_cache: Arc::default(),
+            push_preview: false,
+            compare_remote: None,
+            modified_since: None,
+            preferred_remote: None,
+            verify_working_tree: true,
+            check_notes: false,
+            check_tags: false,
+            check_default_branch: false,
+        }
+    }
+
+    fn is_allowed_local_branch(&self, branch_name: &str) -> bool {
+        self.allowed_local_branches
+            .iter()
+            .any(|pattern| pattern.matches(branch_name))
+    }
+
+    fn is_selected_remote(&self, remote_name: &str) -> bool {
+        self.selected_remotes
+            .as_ref()
+            .is_none_or(|selected| selected.iter().any(|name| name == remote_name))
+    }
+
+    fn is_qualifying_url(&self, url: &str) -> bool {
+        self.qualifying_url_prefixes
+            .iter()
+            .any(|prefix| url.starts_with(prefix.as_str()))
+            || self
+                .qualifying_url_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(url))
+    }
+}
+
+/// Checks the directory at `path`, which is expected to be a git repository.
+///
+/// Problems that can be handled are printed by the `Printer` and recorded in the returned report.
+/// Only unexpected failures are returned, as a `CheckError`.
+pub fn check_repo(
+    path: &Path,
+    options: &CheckOptions,
+    printer: &mut Printer,
+) -> Result<RepoReport> {
+    let mut report = inspect_repo(path, options, printer)?;
+    report.apply_severity_map(&options.severity_map);
+    Ok(report)
+}
+
+/// `check_repo` with the built-in severities
+fn inspect_repo(path: &Path, options: &CheckOptions, printer: &mut Printer) -> Result<RepoReport> {
+    let mut report = RepoReport::new(path.to_path_buf());
+    let opening = Instant::now();
+    let repo = match open_repo(path) {
+        Ok(repo) => {
+            debug!("Opened {} in {:?}", path.display(), opening.elapsed());
+            repo
+        }
+        Err(_) if is_permission_denied(path) => {
+            printer.log_permission_denied(path);
+            report.findings.push(Finding::PermissionDenied);
+            return Ok(report);
+        }
+        Err(CheckError::NotARepository(error)) => {
+            printer.log_nongit_dir(path, error.message());
+            report.findings.push(Finding::NotARepository);
+            return Ok(report);
+        }
+        // A repository that's there but can't be opened, e.g. for a broken config
+        Err(error) => return Err(error),
+    };
+    // Current entry is a git repository
+    printer.log_entry_is_a_git_repo(path);
+
+    if let Some(modified_since) = options.modified_since {
+        let cutoff = modified_since.cutoff(Local::now());
+        // A repository whose age can't be told is checked, rather than hidden
+        if let Some(last_modified) = last_modified(&repo).filter(|&time| time < cutoff) {
+            printer.log_not_modified_since(path, last_modified, cutoff);
+            report.findings.push(Finding::NotModifiedSince);
+            return Ok(report);
+        }
+    }
+
+    if repo.is_bare() {
+        // There is no working tree to check, but the branches still need to be pushed
+        printer.log_bare_repo(path);
+    } else if !options.verify_working_tree {
+        printer.log_working_tree_not_verified(path);
+    } else {
+        check_working_tree(&repo, path, printer, &mut report)?;
+    }
+    // Commits only a detached HEAD points at are lost on the next checkout, working tree or not.
+    // When only the current branch is checked, that check looks at a detached HEAD itself.
+    let is_linked_worktree = repo.is_worktree();
+    let current_branch_only = options.current_branch_only || is_linked_worktree;
+    if !repo.is_bare() && !current_branch_only {
+        check_detached_head(&repo, path, printer, &mut report)?;
+    }
+
+    let is_shallow = repo.is_shallow();
+    if is_shallow {
+        printer.log_shallow_repo(path);
+        report.findings.push(Finding::ShallowClone);
+    }
+
+    // Branch pointers are in flux until the operation is finished or aborted
+    if let Some(operation) = operation_in_progress(repo.state()) {
+        printer.log_repo_in_progress(path, operation);
+        report.findings.push(Finding::OperationInProgress {
+            operation: operation.to_owned(),
+        });
+    }
+
+    // A freshly initialized repository, whose HEAD points at a branch that doesn't exist yet
+    let unborn_head = match repo.head() {
+        Err(error) if error.code() == git2::ErrorCode::UnbornBranch => {
+            let branch = repo
+                .find_reference("HEAD")?
+                .symbolic_target()
+                .map(|target| target.trim_start_matches("refs/heads/").to_owned());
+            printer.log_no_commits_yet(path, branch.as_deref());
+            report.findings.push(Finding::NoCommitsYet);
+            true
+        }
+        _ => false,
+    };
+
+    // Find all remotes
+    let remote_names = match repo.remotes() {
+        Ok(remote_names) => remote_names,
+        Err(error) => {
+            // e.g. an unreadable config. Without the remotes there is nothing to compare with.
+            printer.log_remotes_enumeration_failed(path, &error);
+            report.findings.push(Finding::CheckFailed {
+                message: format!("couldn't list the remotes: {}", error),
+            });
+            return Ok(report);
+        }
+    };
+    if remote_names.is_empty() {
+        // Nothing in this repository has ever been pushed anywhere
+        printer.log_no_remotes(path);
+        report.findings.push(Finding::NoRemotes);
+    }
+    let mut qualifying_remotes: Vec<Remote> = Vec::new();
+    for (remote_name, remote_name_bytes) in
+        std::iter::zip(remote_names.iter(), remote_names.iter_bytes())
+    {
+        let remote_name = match remote_name {
+            Some(remote) => remote,
+            None => {
+                printer.log_remote_bad_name(path, remote_name_bytes);
+                report.findings.push(Finding::InvalidRemote);
+                continue;
+            }
+        };
+        if !options.is_selected_remote(remote_name) {
+            printer.log_remote_not_selected(path, remote_name);
+            continue;
+        }
+        let remote = match repo.find_remote(remote_name) {
+            Ok(remote) => remote,
+            Err(error) => {
+                printer.log_remote_not_found(path, remote_name, error.into());
+                report.findings.push(Finding::InvalidRemote);
+                continue;
+            }
+        };
+        let url = match remote.url() {
+            Some(url) => url,
+            None => {
+                printer.log_remote_bad_url(path, remote_name, remote.url_bytes());
+                report.findings.push(Finding::InvalidRemote);
+                continue;
+            }
+        };
+        if options.is_qualifying_url(url) {
+            if let Some(host) = ssh_config::host_of_url(url) {
+                if !report.hosts.iter().any(|known| known == host) {
+                    report.hosts.push(host.to_owned());
+                }
+            }
+            qualifying_remotes.push(remote);
+        } else {
+            printer.log_unqualified_remote(path, remote_name, url);
+            report.findings.push(Finding::UnqualifiedRemote {
+                remote: remote_name.to_owned(),
+            });
+        }
+    }
+
+    if options.dry_run {
+        // Without a fetch the remote-tracking branches may be stale, so the branches aren't judged
+        for remote in &qualifying_remotes {
+            printer.log_would_fetch(path, remote.name().unwrap(), remote.url().unwrap());
+        }
+        return Ok(report);
+    }
+
+    let synced_remotes = {
+        // Fetch all qualifying remotes
+        let outcomes = fetch_remotes(&repo, &mut qualifying_remotes, options, path, printer);
+        let synced_remotes: Vec<_> = std::iter::zip(qualifying_remotes.iter_mut(), outcomes)
+            .filter_map(|(remote, outcome)| match outcome {
+                FetchOutcome::Fetched(fetched) => {
+                    printer.log_remote_fetch_succeeded(
+                        path,
+                        remote.name().unwrap(),
+                        remote.url().unwrap_or_default(),
+                        fetched.received_objects,
+                        fetched.received_bytes,
+                    );
+                    printer.log_remote_auth_method(
+                        path,
+                        remote.name().unwrap(),
+                        fetched.auth_method.as_ref(),
+                    );
+                    Some(remote)
+                }
+                FetchOutcome::CoolingDown(fetched_ago) => {
+                    printer.log_remote_fetch_skipped(path, remote.name().unwrap(), fetched_ago);
+                    Some(remote)
+                }
+                FetchOutcome::Failed(error) => {
+                    printer.log_remote_fetch_failed(
+                        path,
+                        remote.name().unwrap(),
+                        remote.url().unwrap_or_default(),
+                        error,
+                    );
+                    report.findings.push(Finding::RemoteFetchFailed {
+                        remote: remote.name().unwrap().to_owned(),
+                    });
+                    None
+                }
+                FetchOutcome::TimedOut(timeout) => {
+                    printer.log_remote_fetch_timeout(path, remote.name().unwrap(), timeout);
+                    report.findings.push(Finding::RemoteFetchTimedOut {
+                        remote: remote.name().unwrap().to_owned(),
+                    });
+                    None
+                }
+            })
+            .collect(); // Must be eagerly iterated, because `printer` is borrowed mutably
+        synced_remotes
+    };
+
+    // Get all local branches (i.e. not remote-tracking branches) and check
+    // 1. that they have a corresponding remote-tracking branch
+    // 2. that they're not ahead of the remote-tracking branch
+    // A linked worktree shares its branches with the main repository, which checks all of them
+    if is_linked_worktree {
+        printer.log_linked_worktree(path, &main_repository_path(&repo));
+    }
+    let branches = if current_branch_only {
+        current_branch(&repo, &synced_remotes, path, printer, &mut report)?
+    } else {
+        let branches = match local_branches(&repo) {
+            Ok(branches) => branches,
+            Err(error) => {
+                // e.g. a corrupt refs database
+                printer.log_branches_enumeration_failed(path, &error);
+                report.findings.push(Finding::CheckFailed {
+                    message: format!("couldn't list the local branches: {}", error),
+                });
+                return Ok(report);
+            }
+        };
+        // Without any commits there can't be branches, and nothing to take a look at
+        if branches.is_empty() && !unborn_head {
+            printer.log_no_local_branches(path);
+            report.findings.push(Finding::NoLocalBranches);
+        }
+        branches
+    };
+    // Only a fetched remote has tips worth comparing with
+    let compare_remote = options.compare_remote.as_deref().filter(|&compare_remote| {
+        if !remote_names.iter().any(|name| name == Some(compare_remote)) {
+            printer.log_no_compare_remote(path, compare_remote);
+            return false;
+        }
+        let fetched = synced_remotes
+            .iter()
+            .any(|remote| remote.name() == Some(compare_remote));
+        if !fetched {
+            printer.log_compare_remote_not_fetched(path, compare_remote);
+        }
+        fetched
+    });
+    let context = BranchContext {
+        options,
+        path,
+        synced_remote_names: synced_remotes
+            .iter()
+            .map(|remote| remote.name().unwrap())
+            .collect(),
+        compare_remote,
+        has_remotes: !remote_names.is_empty(),
+        is_shallow,
+    };
+    check_branches(&repo, branches, &context, printer, &mut report);
+
+    if options.show_unfetched_remote_branches {
+        let synced_remote_names = synced_remotes.iter().map(|remote| remote.name().unwrap());
+        check_remote_branches(&repo, synced_remote_names, path, printer, &mut report)?;
+    }
+    if options.check_notes {
+        check_notes(&repo, &synced_remotes, path, printer, &mut report)?;
+    }
+    if options.check_tags {
+        let synced_remote_names = synced_remotes.iter().map(|remote| remote.name().unwrap());
+        check_tags(&repo, synced_remote_names, path, printer, &mut report)?;
+    }
+    if options.check_default_branch {
+        check_default_branch(&repo, &context, printer, &mut report)?;
+    }
+
+    Ok(report)
+}
+
+/// Checks that the default branch of the main remote (the preferred remote, else `origin`, else
+/// the first one fetched) has a local branch, and that the local branch is where the remote's is
+fn check_default_branch(
+    repo: &Repository,
+    context: &BranchContext,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    let path = context.path;
+    let synced_remote_names = &context.synced_remote_names;
+    let Some(remote_name) = context
+        .options
+        .preferred_remote
+        .as_deref()
+        .filter(|preferred_remote| synced_remote_names.contains(preferred_remote))
+        .or_else(|| {
+            synced_remote_names
+                .iter()
+                .copied()
+                .find(|&name| name == "origin")
+        })
+        .or_else(|| synced_remote_names.first().copied())
+    else {
+        return Ok(());
+    };
+    // A dangling `<remote>/HEAD` says as little as a missing one
+    let default_branch = remote_default_branch(repo, remote_name).and_then(|branch_name| {
+        let refname = format!("refs/remotes/{}/{}", remote_name, branch_name);
+        Some((repo.refname_to_id(&refname).ok()?, branch_name))
+    });
+    let Some((remote_oid, branch_name)) = default_branch else {
+        printer.log_default_branch_unknown(path, remote_name);
+        return Ok(());
+    };
+    let local_oid = match repo.find_branch(&branch_name, git2::BranchType::Local) {
+        Ok(branch) => branch.get().target(),
+        Err(error) if error.code() == git2::ErrorCode::NotFound => None,
+        Err(error) => return Err(error.into()),
+    };
+    let Some(local_oid) = local_oid else {
+        printer.log_default_branch_missing(path, &branch_name, remote_name);
+        report.findings.push(Finding::DefaultBranchMissing {
+            branch: branch_name,
+            remote: remote_name.to_owned(),
+        });
+        return Ok(());
+    };
+    let (ahead, behind) = repo.graph_ahead_behind(local_oid, remote_oid)?;
+    if ahead > 0 || behind > 0 {
+        printer.log_default_branch_not_synced(path, &branch_name, remote_name, ahead, behind);
+        report.findings.push(Finding::DefaultBranchNotSynced {
+            branch: branch_name,
+            remote: remote_name.to_owned(),
+            ahead,
+            behind,
+        });
+    }
+    Ok(())
+}
+
+/// The branch that `<remote>/HEAD` points at, as set up by `git clone` or
+/// `git remote set-head`, or else `main` or `master` if the remote has it.
+/// `Remote::default_branch` would need another connection, after the fetch already closed one.
+fn remote_default_branch(repo: &Repository, remote_name: &str) -> Option<String> {
+    let remote_prefix = format!("refs/remotes/{}/", remote_name);
+    let head = repo
+        .find_reference(&format!("{}HEAD", remote_prefix))
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_owned))
+        .and_then(|target| target.strip_prefix(&remote_prefix).map(str::to_owned));
+    head.or_else(|| {
+        ["main", "master"]
+            .into_iter()
+            .find(|name| {
+                repo.find_reference(&format!("{}{}", remote_prefix, name))
+                    .is_ok()
+            })
+            .map(str::to_owned)
+    })
+}
+
+/// What every branch of a repository is checked against
+struct BranchContext<'a> {
+    options: &'a CheckOptions,
+    path: &'a Path,
+    /// The remotes whose remote-tracking branches are up to date
+    synced_remote_names: Vec<&'a str>,
+    /// --compare-remote, if that remote was fetched
+    compare_remote: Option<&'a str>,
+    has_remotes: bool,
+    is_shallow: bool,
+}
+
+/// Checks each of `branches`, up to `branch_jobs` of them at a time
+fn check_branches(
+    repo: &Repository,
+    branches: Vec<git2::Branch>,
+    context: &BranchContext,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) {
+    let refnames: Option<Vec<&str>> = branches.iter().map(|branch| branch.get().name()).collect();
+    let refnames = match refnames {
+        Some(refnames) if context.options.branch_jobs > 1 && branches.len() > 1 => refnames,
+        // A branch whose refname isn't UTF-8 couldn't be looked up again by a worker
+        _ => {
+            for branch in &branches {
+                check_branch(repo, branch, context, printer, report);
+            }
+            return;
+        }
+    };
+
+    // `Branch` borrows the `Repository`, which isn't `Sync`, so each worker opens its own and looks
+    // the branches up again by refname. Each branch's messages and verdicts are collected
+    // separately and merged back in branch order, so the report doesn't depend on the timing.
+    let repo_path = repo.path();
+    let next_branch = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<(Printer, RepoReport)>>> =
+        refnames.iter().map(|_| Mutex::new(None)).collect();
+    let template = &*printer;
+    thread::scope(|scope| {
+        for _ in 0..context.options.branch_jobs.min(refnames.len()) {
+            scope.spawn(|| {
+                let worker_repo = Repository::open(repo_path);
+                loop {
+                    let index = next_branch.fetch_add(1, Ordering::Relaxed);
+                    let Some(&refname) = refnames.get(index) else {
+                        break;
+                    };
+                    let mut branch_printer = template.buffer();
+                    let mut branch_report = RepoReport::new(context.path.to_path_buf());
+                    let checked = worker_repo
+                        .as_ref()
+                        .map_err(|error| git2::Error::from_str(error.message()))
+                        .and_then(|worker_repo| {
+                            let branch = git2::Branch::wrap(worker_repo.find_reference(refname)?);
+                            check_branch(
+                                worker_repo,
+                                &branch,
+                                context,
+                                &mut branch_printer,
+                                &mut branch_report,
+                            );
+                            Ok(())
+                        });
+                    if let Err(error) = checked {
+                        let branch_name = refname.strip_prefix("refs/heads/").unwrap_or(refname);
+                        branch_printer.log_general_branch_error(
+                            context.path,
+                            branch_name,
+                            error.into(),
+                        );
+                        branch_report.branches.push(BranchReport {
+                            name: branch_name.to_owned(),
+                            upstream: None,
+                            remote: None,
+                            status: BranchStatus::Failed,
+                            ahead: 0,
+                            behind: 0,
+                            last_commit: None,
+                        });
+                    }
+                    *results[index].lock().unwrap() = Some((branch_printer, branch_report));
+                }
+            });
+        }
+    });
+    for result in results {
+        let (mut branch_printer, mut branch_report) = result.into_inner().unwrap().unwrap();
+        printer.append(&mut branch_printer);
+        report.findings.append(&mut branch_report.findings);
+        report.branches.append(&mut branch_report.branches);
+    }
+}
+
+/// Checks one local branch against its upstream, or against the remotes with --any-remote
+fn check_branch(
+    repo: &Repository,
+    branch: &git2::Branch,
+    context: &BranchContext,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) {
+    let options = context.options;
+    let path = context.path;
+    // Convert a Result<Option<&str, Error> to a Result<String, Error>
+    let branch_name = branch.name().and_then(|maybe_branch_name| {
+        maybe_branch_name.map_or_else(
+            || {
+                branch
+                    .name_bytes()
+                    .map(|slice| String::from_utf8_lossy(slice).to_string())
+            },
+            |branch_name| Ok(branch_name.to_owned()),
+        )
+    });
+    let branch_name = match branch_name {
+        Ok(branch_name) => {
+            printer.log_branch_name(path, &branch_name);
+            branch_name
+        }
+        Err(error) => {
+            printer.log_branch_name_error(path, error.into());
+            return;
+        }
+    };
+    let mut branch_report = BranchReport {
+        name: branch_name.clone(),
+        upstream: None,
+        remote: None,
+        status: BranchStatus::Failed,
+        ahead: 0,
+        behind: 0,
+        last_commit: None,
+    };
+    if let Some(compare_remote) = context.compare_remote {
+        compare_with_remote(
+            repo,
+            branch,
+            &branch_name,
+            compare_remote,
+            path,
+            printer,
+            report,
+        );
+    }
+    if options.any_remote {
+        let synced_remote_names = context.synced_remote_names.iter().copied();
+        if let Some((remote_name, behind)) =
+            find_remote_containing_branch(repo, synced_remote_names, branch, &branch_name)
+        {
+            printer.log_branch_is_synced_with_remote(path, &branch_name, &remote_name);
+            branch_report.remote = Some(remote_name);
+            branch_report.behind = behind;
+            branch_report.status = match behind {
+                0 => BranchStatus::UpToDate,
+                _ => BranchStatus::Behind,
+            };
+            report.branches.push(branch_report);
+            return;
+        }
+        // Not on any remote under the same name, so the configured upstream gets the final say
+    }
+    // Without a configured upstream, the same-named branch on the preferred remote stands in
+    let upstream = branch.upstream().or_else(|error| {
+        let Some(preferred_remote) = &options.preferred_remote else {
+            return Err(error);
+        };
+        let remote_branch_name = format!("{}/{}", preferred_remote, branch_name);
+        let remote_branch = repo
+            .find_branch(&remote_branch_name, git2::BranchType::Remote)
+            .map_err(|_| error)?;
+        printer.log_branch_preferred_remote_fallback(path, &branch_name, &remote_branch_name);
+        Ok(remote_branch)
+    });
+    let remote_tracking_branch = match upstream {
+        Ok(remote_tracking_branch) => remote_tracking_branch,
+        Err(_) if options.is_allowed_local_branch(&branch_name) => {
+            printer.log_allowed_local_branch(path, &branch_name);
+            branch_report.status = BranchStatus::AllowedLocal;
+            report.branches.push(branch_report);
+            return;
+        }
+        Err(error) => {
+            // The checked-out branch is where the work in progress is, so it stands out, unless
+            // there's no working tree or nowhere to push it anyway
+            if branch.is_head() && !repo.is_bare() && context.has_remotes {
+                printer.log_head_branch_no_upstream(path, &branch_name);
+                report.findings.push(Finding::HeadBranchNoUpstream {
+                    branch: branch_name.clone(),
+                });
+            } else {
+                printer.log_local_branch_has_no_remote_tracking_branch(
+                    path,
+                    &branch_name,
+                    error.into(),
+                );
+            }
+            branch_report.status = BranchStatus::NoUpstream;
+            branch_report.last_commit =
+                branch.get().target().and_then(|oid| last_commit(repo, oid));
+            log_last_commit(printer, path, &branch_report);
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+
+    // Check upstream tracks a synced remote
+    let remote_tracking_branch_fqrefname = match remote_tracking_branch.name() {
+        Ok(Some(remote_tracking_branch_name)) => {
+            printer.log_branch_upstream_name(path, &branch_name, remote_tracking_branch_name);
+            branch_report.upstream = Some(remote_tracking_branch_name.to_owned());
+            // The `repo.branch_remote_name` function expects a fully qualified refname
+            format!("refs/remotes/{}", remote_tracking_branch_name)
+        }
+        Ok(None) => {
+            match remote_tracking_branch.name_bytes() {
+                Ok(name_bytes) => printer.log_branch_bad_name(path, name_bytes),
+                Err(error) => printer.log_branch_name_error(path, error.into()),
+            }
+            report.branches.push(branch_report);
+            return;
+        }
+        Err(error) => {
+            printer.log_branch_name_error(path, error.into());
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+    let remote_name = match repo.branch_remote_name(&remote_tracking_branch_fqrefname) {
+        Ok(buf) => match buf.as_str() {
+            Some(remote_name) => {
+                printer.log_branch_upstream_remote_name(path, &branch_name, remote_name);
+                remote_name.to_owned()
+            }
+            None => {
+                printer.log_remote_bad_name(path, &[]);
+                report.branches.push(branch_report);
+                return;
+            }
+        },
+        Err(error) => {
+            printer.log_general_branch_error(path, &remote_tracking_branch_fqrefname, error.into());
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+    branch_report.remote = Some(remote_name.clone());
+    if !options.is_selected_remote(&remote_name) {
+        printer.log_branch_remote_not_selected(path, &branch_name, &remote_name);
+        branch_report.status = BranchStatus::RemoteNotSelected;
+        report.branches.push(branch_report);
+        return;
+    }
+    let has_synced_remote = context.synced_remote_names.contains(&remote_name.as_str());
+    // A remote that was left out for its url was never meant to be fetched
+    let is_qualifying = repo
+        .find_remote(&remote_name)
+        .ok()
+        .and_then(|remote| remote.url().map(|url| options.is_qualifying_url(url)));
+    if is_qualifying == Some(false) {
+        printer.log_branch_upstream_remote_not_qualifying(path, &branch_name, &remote_name);
+        branch_report.status = BranchStatus::RemoteNotQualifying;
+        report.branches.push(branch_report);
+        return;
+    }
+    if !has_synced_remote {
+        printer.log_branch_remote_not_fetched(path, &branch_name, &remote_name);
+        branch_report.status = BranchStatus::RemoteNotFetched;
+        report.branches.push(branch_report);
+        return;
+    }
+
+    // Check that the local branch is not ahead of the remote-tracking branch
+    let branch_direct_ref = match branch.get().resolve() {
+        Ok(direct_ref) => direct_ref,
+        Err(error) => {
+            printer.log_general_branch_error(path, &branch_name, error.into());
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+    let Some(branch_oid) = branch_direct_ref.target() else {
+        printer.log_branch_without_commit(path, &branch_name);
+        report.branches.push(branch_report);
+        return;
+    };
+    let upstream_direct_ref = match remote_tracking_branch.get().resolve() {
+        Ok(direct_ref) => direct_ref,
+        Err(error) => {
+            printer.log_general_branch_error(path, &branch_name, error.into());
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+    let Some(upstream_oid) = upstream_direct_ref.target() else {
+        printer.log_branch_without_commit(path, &remote_tracking_branch_fqrefname);
+        report.branches.push(branch_report);
+        return;
+    };
+
+    trace!(
+        "Walking {}..{} for branch {}",
+        branch_oid,
+        upstream_oid,
+        branch_name
+    );
+    let (ahead, behind) = match repo.graph_ahead_behind(branch_oid, upstream_oid) {
+        Ok(ahead_behind) => ahead_behind,
+        // The walk ran into commits that were cut off when the repository was cloned
+        Err(_) if context.is_shallow => {
+            printer.log_branch_unverifiable_in_shallow_repo(path, &branch_name);
+            branch_report.status = BranchStatus::Unverifiable;
+            report.branches.push(branch_report);
+            return;
+        }
+        Err(error) => {
+            printer.log_general_branch_error(path, &branch_name, error.into());
+            report.branches.push(branch_report);
+            return;
+        }
+    };
+    printer.log_branch_ancestry(
+        path,
+        &branch_name,
+        &remote_tracking_branch_fqrefname,
+        ahead,
+        behind,
+    );
+    branch_report.ahead = ahead;
+    branch_report.behind = behind;
+    if ahead > 0 {
+        // Either the local branch is ahead of the upstream, or it diverged
+        if behind == 0 && options.allow_ahead {
+            printer.log_allowed_ahead_branch(path, &branch_name, ahead);
+            branch_report.status = BranchStatus::AllowedAhead;
+        } else if behind == 0 {
+            printer.log_local_branch_ahead_of_upstream(path, &branch_name);
+            branch_report.status = BranchStatus::Ahead;
+        } else if context.is_shallow {
+            // Truncated history can hide a connection between the tips, but never invent one,
+            // so only this verdict is in doubt
+            printer.log_branch_unverifiable_in_shallow_repo(path, &branch_name);
+            branch_report.status = BranchStatus::Unverifiable;
+        } else {
+            // Where they forked tells a trivial divergence from a serious one
+            let merge_base = repo.merge_base(branch_oid, upstream_oid).ok();
+            printer.log_branch_diverged(path, &branch_name, ahead, behind, merge_base);
+            branch_report.status = BranchStatus::Diverged;
+        }
+        branch_report.last_commit = last_commit(repo, branch_oid);
+        log_last_commit(printer, path, &branch_report);
+        // Only a branch that's simply ahead could be pushed as it is
+        if behind == 0 && options.push_preview {
+            match push_preview(repo, &branch_name, branch_oid, upstream_oid) {
+                Ok((refspec, commits)) => {
+                    printer.log_push_preview(path, &branch_name, &refspec, &commits)
+                }
+                Err(error) => printer.log_general_branch_error(path, &branch_name, error.into()),
+            }
+        }
+        report.branches.push(branch_report);
+        return;
+    }
+    // Giving `behind` a severity asks for it to be reported just like --show-behind does
+    let show_behind = options.show_behind || options.severity_map.get(Category::Behind).is_some();
+    if behind > 0 && show_behind {
+        printer.log_branch_behind(path, &branch_name, behind);
+    }
+
+    // Local branch is the upstream or in its ancestry
+    printer.log_branch_is_synced(path, &branch_name);
+    branch_report.status = match behind {
+        0 => BranchStatus::UpToDate,
+        _ => BranchStatus::Behind,
+    };
+    report.branches.push(branch_report);
+}
+
+/// Only worked out for flagged branches, since clean ones don't need a decision
+fn last_commit(repo: &Repository, oid: git2::Oid) -> Option<LastCommit> {
+    let commit = repo
+        .find_commit(oid)
+        .inspect_err(|error| debug!("Couldn't read the tip commit {}: {}", oid, error))
+        .ok()?;
+    let committed = commit.committer().when();
+    let offset = FixedOffset::east_opt(committed.offset_minutes() * 60)?;
+    let author = String::from_utf8_lossy(commit.author().name_bytes()).into_owned();
+    Some(LastCommit {
+        committed_at: DateTime::from_timestamp(committed.seconds(), 0)?.with_timezone(&offset),
+        author,
+    })
+}
+
+/// When the repository was last worked in: the latest commit on any local branch, or the last
+/// change to the index, whichever is later. Edits that were never staged don't count.
+fn last_modified(repo: &Repository) -> Option<DateTime<Local>> {
+    let committed = local_branches(repo)
+        .inspect_err(|error| debug!("Couldn't list the local branches: {}", error))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|branch| branch.get().peel_to_commit().ok())
+        .filter_map(|commit| DateTime::from_timestamp(commit.committer().when().seconds(), 0));
+    let staged = fs::metadata(repo.path().join("index"))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::from);
+    committed
+        .chain(staged)
+        .max()
+        .map(|time| time.with_timezone(&Local))
+}
+
+fn log_last_commit(printer: &mut Printer, path: &Path, branch_report: &BranchReport) {
+    if let Some(last_commit) = &branch_report.last_commit {
+        printer.log_branch_last_commit(
+            path,
+            &branch_report.name,
+            branch_report.status,
+            last_commit,
+        );
+    }
+}
+
+/// Looks for branches on the remotes that no local branch tracks, e.g. a colleague's branch
+/// that's waiting for review. Branches already merged into a local branch are left out.
+fn check_remote_branches<'a>(
+    repo: &Repository,
+    remote_names: impl IntoIterator<Item = &'a str>,
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    let mut upstreams = Vec::new();
+    let mut local_oids = Vec::new();
+    for branch in repo.branches(Some(git2::BranchType::Local))? {
+        let (branch, _) = branch?;
+        local_oids.extend(branch.get().target());
+        if let Ok(upstream) = branch.upstream() {
+            upstreams.extend(upstream.get().name().map(str::to_owned));
+        }
+    }
+    for remote_name in remote_names {
+        for reference in repo.references_glob(&format!("refs/remotes/{}/*", remote_name))? {
+            let reference = reference?;
+            let (Some(refname), Some(oid)) = (reference.name(), reference.target()) else {
+                // Symbolic refs like `origin/HEAD` just point at another remote branch
+                continue;
+            };
+            if upstreams.iter().any(|upstream| upstream == refname) {
+                continue;
+            }
+            let merged = local_oids.iter().any(|&local_oid| {
+                local_oid == oid || repo.graph_descendant_of(local_oid, oid).unwrap_or(false)
+            });
+            if merged {
+                continue;
+            }
+            let branch = reference.shorthand().unwrap_or(refname).to_owned();
+            printer.log_untracked_remote_branch(path, &branch);
+            report
+                .findings
+                .push(Finding::UntrackedRemoteBranch { branch });
+        }
+    }
+    Ok(())
+}
+
+fn local_branches(repo: &Repository) -> Result<Vec<git2::Branch<'_>>, git2::Error> {
+    repo.branches(Some(git2::BranchType::Local))?
+        .map(|branch| branch.map(|(branch, _)| branch))
+        .collect()
+}
+
+/// Points out notes refs that no fetched remote has, since a plain `git push` leaves notes behind.
+/// A remote's notes can only be seen if they're fetched, with a refspec like
+/// `+refs/notes/*:refs/notes/origin/*`.
+fn check_notes(
+    repo: &Repository,
+    synced_remotes: &[&mut Remote],
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    let fetch_refspecs: Vec<_> = synced_remotes
+        .iter()
+        .flat_map(|remote| remote.refspecs())
+        .filter(|refspec| refspec.direction() == git2::Direction::Fetch)
+        .collect();
+    for reference in repo.references_glob("refs/notes/*")? {
+        let reference = reference?;
+        let (Some(refname), Some(oid)) = (reference.name(), reference.target()) else {
+            continue;
+        };
+        // The copies of the remotes' notes are what the local notes are checked against
+        if fetch_refspecs
+            .iter()
+            .any(|refspec| refspec.dst_matches(refname))
+        {
+            continue;
+        }
+        let pushed = fetch_refspecs
+            .iter()
+            .filter(|refspec| refspec.src_matches(refname))
+            .filter_map(|refspec| refspec.transform(refname).ok())
+            .filter_map(|remote_refname| repo.refname_to_id(remote_refname.as_str()?).ok())
+            .any(|remote_oid| {
+                remote_oid == oid || repo.graph_descendant_of(remote_oid, oid).unwrap_or(false)
+            });
+        if !pushed {
+            printer.log_notes_not_pushed(path, refname);
+            report.findings.push(Finding::NotesNotPushed {
+                notes_ref: refname.to_owned(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Points out tags whose commits aren't on any fetched remote. Whether the tag itself was pushed
+/// can't be told without asking the remote, but its commit is what would be lost.
+fn check_tags<'a>(
+    repo: &Repository,
+    remote_names: impl IntoIterator<Item = &'a str> + Clone,
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    for reference in repo.references_glob("refs/tags/*")? {
+        let reference = reference?;
+        let Some(tag_name) = reference.shorthand().map(str::to_owned) else {
+            continue;
+        };
+        // An annotated tag points at a tag object rather than at the commit, which is what
+        // remote-tracking branches can contain. Tags of trees or blobs aren't worth checking.
+        let Ok(commit) = reference.peel_to_commit() else {
+            continue;
+        };
+        if find_remote_containing_commit(repo, remote_names.clone(), commit.id())?.is_none() {
+            printer.log_tag_not_pushed(path, &tag_name, commit.id());
+            report
+                .findings
+                .push(Finding::TagNotPushed { tag: tag_name });
+        }
+    }
+    Ok(())
+}
+
+/// Resolves HEAD to the branch it points at. A detached HEAD is instead checked for whether its
+/// commit made it to a remote, since there is no branch to compare against an upstream.
+fn current_branch<'repo>(
+    repo: &'repo Repository,
+    synced_remotes: &[&mut Remote],
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<Vec<git2::Branch<'repo>>> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        // An unborn HEAD has no commits that could be lost
+        Err(error) if error.code() == git2::ErrorCode::UnbornBranch => return Ok(Vec::new()),
+        Err(error) => return Err(error.into()),
+    };
+    if head.is_branch() {
+        return Ok(vec![git2::Branch::wrap(head)]);
+    }
+
+    let head_oid = head.peel_to_commit()?.id();
+    let synced_remote_names = synced_remotes.iter().map(|remote| remote.name().unwrap());
+    match find_remote_containing_commit(repo, synced_remote_names, head_oid)? {
+        Some(remote_name) => printer.log_detached_head_pushed(path, head_oid, &remote_name),
+        None => {
+            printer.log_detached_head_not_pushed(path, head_oid);
+            report
+                .findings
+                .push(Finding::DetachedHeadNotPushed { oid: head_oid });
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// The directory of the repository that a linked worktree belongs to, falling back to the
+/// worktree's own git dir if it can't be worked out
+fn main_repository_path(repo: &Repository) -> PathBuf {
+    // The worktree's git dir has a `commondir` file pointing at the main repository's git dir
+    let git_dir = repo.path();
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim()),
+        Err(_) => return git_dir.to_path_buf(),
+    };
+    let common_dir = fs::canonicalize(&common_dir).unwrap_or(common_dir);
+    match common_dir.file_name() {
+        // The common dir of a non-bare repository is its `.git`
+        Some(name) if name == ".git" => common_dir.parent().unwrap_or(&common_dir).to_path_buf(),
+        _ => common_dir,
+    }
+}
+
+/// Finds a remote with some remote-tracking branch that contains `oid`
+fn find_remote_containing_commit<'a>(
+    repo: &Repository,
+    remote_names: impl IntoIterator<Item = &'a str>,
+    oid: git2::Oid,
+) -> Result<Option<String>> {
+    for remote_name in remote_names {
+        for reference in repo.references_glob(&format!("refs/remotes/{}/*", remote_name))? {
+            let Some(remote_oid) = reference?.target() else {
+                // Symbolic refs like `origin/HEAD` point at another branch that is checked anyway
+                continue;
+            };
+            if remote_oid == oid || repo.graph_descendant_of(remote_oid, oid)? {
+                return Ok(Some(remote_name.to_owned()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Finds a remote whose same-named branch contains the local branch's tip, and how many commits
+/// the local branch is behind it
+fn find_remote_containing_branch<'a>(
+    repo: &Repository,
+    remote_names: impl IntoIterator<Item = &'a str>,
+    branch: &git2::Branch,
+    branch_name: &str,
+) -> Option<(String, usize)> {
+    let branch_oid = branch.get().target()?;
+    remote_names.into_iter().find_map(|remote_name| {
+        let remote_oid = repo
+            .refname_to_id(&format!("refs/remotes/{}/{}", remote_name, branch_name))
+            .ok()?;
+        let (ahead, behind) = repo.graph_ahead_behind(branch_oid, remote_oid).ok()?;
+        (ahead == 0).then(|| (remote_name.to_owned(), behind))
+    })
+}
+
+/// Checks that `remote_name` has the branch at the same commit, for --compare-remote
+fn compare_with_remote(
+    repo: &Repository,
+    branch: &git2::Branch,
+    branch_name: &str,
+    remote_name: &str,
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) {
+    let Some(branch_oid) = branch.get().target() else {
+        return;
+    };
+    let remote_refname = format!("refs/remotes/{}/{}", remote_name, branch_name);
+    let Ok(remote_oid) = repo.refname_to_id(&remote_refname) else {
+        printer.log_branch_missing_from_remote(path, branch_name, remote_name);
+        report.findings.push(Finding::MissingFromRemote {
+            branch: branch_name.to_owned(),
+            remote: remote_name.to_owned(),
+        });
+        return;
+    };
+    if remote_oid == branch_oid {
+        printer.log_branch_matches_remote(path, branch_name, remote_name);
+        return;
+    }
+    match repo.graph_ahead_behind(branch_oid, remote_oid) {
+        Ok((ahead, behind)) => {
+            printer.log_branch_differs_from_remote(path, branch_name, remote_name, ahead, behind)
+        }
+        Err(error) => printer.log_general_branch_error(path, &remote_refname, error.into()),
+    }
+    report.findings.push(Finding::DiffersFromRemote {
+        branch: branch_name.to_owned(),
+        remote: remote_name.to_owned(),
+    });
+}
+
+enum FetchOutcome {
+    Fetched(Fetched),
+    Failed(git2::Error),
+    TimedOut(Duration),
+    /// Not fetched, since it was fetched this long ago, within the cooldown
+    CoolingDown(Duration),
+}
+
+struct Fetched {
+    /// The credentials the remote accepted, if it asked for any
+    auth_method: Option<AuthMethod>,
+    received_objects: usize,
+    received_bytes: usize,
+}
+
+/// Fetches each of `remotes`, up to `fetch_jobs` of them at a time
+fn fetch_remotes(
+    repo: &Repository,
+    remotes: &mut [Remote],
+    options: &CheckOptions,
+    path: &Path,
+    printer: &mut Printer,
+) -> Vec<FetchOutcome> {
+    if options.fetch_jobs <= 1 || remotes.len() <= 1 {
+        return remotes
+            .iter_mut()
+            .map(|remote| fetch_remote(repo, remote, options, path, printer))
+            .collect();
+    }
+
+    // `Remote` borrows the `Repository`, which isn't `Sync`, so each worker opens its own.
+    // Workers can't share the `Printer` either, so each remote's messages are buffered separately
+    // and merged back in remote order once all fetches are done.
+    let repo_path = repo.path();
+    let remote_names: Vec<&str> = remotes
+        .iter()
+        .map(|remote| remote.name().unwrap())
+        .collect();
+    let next_remote = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<(FetchOutcome, Printer)>>> =
+        remote_names.iter().map(|_| Mutex::new(None)).collect();
+    let template = &*printer;
+    thread::scope(|scope| {
+        for _ in 0..options.fetch_jobs.min(remotes.len()) {
+            scope.spawn(|| loop {
+                let index = next_remote.fetch_add(1, Ordering::Relaxed);
+                let Some(remote_name) = remote_names.get(index) else {
+                    break;
+                };
+                let mut remote_printer = template.buffer();
+                let outcome = Repository::open(repo_path)
+                    .and_then(|repo| {
+                        let mut remote = repo.find_remote(remote_name)?;
+                        Ok(fetch_remote(
+                            &repo,
+                            &mut remote,
+                            options,
+                            path,
+                            &mut remote_printer,
+                        ))
+                    })
+                    .unwrap_or_else(FetchOutcome::Failed);
+                *results[index].lock().unwrap() = Some((outcome, remote_printer));
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| {
+            let (outcome, mut remote_printer) = result.into_inner().unwrap().unwrap();
+            printer.append(&mut remote_printer);
+            outcome
+        })
+        .collect()
+}
+
+/// Fetches `remote`, retrying failures that look transient with an exponential backoff
+fn fetch_remote(
+    repo: &Repository,
+    remote: &mut Remote,
+    options: &CheckOptions,
+    path: &Path,
+    printer: &mut Printer,
+) -> FetchOutcome {
+    let remote_name = remote.name().unwrap().to_owned();
+    if !options.fetch_cooldown.is_zero() {
+        match options.fetch_cache.fetched_ago(repo.path(), &remote_name) {
+            Some(fetched_ago) if fetched_ago < options.fetch_cooldown => {
+                return FetchOutcome::CoolingDown(fetched_ago)
+            }
+            _ => {}
+        }
+    }
+    let mut attempt = 0;
+    loop {
+        match fetch_remote_once(repo, remote, options, path) {
+            FetchOutcome::Failed(error)
+                if attempt < options.fetch_retries && is_transient(&error) =>
+            {
+                attempt += 1;
+                let delay = FETCH_RETRY_BASE_DELAY * 2u32.pow(attempt - 1);
+                printer.log_remote_fetch_retry(
+                    path,
+                    remote.name().unwrap(),
+                    attempt,
+                    &error,
+                    delay,
+                );
+                thread::sleep(delay);
+            }
+            outcome => {
+                if let FetchOutcome::Fetched(_) = outcome {
+                    options.fetch_cache.record(repo.path(), &remote_name);
+                }
+                return outcome;
+            }
+        }
+    }
+}
+
+/// Network hiccups are worth retrying; auth failures and the like will just fail again
+fn is_transient(error: &git2::Error) -> bool {
+    error.code() != git2::ErrorCode::Auth
+        && matches!(
+            error.class(),
+            git2::ErrorClass::Net | git2::ErrorClass::Os | git2::ErrorClass::Http
+        )
+}
+
+/// Fetches `remote`, abandoning it if it takes longer than the configured timeout
+fn fetch_remote_once(
+    repo: &Repository,
+    remote: &mut Remote,
+    options: &CheckOptions,
+    path: &Path,
+) -> FetchOutcome {
+    let progress_path = options.show_fetch_progress.then_some(path);
+    // Waiting for a slot doesn't count towards the timeout, only the fetch itself does
+    let slot = options.fetch_limit.acquire();
+    let Some(timeout) = options.fetch_timeout else {
+        return match fetch(
+            repo,
+            remote,
+            &options.credentials,
+            options.proxy.as_deref(),
+            progress_path,
+            options.theme,
+        ) {
+            Ok(fetched) => FetchOutcome::Fetched(fetched),
+            Err(error) => FetchOutcome::Failed(error),
+        };
+    };
+
+    // libgit2's fetch is blocking and can't be cancelled while it waits on the network,
+    // so it runs on a worker thread that is simply left behind if it misses the deadline.
+    // `Remote` borrows the `Repository`, which isn't `Sync`, so the worker opens its own.
+    let (sender, receiver) = mpsc::channel();
+    let repo_path = repo.path().to_path_buf();
+    let remote_name = remote.name().unwrap().to_owned();
+    let credentials = Arc::clone(&options.credentials);
+    let proxy = options.proxy.clone();
+    let progress_path = progress_path.map(Path::to_path_buf);
+    let theme = options.theme;
+    thread::spawn(move || {
+        // A fetch left behind is still on the network, so it keeps its slot until it's done
+        let _slot = slot;
+        let result = Repository::open(repo_path).and_then(|repo| {
+            fetch(
+                &repo,
+                &mut repo.find_remote(&remote_name)?,
+                &credentials,
+                proxy.as_deref(),
+                progress_path.as_deref(),
+                theme,
+            )
+        });
+        // The receiver is gone if we timed out, in which case nobody cares about the result
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(Ok(fetched)) => FetchOutcome::Fetched(fetched),
+        Ok(Err(error)) => FetchOutcome::Failed(error),
+        Err(RecvTimeoutError::Timeout) => FetchOutcome::TimedOut(timeout),
+        Err(RecvTimeoutError::Disconnected) => {
+            FetchOutcome::Failed(git2::Error::from_str("The fetch worker thread panicked"))
+        }
+    }
+}
+
+/// Fetches `remote`, through `proxy` if given. With a `progress_path`, the transfer progress is
+/// written to stderr as it happens, since the `Printer` only writes once the whole entry is done.
+fn fetch(
+    repo: &Repository,
+    remote: &mut Remote,
+    credentials: &Credentials,
+    proxy: Option<&str>,
+    progress_path: Option<&Path>,
+    theme: Theme,
+) -> Result<Fetched, git2::Error> {
+    let remote_name = remote.name().unwrap_or_default().to_owned();
+    let remote_url = remote.url().unwrap_or_default().to_owned();
+    let auth_method = RefCell::new(None);
+    let git_config = repo.config().ok();
+    let mut remote_callbacks =
+        credentials.remote_callbacks(&remote_url, git_config.as_ref(), &auth_method);
+    if let Some(path) = progress_path {
+        let mut last_report = Instant::now();
+        remote_callbacks.transfer_progress(move |progress| {
+            if last_report.elapsed() >= FETCH_PROGRESS_INTERVAL {
+                last_report = Instant::now();
+                eprintln!(
+                    "{} {}",
+                    theme.info,
+                    Printer::msg_remote_fetch_progress(
+                        path,
+                        &remote_name,
+                        progress.received_objects(),
+                        progress.total_objects(),
+                        progress.received_bytes(),
+                    )
+                );
+            }
+            true
+        });
+    }
+    let mut proxy_options = git2::ProxyOptions::new();
+    match proxy {
+        Some(url) => proxy_options.url(url),
+        // Picks up http.proxy and the like, then the http(s)_proxy environment variables
+        None => proxy_options.auto(),
+    };
+    let mut fetch_opts = git2::FetchOptions::new();
+    fetch_opts.remote_callbacks(remote_callbacks);
+    fetch_opts.proxy_options(proxy_options);
+
+    debug!(
+        "Fetching remote {} from {}",
+        remote.name().unwrap_or_default(),
+        remote_url
+    );
+    let fetching = Instant::now();
+    let (received_objects, received_bytes) = match connect_remote(repo, &remote_url, credentials)? {
+        Some(mut aliased) => {
+            // The remote's own refspecs have to be passed along for the remote-tracking
+            // branches to be updated
+            let refspecs: Vec<String> = remote
+                .fetch_refspecs()?
+                .iter()
+                .flatten()
+                .map(str::to_owned)
+                .collect();
+            aliased.fetch(&refspecs, Some(&mut fetch_opts), None)?;
+            let stats = aliased.stats();
+            (stats.received_objects(), stats.received_bytes())
+        }
+        None => {
+            remote.fetch(&[] as &[&str], Some(&mut fetch_opts), None)?;
+            let stats = remote.stats();
+            (stats.received_objects(), stats.received_bytes())
+        }
+    };
+    drop(fetch_opts);
+    debug!(
+        "Fetched remote {} in {:?}: {} object(s), {} byte(s)",
+        remote.name().unwrap_or_default(),
+        fetching.elapsed(),
+        received_objects,
+        received_bytes
+    );
+    Ok(Fetched {
+        auth_method: auth_method.into_inner(),
+        received_objects,
+        received_bytes,
+    })
+}
+
+/// An anonymous remote for `remote_url` with the ssh config's `HostName` for its host, if it's an
+/// alias that libgit2 couldn't connect to itself
+fn connect_remote<'r>(
+    repo: &'r Repository,
+    remote_url: &str,
+    credentials: &Credentials,
+) -> Result<Option<Remote<'r>>, git2::Error> {
+    let Some(connect_url) = credentials.connect_url(remote_url) else {
+        return Ok(None);
+    };
+    debug!("Connecting to {} for {}", connect_url, remote_url);
+    repo.remote_anonymous(&connect_url).map(Some)
+}
+
+/// The refspec that would push `branch_name` to its upstream, and the commits that would be sent,
+/// newest first
+fn push_preview(
+    repo: &Repository,
+    branch_name: &str,
+    branch_oid: git2::Oid,
+    upstream_oid: git2::Oid,
+) -> Result<(String, Vec<(git2::Oid, String)>), git2::Error> {
+    let refspec = format!(
+        "refs/heads/{}:{}",
+        branch_name,
+        upstream_refname(repo, branch_name)
+    );
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(branch_oid)?;
+    revwalk.hide(upstream_oid)?;
+    let commits = revwalk
+        .map(|oid| {
+            let commit = repo.find_commit(oid?)?;
+            let summary = String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default());
+            Ok((commit.id(), summary.into_owned()))
+        })
+        .collect::<Result<_, git2::Error>>()?;
+    Ok((refspec, commits))
+}
+
+/// The name of the branch's upstream on its remote, e.g. `refs/heads/main`. Without a configured
+/// upstream, that's the same-named branch, which is what --preferred-remote compares against.
+fn upstream_refname(repo: &Repository, branch_name: &str) -> String {
+    repo.config()
+        .and_then(|config| config.get_string(&format!("branch.{}.merge", branch_name)))
+        .unwrap_or_else(|_| format!("refs/heads/{}", branch_name))
+}
+
+/// Pushes the local branch `branch_name` to its upstream on `remote_name`, with the same
+/// credentials and proxy as the fetches. Meant for branches found to be ahead, so the branch is
+/// expected to have an upstream, configured or through --preferred-remote.
+pub fn push_branch(
+    path: &Path,
+    branch_name: &str,
+    remote_name: &str,
+    options: &CheckOptions,
+) -> Result<(), git2::Error> {
+    let repo = Repository::open(path)?;
+    let local_refname = format!("refs/heads/{}", branch_name);
+    let upstream_refname = upstream_refname(&repo, branch_name);
+    let mut remote = repo.find_remote(remote_name)?;
+    let remote_url = remote.url().unwrap_or_default().to_owned();
+    let mut aliased = connect_remote(&repo, &remote_url, &options.credentials)?;
+    let remote = aliased.as_mut().unwrap_or(&mut remote);
+
+    let auth_method = RefCell::new(None);
+    let rejection = RefCell::new(None);
+    let git_config = repo.config().ok();
+    let mut remote_callbacks =
+        options
+            .credentials
+            .remote_callbacks(&remote_url, git_config.as_ref(), &auth_method);
+    // A rejected update (e.g. not a fast-forward) doesn't fail the push itself
+    remote_callbacks.push_update_reference(|_, status| {
+        if let Some(status) = status {
+            rejection.replace(Some(status.to_owned()));
+        }
+        Ok(())
+    });
+    let mut proxy_options = git2::ProxyOptions::new();
+    match options.proxy.as_deref() {
+        Some(url) => proxy_options.url(url),
+        None => proxy_options.auto(),
+    };
+    let mut push_options = git2::PushOptions::new();
+    push_options.remote_callbacks(remote_callbacks);
+    push_options.proxy_options(proxy_options);
+    let refspec = format!("{}:{}", local_refname, upstream_refname);
+    remote.push(&[refspec.as_str()], Some(&mut push_options))?;
+    drop(push_options);
+    match rejection.into_inner() {
+        Some(status) => Err(git2::Error::from_str(&format!(
+            "The remote rejected the push: {}",
+            status
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checks out `branch_name`, which HEAD is detached at the tip of, so the working tree stays as it is
+pub fn attach_head(path: &Path, branch_name: &str) -> Result<(), git2::Error> {
+    let repo = Repository::open(path)?;
+    let branch = repo.find_branch(branch_name, git2::BranchType::Local)?;
+    if branch.get().target() != repo.head()?.target() {
+        return Err(git2::Error::from_str(
+            "HEAD is no longer at the tip of the branch",
+        ));
+    }
+    repo.set_head(&format!("refs/heads/{}", branch_name))
+}
+
+/// Creates the branch `branch_name` at the detached HEAD and checks it out, so the commits there
+/// can't be lost to garbage collection
+pub fn create_branch_at_head(path: &Path, branch_name: &str) -> Result<(), git2::Error> {
+    let repo = Repository::open(path)?;
+    let head = repo.head()?.peel_to_commit()?;
+    repo.branch(branch_name, &head, false)?;
+    repo.set_head(&format!("refs/heads/{}", branch_name))
+}
+
+/// Opens the repository at `path`, telling an entry that isn't one apart from one libgit2 fails on
+fn open_repo(path: &Path) -> Result<Repository> {
+    Repository::open(path).map_err(|error| match error.code() {
+        git2::ErrorCode::NotFound => CheckError::NotARepository(error),
+        _ => CheckError::Git(error),
+    })
+}
+
+/// libgit2 doesn't say why it couldn't open a repository, so look for ourselves
+fn is_permission_denied(path: &Path) -> bool {
+    [path.to_path_buf(), path.join(".git")].iter().any(|path| {
+        matches!(fs::read_dir(path), Err(error) if error.kind() == io::ErrorKind::PermissionDenied)
+    })
+}
+
+/// Names the operation a repository is in the middle of, if any
+fn operation_in_progress(state: git2::RepositoryState) -> Option<&'static str> {
+    use git2::RepositoryState;
+    Some(match state {
+        RepositoryState::Clean => return None,
+        RepositoryState::Merge => "merge",
+        RepositoryState::Revert | RepositoryState::RevertSequence => "revert",
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => "cherry-pick",
+        RepositoryState::Bisect => "bisect",
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge => "rebase",
+        RepositoryState::ApplyMailbox => "am",
+        RepositoryState::ApplyMailboxOrRebase => "am or rebase",
+    })
+}
+
+/// Looks for work that only exists in the working tree
+fn check_working_tree(
+    repo: &Repository,
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    let mut status_opts = git2::StatusOptions::new();
+    status_opts
+        .include_untracked(true)
+        .recurse_untracked_dirs(false)
+        .exclude_submodules(true);
+    let statuses = repo.statuses(Some(&mut status_opts))?;
+    // A merge left half-resolved is broken rather than just unfinished, so it's told apart
+    let (conflicted, changed) = statuses
+        .iter()
+        .filter(|status_entry| !status_entry.status().is_ignored())
+        .partition::<Vec<_>, _>(|status_entry| status_entry.status().is_conflicted());
+    if !conflicted.is_empty() {
+        let paths: Vec<String> = conflicted
+            .iter()
+            .map(|status_entry| String::from_utf8_lossy(status_entry.path_bytes()).into_owned())
+            .collect();
+        printer.log_unmerged_paths(path, &paths);
+        report.findings.push(Finding::UnmergedPaths { paths });
+    }
+    let (untracked, uncommitted) = changed
+        .into_iter()
+        .partition::<Vec<_>, _>(|status_entry| status_entry.status().is_wt_new());
+    if !uncommitted.is_empty() {
+        printer.log_uncommitted_changes(path, uncommitted.len());
+        report.findings.push(Finding::UncommittedChanges {
+            count: uncommitted.len(),
+        });
+    }
+    if !untracked.is_empty() {
+        printer.log_untracked_files(path, untracked.len());
+        report.findings.push(Finding::UntrackedFiles {
+            count: untracked.len(),
+        });
+    }
+    // Each stash is an entry in the stash ref's reflog
+    let stashes = repo.reflog("refs/stash")?.len();
+    if stashes > 0 {
+        printer.log_stashes(path, stashes);
+        report.findings.push(Finding::Stashes { count: stashes });
+    }
+    Ok(())
+}
+
+/// Looks for a detached HEAD, which unless it's at a branch's tip may hold commits no branch has
+fn check_detached_head(
+    repo: &Repository,
+    path: &Path,
+    printer: &mut Printer,
+    report: &mut RepoReport,
+) -> Result<()> {
+    if repo.head_detached()? {
+        let head_oid = repo.head()?.peel_to_commit()?.id();
+        // Sitting on a branch's tip, as after checking out its latest commit, loses nothing
+        let tip_of = local_branches(repo)?.into_iter().find_map(|branch| {
+            (branch.get().target() == Some(head_oid))
+                .then(|| branch.name().ok().flatten().map(str::to_owned))
+                .flatten()
+        });
+        match tip_of {
+            Some(branch) => {
+                printer.log_detached_at_branch_tip(path, head_oid, &branch);
+                report.findings.push(Finding::DetachedAtBranchTip {
+                    oid: head_oid,
+                    branch,
+                });
+            }
+            None => {
+                printer.log_detached_head(path, head_oid);
+                report
+                    .findings
+                    .push(Finding::DetachedHead { oid: head_oid });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1254 @@
+use super::*;
+use crate::printer::Verbosity;
+use crate::report::{Severity, SeverityLevel};
+use crate::test_support::{commit, push, Fixture};
+use std::fs;
+
+fn check(fixture: &Fixture) -> RepoReport {
+    check_with(fixture, &fixture.options())
+}
+
+fn check_with(fixture: &Fixture, options: &CheckOptions) -> RepoReport {
+    let mut printer = Printer::new(Verbosity::Entries);
+    let report = check_repo(&fixture.local_path(), options, &mut printer).unwrap();
+    printer.take_texts();
+    report
+}
+
+#[test]
+fn synced_branch() {
+    let fixture = Fixture::new();
+    let report = check(&fixture);
+    assert_eq!(report.findings, vec![]);
+    assert_eq!(
+        report.branch("main"),
+        Some(&BranchReport {
+            name: "main".to_owned(),
+            upstream: Some("origin/main".to_owned()),
+            remote: Some("origin".to_owned()),
+            status: BranchStatus::UpToDate,
+            ahead: 0,
+            behind: 0,
+            last_commit: None,
+        })
+    );
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn behind_branch_is_safe() {
+    let fixture = Fixture::new();
+    fixture.push_from_elsewhere("upstream work");
+    let report = check(&fixture);
+    assert!(!report.has_problems());
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::Behind);
+    assert_eq!((main.ahead, main.behind), (0, 1));
+}
+
+#[test]
+fn behind_branch_is_only_reported_on_request() {
+    let fixture = Fixture::new();
+    fixture.push_from_elsewhere("upstream work");
+    let behind_messages = |options: &CheckOptions| {
+        let mut printer = Printer::new(Verbosity::Normal);
+        check_repo(&fixture.local_path(), options, &mut printer).unwrap();
+        printer
+            .take_texts()
+            .into_iter()
+            .filter(|text| text.contains("behind its upstream by 1 commit(s)"))
+            .count()
+    };
+    assert_eq!(behind_messages(&fixture.options()), 0);
+
+    let mut options = fixture.options();
+    options.show_behind = true;
+    assert_eq!(behind_messages(&options), 1);
+}
+
+#[test]
+fn untracked_remote_branches_are_listed_unless_merged() {
+    let fixture = Fixture::new();
+    let elsewhere = git2::Repository::clone(
+        fixture.remote.path().to_str().unwrap(),
+        fixture.dir.path().join("colleague"),
+    )
+    .unwrap();
+    let head = elsewhere.head().unwrap().peel_to_commit().unwrap();
+    elsewhere.branch("merged", &head, false).unwrap();
+    push(&elsewhere, "origin", "merged");
+    let review = elsewhere.branch("review", &head, false).unwrap();
+    elsewhere.set_head(review.get().name().unwrap()).unwrap();
+    commit(&elsewhere, "needs review");
+    push(&elsewhere, "origin", "review");
+
+    assert_eq!(check(&fixture).findings, vec![]);
+    let mut options = fixture.options();
+    options.show_unfetched_remote_branches = true;
+    assert_eq!(
+        check_with(&fixture, &options).findings,
+        vec![Finding::UntrackedRemoteBranch {
+            branch: "origin/review".to_owned()
+        }]
+    );
+}
+
+#[test]
+fn ahead_branch() {
+    let fixture = Fixture::new();
+    commit(&fixture.local, "local work");
+    commit(&fixture.local, "more local work");
+    let report = check(&fixture);
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::Ahead);
+    assert_eq!((main.ahead, main.behind), (2, 0));
+}
+
+#[test]
+fn flagged_branches_have_their_last_commit() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let report = check(&fixture);
+    let last_commit = report.branch("feature").unwrap().last_commit.as_ref();
+    assert_eq!(last_commit.unwrap().author, "check-gits");
+    assert_eq!(
+        last_commit.unwrap().committed_at.timestamp(),
+        head.committer().when().seconds()
+    );
+    assert_eq!(report.branch("main").unwrap().last_commit, None);
+}
+
+#[test]
+fn pushing_an_ahead_branch_syncs_it() {
+    let fixture = Fixture::new();
+    commit(&fixture.local, "local work");
+    push_branch(&fixture.local_path(), "main", "origin", &fixture.options()).unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn push_preview_lists_unpushed_commits() {
+    let fixture = Fixture::new();
+    let first = commit(&fixture.local, "local work");
+    let second = commit(&fixture.local, "more local work");
+    let mut options = fixture.options();
+    options.push_preview = true;
+    let mut printer = Printer::new(Verbosity::Normal);
+    check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+    let texts = printer.take_texts();
+    let preview: Vec<_> = texts
+        .iter()
+        .find(|text| text.contains("would send 2 commit(s) with refs/heads/main:refs/heads/main"))
+        .unwrap()
+        .lines()
+        .skip(1)
+        .map(str::to_owned)
+        .collect();
+    assert_eq!(
+        preview,
+        [
+            format!("    {:.7} more local work", second),
+            format!("    {:.7} local work", first),
+        ]
+    );
+}
+
+#[test]
+fn preferred_remote_branches_can_be_pushed() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    push(&fixture.local, "origin", "feature");
+    fixture.local.set_head("refs/heads/feature").unwrap();
+    commit(&fixture.local, "feature work");
+    let mut options = fixture.options();
+    options.preferred_remote = Some("origin".to_owned());
+    options.push_preview = true;
+    let mut printer = Printer::new(Verbosity::Normal);
+    let report = check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+    let feature = report.branch("feature").unwrap();
+    assert_eq!(feature.status, BranchStatus::Ahead);
+    assert!(printer.take_texts().iter().any(|text| {
+        text.contains("would send 1 commit(s) with refs/heads/feature:refs/heads/feature")
+    }));
+
+    push_branch(&fixture.local_path(), "feature", "origin", &options).unwrap();
+    assert_eq!(
+        check_with(&fixture, &options)
+            .branch("feature")
+            .unwrap()
+            .status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn diverged_branch() {
+    let fixture = Fixture::new();
+    let fork = fixture.local.head().unwrap().target().unwrap();
+    fixture.push_from_elsewhere("upstream work");
+    commit(&fixture.local, "local work");
+    let mut printer = Printer::new(Verbosity::Normal);
+    let report = check_repo(&fixture.local_path(), &fixture.options(), &mut printer).unwrap();
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::Diverged);
+    assert_eq!((main.ahead, main.behind), (1, 1));
+    let diverged = format!(
+        "🚨 {}: Local branch main has diverged from the upstream, 1 commit(s) ahead and 1 behind since they forked at {:.7}",
+        fixture.local_path().display(),
+        fork
+    );
+    assert!(printer.take_texts().contains(&diverged));
+}
+
+#[test]
+fn allow_ahead_only_downgrades_ahead() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.allow_ahead = true;
+    commit(&fixture.local, "local work");
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::AllowedAhead
+    );
+    assert!(!report.has_problems());
+
+    fixture.push_from_elsewhere("upstream work");
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::Diverged
+    );
+}
+
+#[test]
+fn severity_map_remaps_and_ignores() {
+    let fixture = Fixture::new();
+    commit(&fixture.local, "local work");
+    fs::write(fixture.local_path().join("scratch.txt"), "notes").unwrap();
+    let mut options = fixture.options();
+    options
+        .severity_map
+        .set(Category::Ahead, SeverityLevel::Warn);
+    options
+        .severity_map
+        .set(Category::Untracked, SeverityLevel::Ignore);
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.findings, vec![]);
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::Ahead);
+    assert_eq!(report.branch_severity(main), Severity::Warning);
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn problems_are_described_once_each() {
+    let fixture = Fixture::new();
+    commit(&fixture.local, "local work");
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    fs::write(fixture.local_path().join("notes.txt"), "draft").unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.problems(),
+        vec![
+            "1 untracked file(s)",
+            "branch feature no upstream",
+            "branch main ahead of upstream",
+        ]
+    );
+}
+
+#[test]
+fn branch_without_upstream() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.branch("feature").unwrap().status,
+        BranchStatus::NoUpstream
+    );
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn preferred_remote_stands_in_for_missing_upstream() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    push(&fixture.local, "origin", "feature");
+    assert_eq!(
+        check(&fixture).branch("feature").unwrap().status,
+        BranchStatus::NoUpstream
+    );
+
+    let mut options = fixture.options();
+    options.preferred_remote = Some("origin".to_owned());
+    let report = check_with(&fixture, &options);
+    let feature = report.branch("feature").unwrap();
+    assert_eq!(feature.status, BranchStatus::UpToDate);
+    assert_eq!(feature.upstream.as_deref(), Some("origin/feature"));
+}
+
+#[test]
+fn checked_out_branch_without_upstream_stands_out() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    fixture.local.set_head("refs/heads/feature").unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![Finding::HeadBranchNoUpstream {
+            branch: "feature".to_owned()
+        }]
+    );
+    let feature = report.branch("feature").unwrap();
+    assert_eq!(feature.status, BranchStatus::NoUpstream);
+    assert!(report.has_problems());
+
+    let mut options = fixture.options();
+    options
+        .severity_map
+        .set(Category::NoUpstream, SeverityLevel::Ok);
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.severity(), Severity::Warning);
+}
+
+#[test]
+fn ignoring_no_upstream_covers_the_checked_out_branch() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    fixture.local.set_head("refs/heads/feature").unwrap();
+    let mut options = fixture.options();
+    options
+        .severity_map
+        .set(Category::NoUpstream, SeverityLevel::Ignore);
+    let mut printer =
+        Printer::new(Verbosity::Normal).with_severity_map(options.severity_map.clone());
+    let report = check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+    assert_eq!(report.findings, vec![]);
+    assert!(report.branch("feature").is_none());
+    assert!(!report.has_problems());
+    assert!(printer
+        .take_texts()
+        .iter()
+        .all(|text| !text.contains("feature")));
+}
+
+#[test]
+fn warning_about_no_upstream_covers_the_checked_out_branch() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    fixture.local.set_head("refs/heads/feature").unwrap();
+    let mut options = fixture.options();
+    options
+        .severity_map
+        .set(Category::NoUpstream, SeverityLevel::Warn);
+    let report = check_with(&fixture, &options);
+    let finding = Finding::HeadBranchNoUpstream {
+        branch: "feature".to_owned(),
+    };
+    assert_eq!(report.findings, vec![finding.clone()]);
+    assert_eq!(report.finding_severity(&finding), Severity::Warning);
+    let feature = report.branch("feature").unwrap();
+    assert_eq!(report.branch_severity(feature), Severity::Warning);
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn dirty_working_tree() {
+    let fixture = Fixture::new();
+    let workdir = fixture.local_path();
+    fs::write(workdir.join("initial.txt"), "modified").unwrap();
+    fs::write(workdir.join("scratch.txt"), "untracked").unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![
+            Finding::UncommittedChanges { count: 1 },
+            Finding::UntrackedFiles { count: 1 },
+        ]
+    );
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn stashes_are_reported() {
+    let fixture = Fixture::new();
+    let workdir = fixture.local_path();
+    fs::write(workdir.join("initial.txt"), "modified").unwrap();
+    let mut repo = Repository::open(&workdir).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    repo.stash_save(&signature, "wip", None).unwrap();
+    let report = check(&fixture);
+    assert_eq!(report.findings, vec![Finding::Stashes { count: 1 }]);
+    assert_eq!(report.severity(), Severity::Warning);
+
+    let mut options = fixture.options();
+    options
+        .severity_map
+        .set(Category::Stash, SeverityLevel::Ignore);
+    assert_eq!(check_with(&fixture, &options).findings, vec![]);
+}
+
+#[test]
+fn working_tree_checks_can_be_skipped() {
+    let fixture = Fixture::new();
+    fs::write(fixture.local_path().join("initial.txt"), "modified").unwrap();
+    let mut options = fixture.options();
+    options.verify_working_tree = false;
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.findings, vec![]);
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn skipping_the_working_tree_still_reports_a_detached_head() {
+    let fixture = Fixture::new();
+    let oid = fixture.local.head().unwrap().target().unwrap();
+    fixture.local.set_head_detached(oid).unwrap();
+    let unpushed = commit(&fixture.local, "detached work");
+    let mut options = fixture.options();
+    options.verify_working_tree = false;
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.findings,
+        vec![Finding::DetachedHead { oid: unpushed }]
+    );
+}
+
+#[test]
+fn notes_are_checked_against_the_fetched_ones() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().target().unwrap();
+    let signature = git2::Signature::now("check-gits", "check-gits@example.com").unwrap();
+    fixture
+        .local
+        .note(&signature, &signature, None, head, "reviewed", false)
+        .unwrap();
+    let mut options = fixture.options();
+    options.check_notes = true;
+    assert_eq!(
+        check_with(&fixture, &options).findings,
+        vec![Finding::NotesNotPushed {
+            notes_ref: "refs/notes/commits".to_owned()
+        }]
+    );
+
+    fixture
+        .local
+        .remote_add_fetch("origin", "+refs/notes/*:refs/notes/origin/*")
+        .unwrap();
+    fixture
+        .local
+        .find_remote("origin")
+        .unwrap()
+        .push(&["refs/notes/commits:refs/notes/commits"], None)
+        .unwrap();
+    assert_eq!(check_with(&fixture, &options).findings, vec![]);
+}
+
+#[test]
+fn annotated_and_lightweight_tags_are_checked_by_their_commits() {
+    let fixture = Fixture::new();
+    let signature = git2::Signature::now("check-gits", "check-gits@example.com").unwrap();
+    let pushed = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture
+        .local
+        .tag("v1.0", pushed.as_object(), &signature, "released", false)
+        .unwrap();
+    let unpushed = commit(&fixture.local, "unreleased work");
+    let unpushed = fixture.local.find_object(unpushed, None).unwrap();
+    fixture
+        .local
+        .tag("v1.1", &unpushed, &signature, "about to release", false)
+        .unwrap();
+    fixture
+        .local
+        .tag_lightweight("wip", &unpushed, false)
+        .unwrap();
+    let mut options = fixture.options();
+    options.check_tags = true;
+    let tags_not_pushed = |report: RepoReport| -> Vec<Finding> {
+        report
+            .findings
+            .into_iter()
+            .filter(|finding| matches!(finding, Finding::TagNotPushed { .. }))
+            .collect()
+    };
+    assert_eq!(
+        tags_not_pushed(check_with(&fixture, &options)),
+        vec![
+            Finding::TagNotPushed {
+                tag: "v1.1".to_owned()
+            },
+            Finding::TagNotPushed {
+                tag: "wip".to_owned()
+            },
+        ]
+    );
+
+    push(&fixture.local, "origin", "main");
+    assert_eq!(tags_not_pushed(check_with(&fixture, &options)), vec![]);
+}
+
+#[test]
+fn default_branch_must_be_here_and_in_sync() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.check_default_branch = true;
+    assert_eq!(check_with(&fixture, &options).findings, vec![]);
+
+    fixture.push_from_elsewhere("collaborator's work");
+    assert_eq!(
+        check_with(&fixture, &options).findings,
+        vec![Finding::DefaultBranchNotSynced {
+            branch: "main".to_owned(),
+            remote: "origin".to_owned(),
+            ahead: 0,
+            behind: 1,
+        }]
+    );
+
+    // origin/HEAD, as set by a clone, names the default branch over main and master
+    let head = fixture.local.head().unwrap().target().unwrap();
+    fixture
+        .local
+        .reference("refs/remotes/origin/trunk", head, false, "test")
+        .unwrap();
+    fixture
+        .local
+        .reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/trunk",
+            false,
+            "test",
+        )
+        .unwrap();
+    assert_eq!(
+        check_with(&fixture, &options).findings,
+        vec![Finding::DefaultBranchMissing {
+            branch: "trunk".to_owned(),
+            remote: "origin".to_owned(),
+        }]
+    );
+}
+
+#[test]
+fn detached_head() {
+    let fixture = Fixture::new();
+    let oid = fixture.local.head().unwrap().target().unwrap();
+    fixture.local.set_head_detached(oid).unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![Finding::DetachedAtBranchTip {
+            oid,
+            branch: "main".to_owned()
+        }]
+    );
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+
+    attach_head(&fixture.local_path(), "main").unwrap();
+    assert!(!fixture.local.head_detached().unwrap());
+    assert_eq!(check(&fixture).findings, vec![]);
+}
+
+#[test]
+fn detached_head_off_every_branch_gets_a_new_one() {
+    let fixture = Fixture::new();
+    let oid = fixture.local.head().unwrap().target().unwrap();
+    fixture.local.set_head_detached(oid).unwrap();
+    let detached = commit(&fixture.local, "detached work");
+    let report = check(&fixture);
+    assert_eq!(report.findings[0], Finding::DetachedHead { oid: detached });
+    assert!(attach_head(&fixture.local_path(), "main").is_err());
+
+    create_branch_at_head(&fixture.local_path(), "rescued").unwrap();
+    assert_eq!(fixture.local.head().unwrap().shorthand(), Some("rescued"));
+    let report = check(&fixture);
+    assert!(!report.findings.iter().any(|finding| matches!(
+        finding,
+        Finding::DetachedHead { .. } | Finding::DetachedAtBranchTip { .. }
+    )));
+    assert_eq!(
+        report.branch("rescued").unwrap().status,
+        BranchStatus::NoUpstream
+    );
+}
+
+#[test]
+fn unqualified_remote_is_not_fetched() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.qualifying_url_prefixes = vec!["https://github.com/".to_owned()];
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.findings,
+        vec![Finding::UnqualifiedRemote {
+            remote: "origin".to_owned()
+        }]
+    );
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::RemoteNotQualifying
+    );
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn bare_repository_only_has_its_branches_checked() {
+    let fixture = Fixture::new();
+    let mut printer = Printer::new(Verbosity::Entries);
+    let report = check_repo(fixture.remote.path(), &fixture.options(), &mut printer).unwrap();
+    assert!(printer
+        .take_texts()
+        .iter()
+        .any(|text| text.contains("This is a bare repo")));
+    assert_eq!(report.findings, vec![Finding::NoRemotes]);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::NoUpstream
+    );
+}
+
+#[test]
+fn linked_worktree_only_checks_its_own_branch() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let feature = fixture.local.find_reference("refs/heads/feature").unwrap();
+    let worktree_path = fixture.dir.path().join("feature-worktree");
+    fixture
+        .local
+        .worktree(
+            "feature",
+            &worktree_path,
+            Some(git2::WorktreeAddOptions::new().reference(Some(&feature))),
+        )
+        .unwrap();
+    let mut printer = Printer::new(Verbosity::Entries);
+    let report = check_repo(&worktree_path, &fixture.options(), &mut printer).unwrap();
+    let texts = printer.take_texts();
+    assert!(texts.iter().any(|text| text.contains("linked worktree of")));
+    let branches: Vec<_> = report
+        .branches
+        .iter()
+        .map(|branch| branch.name.as_str())
+        .collect();
+    assert_eq!(branches, vec!["feature"]);
+}
+
+#[test]
+fn not_a_repository() {
+    let fixture = Fixture::new();
+    let plain_dir = fixture.dir.path().join("plain");
+    fs::create_dir(&plain_dir).unwrap();
+    let mut printer = Printer::new(Verbosity::Entries);
+    let report = check_repo(&plain_dir, &fixture.options(), &mut printer).unwrap();
+    printer.take_texts();
+    assert_eq!(report.findings, vec![Finding::NotARepository]);
+    assert!(report.branches.is_empty());
+}
+
+#[test]
+fn unreadable_repository_is_not_mistaken_for_a_plain_directory() {
+    let fixture = Fixture::new();
+    fs::write(fixture.local.path().join("config"), "[broken").unwrap();
+    let mut printer = Printer::new(Verbosity::Entries);
+    let result = check_repo(&fixture.local_path(), &fixture.options(), &mut printer);
+    assert!(matches!(result, Err(CheckError::Git(_))));
+}
+
+#[test]
+fn qualifying_remotes_are_fetched() {
+    let fixture = Fixture::new();
+    let pushed = fixture.push_from_elsewhere("upstream work");
+    check(&fixture);
+    let upstream = fixture
+        .local
+        .find_reference("refs/remotes/origin/main")
+        .unwrap();
+    assert_eq!(upstream.target(), Some(pushed));
+}
+
+#[test]
+fn verbose_fetches_say_how_they_authenticated() {
+    let fixture = Fixture::new();
+    let mut printer = Printer::new(Verbosity::Entries);
+    check_repo(&fixture.local_path(), &fixture.options(), &mut printer).unwrap();
+    let no_credentials = format!(
+        "📝 {}: Remote origin didn't ask for credentials",
+        fixture.local_path().display()
+    );
+    assert!(printer.take_texts().contains(&no_credentials));
+}
+
+#[test]
+fn remotes_are_fetched_concurrently() {
+    let fixture = Fixture::new();
+    for name in ["mirror-a", "mirror-b", "mirror-c"] {
+        fixture.add_remote(name);
+        push(&fixture.local, name, "main");
+    }
+    let mut options = fixture.options();
+    options.fetch_jobs = 2;
+    let mut printer = Printer::new(Verbosity::Entries);
+    let report = check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+    assert_eq!(report.findings, vec![]);
+    // Messages come out in remote order, whichever fetch finished first
+    let synced: Vec<_> = printer
+        .take_texts()
+        .into_iter()
+        .filter_map(|text| {
+            let (_, rest) = text.split_once("Synced remote ")?;
+            Some(rest.split(' ').next()?.to_owned())
+        })
+        .collect();
+    assert_eq!(synced, vec!["mirror-a", "mirror-b", "mirror-c", "origin"]);
+}
+
+#[test]
+fn branches_checked_concurrently_report_in_branch_order() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    for name in ["feature-a", "feature-b", "feature-c"] {
+        fixture
+            .local
+            .branch(name, &head, false)
+            .unwrap()
+            .set_upstream(Some("origin/main"))
+            .unwrap();
+    }
+    commit(&fixture.local, "unpushed work");
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("wip", &head, false).unwrap();
+
+    let check_with_jobs = |branch_jobs| {
+        let mut options = fixture.options();
+        options.branch_jobs = branch_jobs;
+        let mut printer = Printer::new(Verbosity::Branches);
+        let report = check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+        (report, printer.take_texts())
+    };
+    let (report, texts) = check_with_jobs(3);
+    let statuses: Vec<_> = report
+        .branches
+        .iter()
+        .map(|branch| (branch.name.as_str(), branch.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("feature-a", BranchStatus::UpToDate),
+            ("feature-b", BranchStatus::UpToDate),
+            ("feature-c", BranchStatus::UpToDate),
+            ("main", BranchStatus::Ahead),
+            ("wip", BranchStatus::NoUpstream),
+        ]
+    );
+    let (sequential_report, sequential_texts) = check_with_jobs(1);
+    assert_eq!(report.findings, sequential_report.findings);
+    assert_eq!(report.branches, sequential_report.branches);
+    assert_eq!(texts, sequential_texts);
+}
+
+#[test]
+fn fetch_timeout_abandons_hung_remote() {
+    let fixture = Fixture::new();
+    // Accepts connections (via the kernel backlog) but never answers, like an unreachable host
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("git://{}/remote.git", listener.local_addr().unwrap());
+    fixture.local.remote_set_url("origin", &url).unwrap();
+    let mut options = fixture.options();
+    options.qualifying_url_prefixes = vec![url];
+    options.fetch_timeout = Some(Duration::from_millis(500));
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.findings,
+        vec![Finding::RemoteFetchTimedOut {
+            remote: "origin".to_owned()
+        }]
+    );
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::RemoteNotFetched
+    );
+}
+
+#[test]
+fn only_network_errors_are_transient() {
+    use git2::{Error, ErrorClass, ErrorCode};
+    let network = Error::new(ErrorCode::GenericError, ErrorClass::Net, "connection reset");
+    let auth = Error::new(ErrorCode::Auth, ErrorClass::Http, "401 Unauthorized");
+    let ssh = Error::new(ErrorCode::Auth, ErrorClass::Ssh, "authentication failed");
+    let missing = Error::new(ErrorCode::NotFound, ErrorClass::Repository, "not found");
+    assert!(is_transient(&network));
+    assert!(!is_transient(&auth));
+    assert!(!is_transient(&ssh));
+    assert!(!is_transient(&missing));
+}
+
+#[test]
+fn repository_without_remotes() {
+    let fixture = Fixture::new();
+    fixture.local.remote_delete("origin").unwrap();
+    let report = check(&fixture);
+    assert_eq!(report.findings, vec![Finding::NoRemotes]);
+    assert!(report.has_problems());
+}
+
+/// Linux allows any bytes but `/` and NUL in names; other platforms may insist on valid UTF-8
+#[cfg(target_os = "linux")]
+#[test]
+fn non_utf8_directory_name() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    let fixture = Fixture::new();
+    let path = fixture.dir.path().join(OsStr::from_bytes(b"caf\xe9"));
+    git2::Repository::clone(fixture.remote.path().to_str().unwrap(), &path).unwrap();
+    let mut printer = Printer::new(Verbosity::Entries).with_show_synced(true);
+    let report = check_repo(&path, &fixture.options(), &mut printer).unwrap();
+    assert_eq!(report.path, path);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+    let synced = format!(
+        "✅ {}: Local branch main is synced with the remote",
+        fixture.dir.path().join("caf\u{FFFD}").display()
+    );
+    assert!(printer.take_texts().contains(&synced));
+}
+
+#[test]
+fn corrupt_refs_fail_only_this_repository() {
+    let fixture = Fixture::new();
+    fs::write(fixture.local.path().join("packed-refs"), "not a ref\n").unwrap();
+    let report = check(&fixture);
+    assert!(matches!(
+        report.findings.last(),
+        Some(Finding::CheckFailed { message }) if message.starts_with("couldn't list the local branches")
+    ));
+}
+
+#[test]
+fn repositories_not_modified_lately_are_skipped() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.modified_since = Some(ModifiedSince::Ago(Duration::from_secs(60 * 60)));
+    assert_eq!(check_with(&fixture, &options).branches.len(), 1);
+
+    let tomorrow = Local::now() + chrono::Duration::days(1);
+    options.modified_since = Some(ModifiedSince::At(tomorrow));
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.findings, vec![Finding::NotModifiedSince]);
+    assert_eq!(report.branches, vec![]);
+}
+
+#[test]
+fn modified_since_takes_durations_and_dates() {
+    assert_eq!(
+        ModifiedSince::parse("7d"),
+        Ok(ModifiedSince::Ago(Duration::from_secs(7 * 24 * 60 * 60)))
+    );
+    assert_eq!(
+        ModifiedSince::parse("90s"),
+        Ok(ModifiedSince::Ago(Duration::from_secs(90)))
+    );
+    let ModifiedSince::At(start_of_day) = ModifiedSince::parse("2024-05-01").unwrap() else {
+        panic!("a date should be a point in time");
+    };
+    assert_eq!(
+        start_of_day.format("%F %T").to_string(),
+        "2024-05-01 00:00:00"
+    );
+    assert_eq!(
+        ModifiedSince::parse("2024-05-01T09:00:00Z").map(|since| since.cutoff(Local::now())),
+        Ok(DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z")
+            .unwrap()
+            .with_timezone(&Local))
+    );
+    assert!(ModifiedSince::parse("7 days").is_err());
+    assert!(ModifiedSince::parse("d").is_err());
+}
+
+#[test]
+fn repository_without_local_branches() {
+    let fixture = Fixture::new();
+    let oid = fixture.local.head().unwrap().target().unwrap();
+    fixture.local.set_head_detached(oid).unwrap();
+    fixture
+        .local
+        .find_branch("main", git2::BranchType::Local)
+        .unwrap()
+        .delete()
+        .unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![Finding::DetachedHead { oid }, Finding::NoLocalBranches]
+    );
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn freshly_initialized_repository_has_no_commits_yet() {
+    let fixture = Fixture::new();
+    let fresh = Repository::init_opts(
+        fixture.dir.path().join("fresh"),
+        git2::RepositoryInitOptions::new().initial_head("trunk"),
+    )
+    .unwrap();
+    let mut printer = Printer::new(Verbosity::Normal);
+    let report = check_repo(fresh.workdir().unwrap(), &fixture.options(), &mut printer).unwrap();
+    assert!(printer
+        .take_texts()
+        .iter()
+        .any(|text| text.ends_with("Repository has no commits yet on branch trunk")));
+    assert_eq!(
+        report.findings,
+        vec![Finding::NoCommitsYet, Finding::NoRemotes]
+    );
+    assert!(report.branches.is_empty());
+}
+
+#[test]
+fn any_remote_accepts_branch_pushed_elsewhere() {
+    let fixture = Fixture::new();
+    fixture.add_remote("backup");
+    commit(&fixture.local, "local work");
+    push(&fixture.local, "backup", "main");
+
+    let report = check(&fixture);
+    assert_eq!(report.branch("main").unwrap().status, BranchStatus::Ahead);
+
+    let mut options = fixture.options();
+    options.any_remote = true;
+    let report = check_with(&fixture, &options);
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::UpToDate);
+    assert_eq!(main.remote.as_deref(), Some("backup"));
+}
+
+#[test]
+fn compare_remote_checks_branches_whatever_their_upstream() {
+    let fixture = Fixture::new();
+    fixture.add_remote("backup");
+    push(&fixture.local, "backup", "main");
+    let mut options = fixture.options();
+    options.compare_remote = Some("backup".to_owned());
+    assert_eq!(check_with(&fixture, &options).findings, vec![]);
+
+    commit(&fixture.local, "local work");
+    push(&fixture.local, "origin", "main");
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+    assert!(report.findings.contains(&Finding::DiffersFromRemote {
+        branch: "main".to_owned(),
+        remote: "backup".to_owned(),
+    }));
+    assert!(report.findings.contains(&Finding::MissingFromRemote {
+        branch: "feature".to_owned(),
+        remote: "backup".to_owned(),
+    }));
+}
+
+#[test]
+fn any_remote_falls_back_to_upstream_verdict() {
+    let fixture = Fixture::new();
+    fixture.add_remote("backup");
+    commit(&fixture.local, "local work");
+    let mut options = fixture.options();
+    options.any_remote = true;
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.branch("main").unwrap().status, BranchStatus::Ahead);
+}
+
+#[test]
+fn allowed_local_branch_without_upstream() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("wip/idea", &head, false).unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let mut options = fixture.options();
+    options.allowed_local_branches = vec![glob::Pattern::new("wip/*").unwrap()];
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.branch("wip/idea").unwrap().status,
+        BranchStatus::AllowedLocal
+    );
+    assert_eq!(
+        report.branch("feature").unwrap().status,
+        BranchStatus::NoUpstream
+    );
+}
+
+#[test]
+fn allowed_local_pattern_does_not_hide_ahead_branch() {
+    let fixture = Fixture::new();
+    commit(&fixture.local, "local work");
+    let mut options = fixture.options();
+    options.allowed_local_branches = vec![glob::Pattern::new("*").unwrap()];
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.branch("main").unwrap().status, BranchStatus::Ahead);
+}
+
+#[test]
+fn current_branch_only_skips_other_branches() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().peel_to_commit().unwrap();
+    fixture.local.branch("feature", &head, false).unwrap();
+    let mut options = fixture.options();
+    options.current_branch_only = true;
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.branches.len(), 1);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn current_branch_only_checks_detached_head_reachability() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.current_branch_only = true;
+
+    let pushed = fixture.local.head().unwrap().target().unwrap();
+    fixture.local.set_head_detached(pushed).unwrap();
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.findings, vec![]);
+    assert!(report.branches.is_empty());
+
+    let unpushed = commit(&fixture.local, "detached work");
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.findings,
+        vec![Finding::DetachedHeadNotPushed { oid: unpushed }]
+    );
+}
+
+#[test]
+fn half_resolved_merge_leaves_unmerged_paths() {
+    let fixture = Fixture::new();
+    let repo = &fixture.local;
+    let base = repo.head().unwrap().peel_to_commit().unwrap();
+    let signature = git2::Signature::now("check-gits", "check-gits@example.com").unwrap();
+    // Theirs, on another branch, changes the same new file as ours does on main
+    let theirs_blob = repo.blob(b"theirs\n").unwrap();
+    let mut tree_builder = repo.treebuilder(Some(&base.tree().unwrap())).unwrap();
+    tree_builder
+        .insert("shared.txt", theirs_blob, 0o100644)
+        .unwrap();
+    let theirs_tree = repo.find_tree(tree_builder.write().unwrap()).unwrap();
+    repo.commit(
+        Some("refs/heads/theirs"),
+        &signature,
+        &signature,
+        "theirs",
+        &theirs_tree,
+        &[&base],
+    )
+    .unwrap();
+    fs::write(fixture.local_path().join("shared.txt"), "ours\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("shared.txt")).unwrap();
+    index.write().unwrap();
+    let ours_tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "ours",
+        &ours_tree,
+        &[&base],
+    )
+    .unwrap();
+    let theirs = repo
+        .reference_to_annotated_commit(&repo.find_reference("refs/heads/theirs").unwrap())
+        .unwrap();
+    repo.merge(&[&theirs], None, None).unwrap();
+    // Forgetting about the merge doesn't resolve its conflicts
+    repo.cleanup_state().unwrap();
+
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![Finding::UnmergedPaths {
+            paths: vec!["shared.txt".to_owned()]
+        }]
+    );
+}
+
+#[test]
+fn merge_in_progress() {
+    let fixture = Fixture::new();
+    let head = fixture.local.head().unwrap().target().unwrap();
+    fs::write(fixture.local.path().join("MERGE_HEAD"), format!("{head}\n")).unwrap();
+    let report = check(&fixture);
+    assert_eq!(
+        report.findings,
+        vec![Finding::OperationInProgress {
+            operation: "merge".to_owned()
+        }]
+    );
+}
+
+#[test]
+fn file_remotes_qualify_only_when_allowed() {
+    let fixture = Fixture::new();
+    let url = format!("file://{}", fixture.remote.path().display());
+    fixture.local.remote_set_url("origin", &url).unwrap();
+    let mut options = CheckOptions::new(Credentials::default());
+    let report = check_with(&fixture, &options);
+    assert_eq!(
+        report.findings,
+        vec![Finding::UnqualifiedRemote {
+            remote: "origin".to_owned()
+        }]
+    );
+
+    options
+        .qualifying_url_prefixes
+        .push(ExtraScheme::File.url_prefix().to_owned());
+    let report = check_with(&fixture, &options);
+    assert_eq!(report.findings, vec![]);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::UpToDate
+    );
+}
+
+#[test]
+fn remotes_qualify_by_regex() {
+    let fixture = Fixture::new();
+    let mut options = CheckOptions::new(Credentials::default());
+    options.qualifying_url_patterns = vec![regex::Regex::new("^/elsewhere/").unwrap()];
+    assert_eq!(
+        check_with(&fixture, &options).findings,
+        vec![Finding::UnqualifiedRemote {
+            remote: "origin".to_owned()
+        }]
+    );
+
+    let remote_dir = regex::escape(&fixture.dir.path().display().to_string());
+    options.qualifying_url_patterns =
+        vec![regex::Regex::new(&format!("^{}/", remote_dir)).unwrap()];
+    assert_eq!(check_with(&fixture, &options).findings, vec![]);
+}
+
+#[test]
+fn shallow_clone_softens_missing_history() {
+    let fixture = Fixture::new();
+    let local_oid = fixture.local.head().unwrap().target().unwrap();
+    let cut_off_oid = commit(&fixture.local, "cut off");
+    let shallow_oid = commit(&fixture.local, "shallow");
+    push(&fixture.local, "origin", "main");
+    let local_commit = fixture.local.find_object(local_oid, None).unwrap();
+    fixture
+        .local
+        .reset(&local_commit, git2::ResetType::Hard, None)
+        .unwrap();
+    // Make it look like `git fetch --depth 1` brought in the upstream tip without its parent
+    fs::write(
+        fixture.local.path().join("shallow"),
+        format!("{shallow_oid}\n"),
+    )
+    .unwrap();
+    let cut_off_hex = cut_off_oid.to_string();
+    fs::remove_file(
+        fixture
+            .local
+            .path()
+            .join("objects")
+            .join(&cut_off_hex[..2])
+            .join(&cut_off_hex[2..]),
+    )
+    .unwrap();
+
+    let report = check(&fixture);
+    assert_eq!(report.findings, vec![Finding::ShallowClone]);
+    assert_eq!(
+        report.branch("main").unwrap().status,
+        BranchStatus::Unverifiable
+    );
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn dry_run_stops_before_fetching() {
+    let fixture = Fixture::new();
+    fixture.push_from_elsewhere("upstream work");
+    let mut options = fixture.options();
+    options.dry_run = true;
+    let mut printer = Printer::new(Verbosity::Normal);
+    let report = check_repo(&fixture.local_path(), &options, &mut printer).unwrap();
+    assert_eq!(report.branches, vec![]);
+    let url = fixture.remote.path().to_str().unwrap().to_owned();
+    assert!(printer.take_texts().contains(&format!(
+        "📝 {}: Would fetch remote origin ({})",
+        fixture.local_path().display(),
+        url
+    )));
+    // The upstream work hasn't been fetched
+    let origin_main = fixture
+        .local
+        .find_branch("origin/main", git2::BranchType::Remote)
+        .unwrap();
+    assert_eq!(
+        origin_main.get().target(),
+        fixture.local.head().unwrap().target()
+    );
+}
+
+#[test]
+fn unselected_remotes_are_skipped() {
+    let fixture = Fixture::new();
+    fixture.add_remote("mirror");
+    let mut options = fixture.options();
+    options.selected_remotes = Some(vec!["mirror".to_owned()]);
+    let report = check_with(&fixture, &options);
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::RemoteNotSelected);
+    assert_eq!(main.remote.as_deref(), Some("origin"));
+    assert!(!report.has_problems());
+}
+
+#[test]
+fn recently_fetched_remotes_are_not_fetched_again() {
+    let fixture = Fixture::new();
+    let mut options = fixture.options();
+    options.fetch_cooldown = Duration::from_secs(60);
+    options.fetch_cache.record(fixture.local.path(), "origin");
+    fixture.push_from_elsewhere("upstream work");
+    let report = check_with(&fixture, &options);
+    // The upstream work would have made the branch behind, had it been fetched
+    let main = report.branch("main").unwrap();
+    assert_eq!(main.status, BranchStatus::UpToDate);
+    assert_eq!(main.behind, 0);
+}
@@ -0,0 +1,348 @@
+//! How to authenticate to remotes, worked out once per run and shared by every fetch.
+
+use crate::known_hosts::{KnownHosts, Verdict};
+use crate::ssh_config::{self, HostConfig, SshConfig};
+use log::{debug, trace};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default)]
+pub struct Credentials {
+    /// Tried in order, after any `IdentityFile`s that `ssh_config` names for the host
+    ssh_keys: Vec<PathBuf>,
+    ssh_config: SshConfig,
+    /// Resolved per host on first use, since the same few hosts come up for every repository
+    hosts: Mutex<HashMap<Option<String>, Arc<HostCredentials>>>,
+    host_key_check: HostKeyCheck,
+    /// The keys were picked on purpose, so git's `core.sshCommand` doesn't get a say
+    explicit_ssh_keys: bool,
+}
+
+/// What a remote was last offered, which is what it accepted if the fetch went through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthMethod {
+    SshKey(PathBuf),
+    /// A password or token that git's credential helper had for the user
+    CredentialHelper {
+        username: String,
+    },
+}
+
+impl fmt::Display for AuthMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthMethod::SshKey(path) => write!(f, "the ssh key {}", path.display()),
+            AuthMethod::CredentialHelper { username } => {
+                write!(f, "git's credential helper's credentials for {}", username)
+            }
+        }
+    }
+}
+
+/// How ssh servers' host keys are checked
+#[derive(Debug, Default)]
+pub enum HostKeyCheck {
+    /// Leave it to libgit2
+    #[default]
+    Libgit2,
+    /// Only accept host keys listed in a `known_hosts` file
+    KnownHosts(KnownHosts),
+    /// Accept any host key
+    Skip,
+}
+
+/// What to offer a particular host
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HostCredentials {
+    pub user: Option<String>,
+    pub ssh_keys: Vec<PathBuf>,
+}
+
+impl Credentials {
+    pub fn new(ssh_keys: Vec<PathBuf>, ssh_config: SshConfig) -> Self {
+        Self {
+            ssh_keys,
+            ssh_config,
+            hosts: Mutex::default(),
+            host_key_check: HostKeyCheck::default(),
+            explicit_ssh_keys: false,
+        }
+    }
+
+    pub fn with_host_key_check(mut self, host_key_check: HostKeyCheck) -> Self {
+        self.host_key_check = host_key_check;
+        self
+    }
+
+    /// Offer only `ssh_keys` (and the ssh config's), not the ones in git's `core.sshCommand`
+    pub fn with_explicit_ssh_keys(mut self, explicit_ssh_keys: bool) -> Self {
+        self.explicit_ssh_keys = explicit_ssh_keys;
+        self
+    }
+
+    /// The user and keys for the host in `url`. Urls without a host only get the global keys.
+    pub fn for_url(&self, url: &str) -> Arc<HostCredentials> {
+        let host = ssh_config::host_of_url(url).map(str::to_owned);
+        let mut hosts = self.hosts.lock().unwrap_or_else(|error| error.into_inner());
+        hosts
+            .entry(host)
+            .or_insert_with_key(|host| Arc::new(self.resolve(host.as_deref())))
+            .clone()
+    }
+
+    /// The url for libgit2 to connect to in place of the ssh url `url`, if its host is an alias
+    /// with a `HostName` in the ssh config. libssh2 never reads the config, so it'd try to look
+    /// the alias up as is.
+    pub fn connect_url(&self, url: &str) -> Option<String> {
+        if let Some((scheme, _)) = url.split_once("://") {
+            if !matches!(scheme, "ssh" | "git+ssh" | "ssh+git") {
+                return None;
+            }
+        }
+        let host = ssh_config::host_of_url(url)?;
+        let host_name = self.ssh_config.resolve(host).host_name?;
+        if host_name.eq_ignore_ascii_case(host) {
+            return None;
+        }
+        ssh_config::with_host(url, &host_name)
+    }
+
+    fn resolve(&self, host: Option<&str>) -> HostCredentials {
+        let host_config = host
+            .map(|host| self.ssh_config.resolve(host))
+            .unwrap_or_default();
+        let mut ssh_keys: Vec<PathBuf> = Vec::new();
+        for ssh_key in host_config
+            .identity_files
+            .into_iter()
+            .chain(self.ssh_keys.iter().cloned())
+        {
+            if !ssh_keys.contains(&ssh_key) {
+                ssh_keys.push(ssh_key);
+            }
+        }
+        HostCredentials {
+            user: host_config.user,
+            ssh_keys,
+        }
+    }
+
+    /// The identities and user in the ssh command git would run for `git_config`'s repository.
+    /// Like git, `GIT_SSH_COMMAND` wins over `core.sshCommand`.
+    fn ssh_command(&self, git_config: Option<&git2::Config>) -> HostConfig {
+        if self.explicit_ssh_keys {
+            return HostConfig::default();
+        }
+        let command = std::env::var("GIT_SSH_COMMAND").ok().or_else(|| {
+            git_config.and_then(|git_config| git_config.get_string("core.sshCommand").ok())
+        });
+        match command {
+            Some(command) => {
+                debug!("Going by the ssh command {}", command);
+                ssh_config::parse_ssh_command(&command, dirs::home_dir().as_deref())
+            }
+            None => HostConfig::default(),
+        }
+    }
+
+    /// Callbacks for a single fetch. The credentials the remote accepts, if it asks for any, are
+    /// left in `auth_method`. `remote_url` is the remote's url as configured, whose host picks the
+    /// ssh config even when libgit2 connects through `connect_url`. `git_config` is the config of
+    /// the repository being fetched, for its ssh command and credential helpers.
+    ///
+    /// libgit2 calls the credentials callback again each time a key is rejected, so every call
+    /// hands out the next key until one is accepted or they run out.
+    pub fn remote_callbacks<'a>(
+        &'a self,
+        remote_url: &'a str,
+        git_config: Option<&'a git2::Config>,
+        auth_method: &'a RefCell<Option<AuthMethod>>,
+    ) -> git2::RemoteCallbacks<'a> {
+        let ssh_command = self.ssh_command(git_config);
+        let ssh_keys_tried = Cell::new(0);
+        // A helper gives the same answer every time, so it's only worth asking once
+        let credential_helper_asked = Cell::new(false);
+        let mut remote_callbacks = git2::RemoteCallbacks::new();
+        remote_callbacks.credentials(move |url, user_from_url, cred| {
+            trace!("{} asked for credentials of type {:?}", url, cred);
+            let host_credentials = self.for_url(remote_url);
+            // Like ssh, prefer what the url says and fall back to the ssh config for the host
+            // See https://github.com/rust-lang/git2-rs/issues/329#issuecomment-403318088
+            let user = user_from_url
+                .or(ssh_command.user.as_deref())
+                .or(host_credentials.user.as_deref())
+                .unwrap_or(url);
+            if cred.is_username() {
+                // TODO: since `cred` is a bitset, figure out if we need to check for other flags
+                return git2::Cred::username(user);
+            }
+            if cred.is_ssh_key() {
+                let mut ssh_keys: Vec<&PathBuf> = Vec::new();
+                for ssh_key in ssh_command
+                    .identity_files
+                    .iter()
+                    .chain(&host_credentials.ssh_keys)
+                {
+                    if !ssh_keys.contains(&ssh_key) {
+                        ssh_keys.push(ssh_key);
+                    }
+                }
+                let Some(ssh_key) = ssh_keys.get(ssh_keys_tried.get()) else {
+                    // libssh2 doesn't say why a key was rejected, so this is as specific as it gets
+                    let tried: Vec<_> = ssh_keys
+                        .iter()
+                        .map(|key| key.display().to_string())
+                        .collect();
+                    return Err(git2::Error::new(
+                        git2::ErrorCode::Auth,
+                        git2::ErrorClass::Ssh,
+                        format!(
+                            "None of the ssh keys were accepted (tried: {})",
+                            tried.join(", ")
+                        ),
+                    ));
+                };
+                debug!("Offering the ssh key {} to {}", ssh_key.display(), url);
+                ssh_keys_tried.set(ssh_keys_tried.get() + 1);
+                auth_method.replace(Some(AuthMethod::SshKey(ssh_key.to_path_buf())));
+                git2::Cred::ssh_key(user, None, ssh_key, None)
+            } else if let (true, Some(git_config)) = (cred.is_user_pass_plaintext(), git_config) {
+                if credential_helper_asked.replace(true) {
+                    return Err(git2::Error::new(
+                        git2::ErrorCode::Auth,
+                        git2::ErrorClass::Http,
+                        format!("{} rejected the credentials from git's credential helper", url),
+                    ));
+                }
+                // Whatever `credential.helper` is set to, as git itself would ask it. This is what
+                // `Cred::credential_helper` does, but keeping hold of the username.
+                let Some((username, password)) = git2::CredentialHelper::new(url)
+                    .config(git_config)
+                    .username(user_from_url)
+                    .execute()
+                else {
+                    return Err(git2::Error::new(
+                        git2::ErrorCode::Auth,
+                        git2::ErrorClass::Http,
+                        format!("No credential helper in git's config had credentials for {}", url),
+                    ));
+                };
+                let cred = git2::Cred::userpass_plaintext(&username, &password)?;
+                auth_method.replace(Some(AuthMethod::CredentialHelper { username }));
+                Ok(cred)
+            } else {
+                // Unimplemented credential type
+                // TODO: generalize this along with the "qualifying remote" check
+                if url.starts_with("https://") {
+                    return Err(git2::Error::from_str(&format!("Unimplemented git2 credential type in remote auth callback: {:?}. Consider changing the remote url to use ssh instead.", cred)));
+                }
+                Err(git2::Error::from_str(&format!(
+                    "Unimplemented git2 credential type in remote auth callback: {:?}",
+                    cred
+                )))
+            }
+        });
+        // libgit2 only passes the host along, so the port comes from the url
+        let port = ssh_config::port_of_url(remote_url);
+        remote_callbacks
+            .certificate_check(move |cert, host| self.check_certificate(cert, host, port));
+        remote_callbacks
+    }
+
+    fn check_certificate(
+        &self,
+        cert: &git2::cert::Cert,
+        host: &str,
+        port: Option<u16>,
+    ) -> Result<git2::CertificateCheckStatus, git2::Error> {
+        // TLS certificates are left to libgit2, which checks them against the system's CAs
+        let Some(host_key) = cert.as_hostkey() else {
+            return Ok(git2::CertificateCheckStatus::CertificatePassthrough);
+        };
+        let known_hosts = match &self.host_key_check {
+            HostKeyCheck::Libgit2 => {
+                return Ok(git2::CertificateCheckStatus::CertificatePassthrough)
+            }
+            HostKeyCheck::Skip => return Ok(git2::CertificateCheckStatus::CertificateOk),
+            HostKeyCheck::KnownHosts(known_hosts) => known_hosts,
+        };
+        let verdict = host_key
+            .hostkey()
+            .map(|key| known_hosts.verify(host, port, key));
+        debug!("Host key of {}: {:?}", host, verdict);
+        let problem = match verdict {
+            Some(Verdict::Known) => return Ok(git2::CertificateCheckStatus::CertificateOk),
+            Some(Verdict::Mismatch) => {
+                "doesn't match the one in known_hosts. Someone may be impersonating the host"
+            }
+            Some(Verdict::Revoked) => "is revoked in known_hosts",
+            Some(Verdict::Unknown) => {
+                "isn't in known_hosts. Add it with ssh-keyscan, or pass --insecure-skip-host-verify"
+            }
+            None => "wasn't presented by the server",
+        };
+        Err(git2::Error::new(
+            git2::ErrorCode::Certificate,
+            git2::ErrorClass::Ssh,
+            format!("The host key of {} {}", host, problem),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn host_keys_come_first_without_duplicates() {
+        let ssh_config = SshConfig::parse(
+            "Host github.com\n    User git\n    IdentityFile /keys/work\n    IdentityFile /keys/personal\n",
+            None,
+        );
+        let credentials = Credentials::new(
+            vec![
+                PathBuf::from("/keys/personal"),
+                PathBuf::from("/keys/other"),
+            ],
+            ssh_config,
+        );
+        assert_eq!(
+            *credentials.for_url("git@github.com:org/repo.git"),
+            HostCredentials {
+                user: Some("git".to_owned()),
+                ssh_keys: vec![
+                    PathBuf::from("/keys/work"),
+                    PathBuf::from("/keys/personal"),
+                    PathBuf::from("/keys/other"),
+                ],
+            }
+        );
+        assert_eq!(
+            credentials.for_url("/srv/git/repo.git").ssh_keys,
+            vec![Path::new("/keys/personal"), Path::new("/keys/other")]
+        );
+    }
+
+    #[test]
+    fn aliases_connect_to_their_host_name() {
+        let credentials = Credentials::new(
+            Vec::new(),
+            SshConfig::parse("Host github-work\n    HostName github.com\n", None),
+        );
+        assert_eq!(
+            credentials
+                .connect_url("git@github-work:org/repo.git")
+                .as_deref(),
+            Some("git@github.com:org/repo.git")
+        );
+        assert_eq!(
+            credentials.connect_url("https://github-work/org/repo.git"),
+            None
+        );
+        assert_eq!(credentials.connect_url("git@github.com:org/repo.git"), None);
+    }
+}
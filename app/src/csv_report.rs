@@ -0,0 +1,105 @@
+//! A CSV table of a whole run, one row per branch or finding, for auditing in a spreadsheet.
+
+use crate::report::{RepoReport, ScanSummary};
+use crate::reporter::{write_rendered, OutputFile, Reporter};
+use std::io;
+
+const HEADER: [&str; 7] = [
+    "repo_path",
+    "branch",
+    "upstream",
+    "remote",
+    "status",
+    "ahead",
+    "behind",
+];
+
+/// Collects the reports, for a table of the whole run once the run is done
+pub struct CsvReporter {
+    reports: Vec<RepoReport>,
+    output: Option<OutputFile>,
+}
+
+impl CsvReporter {
+    /// Writes to `output` instead of stdout
+    pub fn new(output: Option<OutputFile>) -> Self {
+        Self {
+            reports: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Reporter for CsvReporter {
+    fn repo_finished(&mut self, report: &RepoReport) {
+        self.reports.push(report.clone());
+    }
+    fn run_finished(&mut self, _summary: &ScanSummary) -> io::Result<()> {
+        write_rendered(self.output.as_ref(), &render(&self.reports))
+    }
+}
+
+pub fn render(reports: &[RepoReport]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    // Writing to memory can't fail
+    writer.write_record(HEADER).unwrap();
+    for report in reports {
+        let path = report.path.to_string_lossy();
+        for finding in &report.findings {
+            writer
+                .write_record([&*path, "", "", "", &finding.to_string(), "", ""])
+                .unwrap();
+        }
+        for branch in &report.branches {
+            writer
+                .write_record([
+                    &*path,
+                    &branch.name,
+                    branch.upstream.as_deref().unwrap_or_default(),
+                    branch.remote.as_deref().unwrap_or_default(),
+                    &branch.status.to_string(),
+                    &branch.ahead.to_string(),
+                    &branch.behind.to_string(),
+                ])
+                .unwrap();
+        }
+        // Every entry gets at least one row, so nothing checked goes missing from the table
+        if report.findings.is_empty() && report.branches.is_empty() {
+            writer
+                .write_record([&*path, "", "", "", "nothing to report", "", ""])
+                .unwrap();
+        }
+    }
+    String::from_utf8(writer.into_inner().unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{BranchReport, BranchStatus, Finding};
+    use std::path::PathBuf;
+
+    #[test]
+    fn rows_for_branches_and_findings() {
+        let mut repo = RepoReport::new(PathBuf::from("/code/a,b"));
+        repo.branches.push(BranchReport {
+            name: "main".to_owned(),
+            upstream: Some("origin/main".to_owned()),
+            remote: Some("origin".to_owned()),
+            status: BranchStatus::Ahead,
+            ahead: 2,
+            behind: 0,
+            last_commit: None,
+        });
+        let mut not_a_repo = RepoReport::new(PathBuf::from("/code/notes"));
+        not_a_repo.findings.push(Finding::NotARepository);
+        assert_eq!(
+            render(&[repo, not_a_repo]),
+            "\
+repo_path,branch,upstream,remote,status,ahead,behind
+\"/code/a,b\",main,origin/main,origin,ahead of upstream,2,0
+/code/notes,,,,not a git repository,,
+"
+        );
+    }
+}
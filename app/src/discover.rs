@@ -0,0 +1,454 @@
+//! Finding the entries to check.
+
+use anyhow::{ensure, Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// By path, case-insensitively
+    #[default]
+    Name,
+    /// Problems first, then warnings, then clean entries. Output is held back until the end.
+    Status,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Lists the entries of each directory in turn. The same physical directory is only listed once.
+/// With `skip_hidden`, entries whose names start with a `.` are left out. With `use_ignore_files`,
+/// so are the entries that the directory's `IGNORE_FILE_NAME` matches.
+///
+/// A directory that can't be read becomes a failed entry, so the other directories still get checked.
+pub fn read_repos_directories(
+    repos_directories: &[PathBuf],
+    skip_hidden: bool,
+    use_ignore_files: bool,
+) -> Vec<io::Result<PathBuf>> {
+    let mut entries = Vec::new();
+    for repos_directory in dedup_directories(repos_directories) {
+        let ignore_file = match use_ignore_files {
+            true => match read_ignore_file(repos_directory) {
+                Ok(ignore_file) => ignore_file,
+                Err(error) => {
+                    entries.push(Err(error));
+                    continue;
+                }
+            },
+            false => None,
+        };
+        let is_ignored = |path: &Path| {
+            ignore_file.as_ref().is_some_and(|ignore_file| {
+                path.file_name() == Some(IGNORE_FILE_NAME.as_ref())
+                    || ignore_file.matched(path, path.is_dir()).is_ignore()
+            })
+        };
+        match fs::read_dir(repos_directory) {
+            Ok(dir_entries) => entries.extend(
+                dir_entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .filter(|entry| {
+                        entry.as_ref().map_or(true, |path| {
+                            !(skip_hidden && is_hidden(path) || is_ignored(path))
+                        })
+                    }),
+            ),
+            Err(error) => entries.push(Err(io::Error::new(
+                error.kind(),
+                format!(
+                    "Failed to read projects directory {}: {}",
+                    repos_directory.display(),
+                    error
+                ),
+            ))),
+        }
+    }
+    entries
+}
+
+/// Expands the repos directories that are still globs, like a quoted `'~/code/*/repos'`, into the
+/// directories they match, in order. A path that exists as it's written is never a glob, so
+/// whatever the shell already expanded is left alone, and so is everything else.
+pub fn expand_repos_directories(
+    repos_directories: &[PathBuf],
+    home_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for repos_directory in repos_directories {
+        let pattern = repos_directory.to_string_lossy();
+        if repos_directory.exists() || !pattern.contains(['*', '?', '[']) {
+            expanded.push(repos_directory.clone());
+            continue;
+        }
+        // The shell doesn't expand a quoted `~` either
+        let pattern = match (pattern.strip_prefix("~/"), home_dir) {
+            (Some(rest), Some(home_dir)) => home_dir.join(rest).to_string_lossy().into_owned(),
+            _ => pattern.into_owned(),
+        };
+        let matches = glob::glob(&pattern)
+            .with_context(|| format!("Invalid glob for the repos directory: {}", pattern))?
+            .filter_map(|path| path.ok())
+            .filter(|path| path.is_dir());
+        let count = expanded.len();
+        expanded.extend(matches);
+        ensure!(
+            expanded.len() > count,
+            "No directories match the repos directory glob {}",
+            pattern
+        );
+    }
+    Ok(expanded)
+}
+
+/// Lists entries of the directory it's in that aren't to be checked, in gitignore syntax
+pub const IGNORE_FILE_NAME: &str = ".check-gits-ignore";
+
+/// The directory's ignore file, if it has one
+fn read_ignore_file(directory: &Path) -> io::Result<Option<Gitignore>> {
+    let path = directory.join(IGNORE_FILE_NAME);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut builder = GitignoreBuilder::new(directory);
+    if let Some(error) = builder.add(&path) {
+        return Err(io::Error::other(format!(
+            "Failed to read {}: {}",
+            path.display(),
+            error
+        )));
+    }
+    builder.build().map(Some).map_err(|error| {
+        io::Error::other(format!("Invalid patterns in {}: {}", path.display(), error))
+    })
+}
+
+/// Dotfiles and dot-directories, except for `.git`: the directory being scanned is a repository
+/// itself, and its git dir is checked like any entry
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.as_encoded_bytes().starts_with(b".") && name != ".git")
+}
+
+/// Drops directories that resolve to one listed earlier, keeping the order otherwise
+fn dedup_directories(directories: &[PathBuf]) -> Vec<&PathBuf> {
+    let mut visited = Visited::default();
+    directories
+        .iter()
+        .filter(|directory| visited.insert(directory))
+        .collect()
+}
+
+/// The physical directories seen so far, so that none is processed twice however it's reached
+#[derive(Debug, Default)]
+pub struct Visited {
+    /// The path each directory was first reached under
+    first_paths: HashMap<Identity, PathBuf>,
+}
+
+/// What makes two paths the same directory
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum Identity {
+    /// Device and inode, which also see through case-insensitive names
+    #[cfg(unix)]
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+impl Visited {
+    /// Records `path`, returning whether this is the first time its directory came up.
+    /// Symlinks and `..` are resolved, so a symlink cycle leads back to a visited path.
+    pub fn insert(&mut self, path: &Path) -> bool {
+        self.first_visit(path).is_none()
+    }
+
+    /// Records `path`, returning the path its directory was first reached under, if it was already
+    pub fn first_visit(&mut self, path: &Path) -> Option<PathBuf> {
+        match self.first_paths.entry(identity(path)) {
+            Entry::Occupied(first) => Some(first.get().clone()),
+            Entry::Vacant(vacant) => {
+                vacant.insert(path.to_path_buf());
+                None
+            }
+        }
+    }
+}
+
+fn identity(path: &Path) -> Identity {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let Ok(metadata) = fs::metadata(path) {
+            return Identity::Inode(metadata.dev(), metadata.ino());
+        }
+    }
+    // A path that can't be canonicalized will fail to be read later, with a proper error
+    Identity::Path(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+}
+
+/// Reads repository paths from the file at `list_path`, or from stdin if it's `-`
+pub fn read_repo_list(list_path: &Path) -> Result<Vec<PathBuf>> {
+    if list_path == Path::new("-") {
+        return parse_repo_list(io::stdin().lock())
+            .context("Failed to read repository list from stdin");
+    }
+    let file = File::open(list_path)
+        .with_context(|| format!("Failed to open repository list: {}", list_path.display()))?;
+    parse_repo_list(BufReader::new(file))
+        .with_context(|| format!("Failed to read repository list: {}", list_path.display()))
+}
+
+/// One path per line. Blank lines and lines starting with `#` are skipped.
+pub fn parse_repo_list(reader: impl BufRead) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        paths.push(PathBuf::from(line));
+    }
+    Ok(paths)
+}
+
+/// Whether the file at `path` is a `.git` file, which points at the real git dir of a linked
+/// worktree or submodule
+pub fn is_gitdir_pointer(path: &Path) -> bool {
+    const PREFIX: &[u8] = b"gitdir: ";
+    let mut start = [0; PREFIX.len()];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut start))
+        .is_ok_and(|()| start == PREFIX)
+}
+
+/// The work tree of the repository that `path` sits in, if that repository ignores `path`. Such
+/// a nested repository is deliberately kept out of the enclosing one, like a vendored tool.
+pub fn ignoring_repository(path: &Path) -> Option<PathBuf> {
+    let path = fs::canonicalize(path).ok()?;
+    let enclosing = git2::Repository::discover(path.parent()?).ok()?;
+    let workdir = fs::canonicalize(enclosing.workdir()?).ok()?;
+    let relative = path.strip_prefix(&workdir).ok()?;
+    enclosing
+        .is_path_ignored(relative)
+        .unwrap_or(false)
+        .then_some(workdir)
+}
+
+/// Puts the entries in a stable order, so that runs can be compared.
+///
+/// `SortOrder::Status` needs the verdicts, so here it sorts by name and the caller reorders the
+/// output later. Entries that couldn't be listed go last.
+pub fn sort_entries(entries: &mut [io::Result<PathBuf>], order: SortOrder) {
+    entries.sort_by_cached_key(|entry| {
+        let Ok(path) = entry else {
+            return (true, None, String::new(), PathBuf::new());
+        };
+        let modified = match order {
+            SortOrder::Mtime => Some(std::cmp::Reverse(modified(path))),
+            SortOrder::Name | SortOrder::Status => None,
+        };
+        let name = path.to_string_lossy().to_lowercase();
+        (false, modified, name, path.clone())
+    });
+}
+
+/// When an entry was last touched. Commits update a repository's `.git` rather than its directory.
+fn modified(path: &Path) -> Option<SystemTime> {
+    let modified = |path: &Path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    modified(path).max(modified(&path.join(".git")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn same_directory_is_only_listed_once() {
+        let dir = TempDir::new().unwrap();
+        let work = dir.path().join("work");
+        let personal = dir.path().join("personal");
+        fs::create_dir_all(work.join("a")).unwrap();
+        fs::create_dir_all(personal.join("b")).unwrap();
+        let work_again = dir.path().join("personal/../work");
+        let entries: Vec<_> =
+            read_repos_directories(&[work.clone(), personal.clone(), work_again], true, true)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+        assert_eq!(entries, vec![work.join("a"), personal.join("b")]);
+    }
+
+    #[test]
+    fn hidden_entries_are_skipped_unless_included() {
+        let dir = TempDir::new().unwrap();
+        for name in ["repo", ".cache", ".git"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        let listed = |skip_hidden| {
+            let mut entries: Vec<_> =
+                read_repos_directories(&[dir.path().to_owned()], skip_hidden, true)
+                    .into_iter()
+                    .map(Result::unwrap)
+                    .collect();
+            entries.sort();
+            entries
+        };
+        assert_eq!(
+            listed(true),
+            vec![dir.path().join(".git"), dir.path().join("repo")]
+        );
+        assert_eq!(listed(false).len(), 3);
+    }
+
+    #[test]
+    fn ignore_file_skips_matching_entries() {
+        let dir = TempDir::new().unwrap();
+        for name in ["repo", "scratch", "vendor-a", "vendor-b"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        fs::write(
+            dir.path().join(IGNORE_FILE_NAME),
+            "# third-party checkouts\nvendor-*/\n/scratch\n",
+        )
+        .unwrap();
+        let listed = |use_ignore_files| {
+            let mut entries: Vec<_> =
+                read_repos_directories(&[dir.path().to_owned()], false, use_ignore_files)
+                    .into_iter()
+                    .map(Result::unwrap)
+                    .collect();
+            entries.sort();
+            entries
+        };
+        assert_eq!(listed(true), vec![dir.path().join("repo")]);
+        assert_eq!(listed(false).len(), 5);
+    }
+
+    #[test]
+    fn unreadable_directory_does_not_hide_the_others() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("a")).unwrap();
+        let entries = read_repos_directories(
+            &[dir.path().join("missing"), dir.path().to_owned()],
+            true,
+            true,
+        );
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].is_err());
+        assert_eq!(entries[1].as_ref().unwrap(), &dir.path().join("a"));
+    }
+
+    #[test]
+    fn entries_sort_by_name_ignoring_case() {
+        let mut entries = vec![
+            Ok(PathBuf::from("/code/beta")),
+            Err(io::Error::other("unreadable")),
+            Ok(PathBuf::from("/code/Alpha")),
+            Ok(PathBuf::from("/code/alpha-2")),
+        ];
+        sort_entries(&mut entries, SortOrder::Name);
+        let paths: Vec<_> = entries.iter().map(|entry| entry.as_ref().ok()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                Some(&PathBuf::from("/code/Alpha")),
+                Some(&PathBuf::from("/code/alpha-2")),
+                Some(&PathBuf::from("/code/beta")),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn repositories_ignored_by_an_enclosing_one_are_recognized() {
+        let dir = TempDir::new().unwrap();
+        git2::Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join(".gitignore"), "vendor/\n").unwrap();
+        for name in ["vendor", "tracked"] {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        let workdir = fs::canonicalize(dir.path()).unwrap();
+        assert_eq!(
+            ignoring_repository(&dir.path().join("vendor")),
+            Some(workdir)
+        );
+        assert_eq!(ignoring_repository(&dir.path().join("tracked")), None);
+    }
+
+    #[test]
+    fn unexpanded_globs_become_the_directories_they_match() {
+        let dir = TempDir::new().unwrap();
+        for path in ["work/repos", "home/repos", "home/other", "file"] {
+            fs::create_dir_all(dir.path().join(path)).unwrap();
+        }
+        fs::write(dir.path().join("file/repos"), "").unwrap();
+        let literal = dir.path().join("[literal]");
+        fs::create_dir(&literal).unwrap();
+
+        let glob = dir.path().join("*/repos");
+        assert_eq!(
+            expand_repos_directories(&[glob, literal.clone()], None).unwrap(),
+            vec![
+                dir.path().join("home/repos"),
+                dir.path().join("work/repos"),
+                literal
+            ]
+        );
+        let home_glob = PathBuf::from("~/*/repos");
+        assert_eq!(
+            expand_repos_directories(&[home_glob], Some(dir.path())).unwrap(),
+            vec![dir.path().join("home/repos"), dir.path().join("work/repos")]
+        );
+        assert!(expand_repos_directories(&[dir.path().join("*/nothing")], None).is_err());
+    }
+
+    #[test]
+    fn recognizes_gitdir_pointers() {
+        let dir = TempDir::new().unwrap();
+        let pointer = dir.path().join(".git");
+        fs::write(&pointer, "gitdir: ../repo/.git/worktrees/feature\n").unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "gitdir").unwrap();
+        assert!(is_gitdir_pointer(&pointer));
+        assert!(!is_gitdir_pointer(&notes));
+        assert!(!is_gitdir_pointer(dir.path()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn visited_resolves_symlinks() {
+        let dir = TempDir::new().unwrap();
+        let repo = dir.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&repo, &link).unwrap();
+        let mut visited = Visited::default();
+        assert!(visited.insert(&link));
+        assert!(!visited.insert(&repo));
+        assert!(!visited.insert(&repo.join("../link")));
+        assert_eq!(visited.first_visit(&repo), Some(link));
+    }
+
+    #[test]
+    fn repo_list_skips_blanks_and_comments() {
+        let list = "# work\n~/code/a\n\n  /srv/b  \n#/srv/c\n/srv/d\r\n";
+        assert_eq!(
+            parse_repo_list(list.as_bytes()).unwrap(),
+            vec![
+                PathBuf::from("~/code/a"),
+                PathBuf::from("/srv/b"),
+                PathBuf::from("/srv/d"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use std::io;
+use thiserror::Error;
+
+/// What can go wrong while checking an entry, beyond the problems that are reported as findings.
+///
+/// Split by cause so a failure to even read the entry can be told apart from git choking on it.
+#[derive(Debug, Error)]
+pub enum CheckError {
+    /// The entry couldn't be read, e.g. due to missing permissions
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// libgit2 failed on the repository, e.g. due to corruption
+    #[error("{0}")]
+    Git(#[from] git2::Error),
+    /// There's no repository at the entry
+    #[error("{0}")]
+    NotARepository(git2::Error),
+}
+
+pub type Result<T, E = CheckError> = std::result::Result<T, E>;
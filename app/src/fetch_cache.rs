@@ -0,0 +1,122 @@
+//! When each remote was last fetched, so back-to-back runs can skip fetches that just happened.
+//!
+//! Kept as one line per remote, `<unix seconds>\t<remote>\t<git dir>`, in the user's cache dir.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Default)]
+pub struct FetchCache {
+    /// Keyed on the repository's git dir and the remote's name
+    fetched_at: Mutex<HashMap<(PathBuf, String), SystemTime>>,
+}
+
+impl FetchCache {
+    /// Where the cache lives by default, if there's a cache dir at all
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|cache_dir| cache_dir.join("check-gits").join("fetch-times"))
+    }
+
+    /// Reads the cache at `path`. A missing file is the same as an empty one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Lines that can't be understood are dropped; the worst that can happen is an extra fetch
+    fn parse(contents: &str) -> Self {
+        let fetched_at = contents
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let seconds: u64 = fields.next()?.parse().ok()?;
+                let remote_name = fields.next()?;
+                let git_dir = fields.next()?;
+                Some((
+                    (PathBuf::from(git_dir), remote_name.to_owned()),
+                    UNIX_EPOCH + Duration::from_secs(seconds),
+                ))
+            })
+            .collect();
+        Self {
+            fetched_at: Mutex::new(fetched_at),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let fetched_at = self.lock();
+        let mut contents = String::new();
+        for ((git_dir, remote_name), time) in fetched_at.iter() {
+            let seconds = time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            contents.push_str(&format!(
+                "{}\t{}\t{}\n",
+                seconds,
+                remote_name,
+                git_dir.display()
+            ));
+        }
+        fs::write(path, contents)
+    }
+
+    /// How long ago the remote was fetched, if it ever was
+    pub fn fetched_ago(&self, git_dir: &Path, remote_name: &str) -> Option<Duration> {
+        let fetched_at = *self
+            .lock()
+            .get(&(key_path(git_dir), remote_name.to_owned()))?;
+        // A fetch from the future (the clock went back) is as good as a fresh one
+        Some(fetched_at.elapsed().unwrap_or_default())
+    }
+
+    pub fn record(&self, git_dir: &Path, remote_name: &str) {
+        self.lock().insert(
+            (key_path(git_dir), remote_name.to_owned()),
+            SystemTime::now(),
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<(PathBuf, String), SystemTime>> {
+        self.fetched_at
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+}
+
+/// The same repository can be reached through different paths
+fn key_path(git_dir: &Path) -> PathBuf {
+    fs::canonicalize(git_dir).unwrap_or_else(|_| git_dir.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_the_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let git_dir = dir.path().join("repo.git");
+        fs::create_dir(&git_dir).unwrap();
+        let cache_path = dir.path().join("cache").join("fetch-times");
+
+        let cache = FetchCache::load(&cache_path).unwrap();
+        assert_eq!(cache.fetched_ago(&git_dir, "origin"), None);
+        cache.record(&git_dir, "origin");
+        cache.save(&cache_path).unwrap();
+
+        let cache = FetchCache::load(&cache_path).unwrap();
+        assert!(cache.fetched_ago(&git_dir, "origin").unwrap() < Duration::from_secs(60));
+        assert_eq!(cache.fetched_ago(&git_dir, "mirror"), None);
+    }
+}
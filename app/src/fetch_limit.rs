@@ -0,0 +1,89 @@
+//! A cap on how many fetches are on the network at once, across every repository of the run.
+
+use std::sync::{Arc, Condvar, Mutex};
+
+#[derive(Debug)]
+pub struct FetchLimit {
+    available: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Taken for as long as a fetch runs. Dropping it lets the next fetch start.
+#[derive(Debug)]
+pub struct FetchSlot {
+    limit: Arc<FetchLimit>,
+}
+
+pub const DEFAULT_MAX_FETCHES: usize = 4;
+
+impl FetchLimit {
+    /// At least one fetch can always run
+    pub fn new(max_fetches: usize) -> Self {
+        Self {
+            available: Mutex::new(max_fetches.max(1)),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Waits until fewer than the maximum are running
+    pub fn acquire(self: &Arc<Self>) -> FetchSlot {
+        let mut available = self
+            .available
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        while *available == 0 {
+            available = self
+                .freed
+                .wait(available)
+                .unwrap_or_else(|error| error.into_inner());
+        }
+        *available -= 1;
+        FetchSlot {
+            limit: Arc::clone(self),
+        }
+    }
+}
+
+impl Default for FetchLimit {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FETCHES)
+    }
+}
+
+impl Drop for FetchSlot {
+    fn drop(&mut self) {
+        let mut available = self
+            .limit
+            .available
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        *available += 1;
+        self.limit.freed.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn never_more_than_the_maximum_at_once() {
+        let limit = Arc::new(FetchLimit::new(2));
+        let (running, most_running) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        thread::scope(|scope| {
+            for _ in 0..6 {
+                scope.spawn(|| {
+                    let _slot = limit.acquire();
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_running.fetch_max(now_running, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+    }
+}
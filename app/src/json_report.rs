@@ -0,0 +1,179 @@
+//! JSON Lines of a whole run, one object per entry, for scripts to build on. The last object is
+//! the run's summary, which has a `summary` field instead of a `path`.
+//!
+//! Every object carries `schema_version` and `tool_version`, so a parser can tell which shape it's
+//! reading.
+
+use crate::report::{BranchReport, Finding, RepoReport, ScanSummary, Severity};
+use crate::reporter::{write_rendered, OutputFile, Reporter};
+use crate::sarif::{branch_rule_id, finding_rule_id};
+use serde_json::{json, Value};
+use std::io;
+
+/// Bumped whenever a field is removed, renamed or changes type. New fields don't bump it, so
+/// parsers should ignore the ones they don't know.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Collects the reports, for a line per entry once the run is done
+pub struct JsonReporter {
+    reports: Vec<RepoReport>,
+    output: Option<OutputFile>,
+}
+
+impl JsonReporter {
+    /// Writes to `output` instead of stdout
+    pub fn new(output: Option<OutputFile>) -> Self {
+        Self {
+            reports: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Reporter for JsonReporter {
+    fn repo_finished(&mut self, report: &RepoReport) {
+        self.reports.push(report.clone());
+    }
+    fn run_finished(&mut self, summary: &ScanSummary) -> io::Result<()> {
+        write_rendered(self.output.as_ref(), &render(&self.reports, summary))
+    }
+}
+
+pub fn render(reports: &[RepoReport], summary: &ScanSummary) -> String {
+    let mut rendered = String::new();
+    for report in reports {
+        let findings: Vec<Value> = report
+            .findings
+            .iter()
+            .map(|finding| finding_object(report, finding))
+            .collect();
+        let branches: Vec<Value> = report
+            .branches
+            .iter()
+            .map(|branch| branch_object(report, branch))
+            .collect();
+        let entry = json!({
+            "schema_version": SCHEMA_VERSION,
+            "tool_version": env!("CARGO_PKG_VERSION"),
+            "path": report.path.to_string_lossy(),
+            "severity": severity_name(report.severity()),
+            "hosts": report.hosts,
+            "findings": findings,
+            "branches": branches,
+        });
+        rendered.push_str(&entry.to_string());
+        rendered.push('\n');
+    }
+    let summary = json!({
+        "schema_version": SCHEMA_VERSION,
+        "tool_version": env!("CARGO_PKG_VERSION"),
+        "summary": summary_object(summary),
+    });
+    rendered.push_str(&summary.to_string());
+    rendered.push('\n');
+    rendered
+}
+
+/// The run's counts, which the SARIF log carries too
+pub(crate) fn summary_object(summary: &ScanSummary) -> Value {
+    let by_severity: serde_json::Map<String, Value> = summary
+        .by_severity
+        .iter()
+        .map(|(&severity, &count)| (severity_name(severity).to_owned(), json!(count)))
+        .collect();
+    json!({
+        "total": summary.total,
+        "clean": summary.clean,
+        "with_problems": summary.with_problems,
+        "by_severity": by_severity,
+        "unpushed_commits": summary.unpushed_commits,
+        "unpushed_branches": summary.unpushed_branches,
+        "unpushed_repos": summary.unpushed_repos,
+        "duration_seconds": summary.duration.as_secs_f64(),
+        "interrupted": summary.interrupted,
+    })
+}
+
+fn finding_object(report: &RepoReport, finding: &Finding) -> Value {
+    json!({
+        "kind": finding_rule_id(finding),
+        "severity": severity_name(report.finding_severity(finding)),
+        "message": finding.to_string(),
+    })
+}
+
+fn branch_object(report: &RepoReport, branch: &BranchReport) -> Value {
+    let last_commit = branch.last_commit.as_ref().map(|last_commit| {
+        json!({
+            "committed_at": last_commit.committed_at.to_rfc3339(),
+            "author": last_commit.author,
+        })
+    });
+    json!({
+        "name": branch.name,
+        "upstream": branch.upstream,
+        "remote": branch.remote,
+        "status": branch_rule_id(branch.status).trim_start_matches("branch-"),
+        "severity": severity_name(report.branch_severity(branch)),
+        "ahead": branch.ahead,
+        "behind": branch.behind,
+        "last_commit": last_commit,
+    })
+}
+
+pub(crate) fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Ok => "ok",
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::BranchStatus;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn an_object_per_entry() {
+        let mut repo = RepoReport::new(PathBuf::from("/code/app"));
+        repo.branches.push(BranchReport {
+            name: "main".to_owned(),
+            upstream: Some("origin/main".to_owned()),
+            remote: Some("origin".to_owned()),
+            status: BranchStatus::NoUpstream,
+            ahead: 0,
+            behind: 0,
+            last_commit: None,
+        });
+        let mut not_a_repo = RepoReport::new(PathBuf::from("/code/notes"));
+        not_a_repo.findings.push(Finding::NotARepository);
+        let reports = [repo, not_a_repo];
+        let summary = ScanSummary::new(&reports, Duration::from_millis(1500));
+        let rendered = render(&reports, &summary);
+        let lines: Vec<Value> = rendered
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            assert_eq!(line["schema_version"], SCHEMA_VERSION);
+            assert_eq!(line["tool_version"], env!("CARGO_PKG_VERSION"));
+        }
+        assert_eq!(lines[0]["path"], "/code/app");
+        assert_eq!(lines[0]["severity"], "error");
+        assert_eq!(lines[0]["branches"][0]["status"], "no-upstream");
+        assert_eq!(lines[0]["branches"][0]["last_commit"], Value::Null);
+        assert_eq!(lines[1]["findings"][0]["kind"], "not-a-repository");
+        assert_eq!(lines[1]["findings"][0]["message"], "not a git repository");
+        assert_eq!(lines[2]["path"], Value::Null);
+        assert_eq!(lines[2]["summary"]["total"], 2);
+        assert_eq!(lines[2]["summary"]["clean"], 0);
+        assert_eq!(lines[2]["summary"]["with_problems"], 2);
+        assert_eq!(lines[2]["summary"]["by_severity"], json!({ "error": 2 }));
+        assert_eq!(lines[2]["summary"]["duration_seconds"], 1.5);
+    }
+}
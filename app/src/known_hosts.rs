@@ -0,0 +1,273 @@
+//! Verifying ssh host keys against an OpenSSH `known_hosts` file.
+//!
+//! Supports plain and hashed (`|1|salt|hash`) host names, `[host]:port` for servers on other
+//! ports than 22, wildcards and `@revoked` keys.
+//! `@cert-authority` lines are skipped, since libssh2 doesn't hand out host certificates.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const DEFAULT_PORT: u16 = 22;
+
+#[derive(Clone, Debug, Default)]
+pub struct KnownHosts {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    hosts: HostPatterns,
+    key: Vec<u8>,
+    revoked: bool,
+}
+
+#[derive(Clone, Debug)]
+enum HostPatterns {
+    /// Comma separated, possibly with wildcards and `!` negation
+    Plain(Vec<String>),
+    Hashed {
+        salt: Vec<u8>,
+        hash: Vec<u8>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Known,
+    /// The host is listed, but with other keys. Could be a man-in-the-middle.
+    Mismatch,
+    Revoked,
+    Unknown,
+}
+
+impl KnownHosts {
+    /// Reads the file at `path`. A missing file is the same as an empty one.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Lines that can't be understood are skipped, like ssh does
+    pub fn parse(contents: &str) -> Self {
+        let entries = contents
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let mut fields = line.split_whitespace();
+                let mut hosts = fields.next()?;
+                let revoked = match hosts {
+                    "@revoked" => {
+                        hosts = fields.next()?;
+                        true
+                    }
+                    "@cert-authority" => return None,
+                    _ => false,
+                };
+                let _key_type = fields.next()?;
+                let key = BASE64.decode(fields.next()?).ok()?;
+                Some(Entry {
+                    hosts: HostPatterns::parse(hosts)?,
+                    key,
+                    revoked,
+                })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Checks the raw host `key` that `host` presented on `port`. Like ssh, a server on another
+    /// port than 22 is only known through `[host]:port` entries.
+    pub fn verify(&self, host: &str, port: Option<u16>, key: &[u8]) -> Verdict {
+        let port = port.filter(|&port| port != DEFAULT_PORT);
+        let mut listed = false;
+        for entry in self
+            .entries
+            .iter()
+            .filter(|entry| entry.hosts.matches(host, port))
+        {
+            if entry.key == key {
+                if entry.revoked {
+                    return Verdict::Revoked;
+                }
+                listed = true;
+            }
+        }
+        if listed {
+            return Verdict::Known;
+        }
+        let has_other_keys = self
+            .entries
+            .iter()
+            .any(|entry| !entry.revoked && entry.hosts.matches(host, port));
+        if has_other_keys {
+            Verdict::Mismatch
+        } else {
+            Verdict::Unknown
+        }
+    }
+}
+
+impl HostPatterns {
+    fn parse(hosts: &str) -> Option<Self> {
+        let Some(hashed) = hosts.strip_prefix("|1|") else {
+            return Some(HostPatterns::Plain(
+                hosts.split(',').map(str::to_ascii_lowercase).collect(),
+            ));
+        };
+        let (salt, hash) = hashed.split_once('|')?;
+        Some(HostPatterns::Hashed {
+            salt: BASE64.decode(salt).ok()?,
+            hash: BASE64.decode(hash).ok()?,
+        })
+    }
+
+    /// `port` is `None` for the default one
+    fn matches(&self, host: &str, port: Option<u16>) -> bool {
+        let host = host.to_ascii_lowercase();
+        // What ssh looks up, e.g. `[github.com]:2222`
+        let name = match port {
+            Some(port) => format!("[{}]:{}", host, port),
+            None => host.clone(),
+        };
+        let glob_matches = |pattern: &str, text: &str| {
+            glob::Pattern::new(pattern).is_ok_and(|pattern| pattern.matches(text))
+        };
+        match self {
+            HostPatterns::Plain(patterns) => {
+                let matches = |pattern: &str| match split_port(pattern) {
+                    // The brackets would otherwise be taken for a glob character class
+                    Some((pattern, pattern_port)) => {
+                        Some(pattern_port).filter(|&port| port != DEFAULT_PORT) == port
+                            && glob_matches(pattern, &host)
+                    }
+                    None => glob_matches(pattern, &name),
+                };
+                // A matching negated pattern rules the line out regardless of the others
+                let negated = patterns
+                    .iter()
+                    .filter_map(|pattern| pattern.strip_prefix('!'))
+                    .any(matches);
+                !negated
+                    && patterns
+                        .iter()
+                        .filter(|pattern| !pattern.starts_with('!'))
+                        .any(|pattern| matches(pattern))
+            }
+            HostPatterns::Hashed { salt, hash } => {
+                let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(salt) else {
+                    return false;
+                };
+                mac.update(name.as_bytes());
+                mac.verify_slice(hash).is_ok()
+            }
+        }
+    }
+}
+
+/// Splits `[host]:port` into the host and the port
+fn split_port(pattern: &str) -> Option<(&str, u16)> {
+    let (host, port) = pattern.strip_prefix('[')?.split_once("]:")?;
+    Some((host, port.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GITHUB_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl";
+    const OTHER_KEY: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIKiwfq/2bV0dESexaHo4ogzN8ahYhvWKz9RHMzLdj2ar";
+
+    fn key(base64: &str) -> Vec<u8> {
+        BASE64.decode(base64).unwrap()
+    }
+
+    #[test]
+    fn plain_and_hashed_hosts() {
+        let known_hosts = KnownHosts::parse(&format!(
+            "# comment\n\
+             gitlab.com,*.example.com,!evil.example.com ssh-ed25519 {OTHER_KEY}\n\
+             |1|MDEyMzQ1Njc4OWFiY2RlZmdoaWo=|anUhMiNmCXr96buiAF9of6zM1wM= ssh-ed25519 {GITHUB_KEY}\n"
+        ));
+        assert_eq!(
+            known_hosts.verify("GitHub.com", None, &key(GITHUB_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("github.com", None, &key(OTHER_KEY)),
+            Verdict::Mismatch
+        );
+        assert_eq!(
+            known_hosts.verify("git.example.com", None, &key(OTHER_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("evil.example.com", None, &key(OTHER_KEY)),
+            Verdict::Unknown
+        );
+    }
+
+    #[test]
+    fn revoked_keys() {
+        let known_hosts = KnownHosts::parse(&format!(
+            "github.com ssh-ed25519 {GITHUB_KEY}\n\
+             @revoked * ssh-ed25519 {OTHER_KEY}\n"
+        ));
+        assert_eq!(
+            known_hosts.verify("github.com", None, &key(OTHER_KEY)),
+            Verdict::Revoked
+        );
+        assert_eq!(
+            known_hosts.verify("gitlab.com", None, &key(OTHER_KEY)),
+            Verdict::Revoked
+        );
+        assert_eq!(
+            known_hosts.verify("gitlab.com", Some(2222), &key(OTHER_KEY)),
+            Verdict::Revoked
+        );
+    }
+
+    #[test]
+    fn hosts_on_other_ports() {
+        // The hashed entry is for [github.com]:2222
+        let known_hosts = KnownHosts::parse(&format!(
+            "[gitlab.com]:2222,[*.example.com]:2200 ssh-ed25519 {OTHER_KEY}\n\
+             [github.com]:22 ssh-ed25519 {GITHUB_KEY}\n\
+             |1|MDEyMzQ1Njc4OWFiY2RlZmdoaWo=|vgL8ciQZIh566NPd1H/2+H/tGgs= ssh-ed25519 {GITHUB_KEY}\n"
+        ));
+        assert_eq!(
+            known_hosts.verify("gitlab.com", Some(2222), &key(OTHER_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("gitlab.com", None, &key(OTHER_KEY)),
+            Verdict::Unknown
+        );
+        assert_eq!(
+            known_hosts.verify("git.example.com", Some(2200), &key(OTHER_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("github.com", Some(22), &key(GITHUB_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("github.com", Some(2222), &key(GITHUB_KEY)),
+            Verdict::Known
+        );
+        assert_eq!(
+            known_hosts.verify("github.com", Some(2222), &key(OTHER_KEY)),
+            Verdict::Mismatch
+        );
+    }
+}
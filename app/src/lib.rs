@@ -0,0 +1,20 @@
+pub mod check;
+pub mod credentials;
+pub mod csv_report;
+pub mod discover;
+pub mod error;
+pub mod fetch_cache;
+pub mod fetch_limit;
+pub mod json_report;
+pub mod known_hosts;
+pub mod markdown;
+pub mod plain_report;
+pub mod printer;
+pub mod report;
+pub mod reporter;
+pub mod sarif;
+pub mod ssh_config;
+pub mod text_report;
+
+#[cfg(test)]
+mod test_support;
@@ -1,722 +1,1242 @@
 mod cli {
-    use clap::Parser;
+    use check_gits::check::{ExtraScheme, ModifiedSince};
+    use check_gits::discover::SortOrder;
+    use check_gits::fetch_limit::DEFAULT_MAX_FETCHES;
+    use check_gits::printer::{OutputFormat, ThemeName, DEFAULT_DATE_FORMAT};
+    use check_gits::report::{Category, SeverityLevel, SeverityMap};
+    use chrono::format::{Item, StrftimeItems};
+    use clap::error::ErrorKind;
+    use clap::parser::ValueSource;
+    use clap::{CommandFactory, FromArgMatches, Parser};
+    use clap_complete::Shell;
+    use regex::Regex;
     use std::path::PathBuf;
 
     #[derive(Parser, Debug)]
     #[command(version, about, long_about = None)]
-
     pub struct Args {
-        /// Show all entries
-        #[arg(short = 'a', long = "verbose")]
-        pub verbose: bool,
+        /// Show more detail. -v shows every entry and the fetches that went fine, -vv also traces each
+        /// branch's upstream and remote, and -vvv also the ancestry checks behind each verdict.
+        #[arg(short = 'v', short_alias = 'a', long = "verbose", action = clap::ArgAction::Count)]
+        pub verbose: u8,
 
-        /// Path to the ssh private key to use for authentication. Defaults to ~/.ssh/id_rsa
-        #[arg(short = 'i', long = "ssh-private-key")]
-        pub ssh_private_key: Option<PathBuf>,
+        /// Only print warnings and errors, so a clean run prints nothing at all
+        #[arg(short = 'q', long = "quiet", conflicts_with = "verbose")]
+        pub quiet: bool,
 
-        /// The directory where the repositories are stored. Defaults to the current working directory.
-        pub repos_directory: Option<PathBuf>,
-    }
+        /// Also list synced branches when not verbose
+        #[arg(long = "show-synced")]
+        pub show_synced: bool,
 
-    pub fn get_args() -> Args {
-        Args::parse()
-    }
-}
+        /// Path to an ssh private key to use for authentication. Can be repeated; the keys are tried in
+        /// order until one is accepted. Defaults to whichever of ~/.ssh/id_ed25519, ~/.ssh/id_ecdsa and
+        /// ~/.ssh/id_rsa exist. IdentityFiles configured for the host in ~/.ssh/config are tried first,
+        /// and when no key is given, so are the -i keys of git's core.sshCommand or GIT_SSH_COMMAND.
+        #[arg(short = 'i', long = "ssh-private-key", env = "CHECK_GITS_SSH_KEY")]
+        pub ssh_private_keys: Vec<PathBuf>,
 
-use anyhow::{ensure, Context, Error, Result};
-use std::{fs};
+        /// The known_hosts file to verify ssh servers' host keys against. Defaults to ~/.ssh/known_hosts.
+        #[arg(long = "known-hosts", value_name = "PATH")]
+        pub known_hosts: Option<PathBuf>,
 
-struct Printer {
-    verbose: bool,
-    messages: Vec<String>,
-}
+        /// Accept any ssh host key. Only use this on networks you trust.
+        #[arg(long = "insecure-skip-host-verify", conflicts_with = "known_hosts")]
+        pub insecure_skip_host_verify: bool,
 
-const UNEXPECTED_GENERAL_ENTRY_ERROR: &str = "Something unexpectedly failed for the current entry";
+        /// Fetch through this proxy, e.g. http://proxy.example.com:8080. By default git's http.proxy
+        /// config and the http_proxy/https_proxy environment variables are used.
+        #[arg(long = "proxy", value_name = "URL")]
+        pub proxy: Option<String>,
 
-impl Printer {
-    fn flush(&mut self) {
-        for message in self.messages.iter() {
-            println!("{}", message);
-        }
-        self.messages.clear();
+        /// Only fetch and check this remote. Can be repeated. Branches tracking other remotes get a
+        /// warning instead of a verdict.
+        #[arg(long = "remotes", value_name = "NAME")]
+        pub remotes: Vec<String>,
+
+        /// Shorthand for --remotes origin
+        #[arg(long = "origin-only", conflicts_with = "remotes")]
+        pub origin_only: bool,
+
+        /// Also count remotes with these url schemes as qualifying, e.g. 'file,git'
+        #[arg(
+            long = "allow-schemes",
+            value_name = "SCHEMES",
+            value_enum,
+            value_delimiter = ','
+        )]
+        pub allow_schemes: Vec<ExtraScheme>,
+
+        /// Also count remotes whose url matches this regex as qualifying, e.g.
+        /// '^git@github\.mycorp\.net:(teamA|teamB)/'. Can be repeated.
+        #[arg(long = "remote-regex", value_name = "PATTERN", value_parser = parse_remote_regex)]
+        pub remote_regexes: Vec<Regex>,
+
+        /// Give up on fetching a remote after this many seconds. Waits indefinitely by default.
+        #[arg(long = "fetch-timeout", value_name = "SECONDS")]
+        pub fetch_timeout: Option<u64>,
+
+        /// Don't fetch remotes that were fetched less than this many seconds ago, and check against
+        /// what was fetched then. When each remote was fetched is kept in the user's cache directory.
+        /// Defaults to 0, or to 300 with --watch.
+        #[arg(long = "fetch-cooldown", value_name = "SECONDS")]
+        pub fetch_cooldown: Option<u64>,
+
+        /// How many times to retry a fetch that failed with a network error
+        #[arg(long = "fetch-retries", value_name = "COUNT", default_value_t = 2)]
+        pub fetch_retries: u32,
+
+        /// How many of a repository's remotes to fetch at the same time
+        #[arg(long = "fetch-jobs", value_name = "COUNT", default_value_t = 1)]
+        pub fetch_jobs: usize,
+
+        /// How many of a repository's branches to check at the same time. Worth raising for
+        /// repositories with many branches, where the ancestry checks take most of the time.
+        #[arg(long = "branch-jobs", value_name = "COUNT", default_value_t = 1)]
+        pub branch_jobs: usize,
+
+        /// How many fetches may be on the network at the same time over the whole run, however
+        /// many jobs there are
+        #[arg(
+            long = "max-fetch-parallelism",
+            value_name = "COUNT",
+            default_value_t = DEFAULT_MAX_FETCHES,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        pub max_fetch_parallelism: usize,
+
+        /// List the repositories and the remotes that would be fetched, without fetching anything
+        #[arg(long = "dry-run")]
+        pub dry_run: bool,
+
+        /// Stop at the first repository with a problem and exit with a non-zero code
+        #[arg(long = "fail-fast")]
+        pub fail_fast: bool,
+
+        /// Count a branch as synced if the same-named branch on any qualifying remote contains it,
+        /// not just its configured upstream
+        #[arg(long = "any-remote")]
+        pub any_remote: bool,
+
+        /// Skip repositories that nobody has committed to or staged in since this long ago, e.g. 7d,
+        /// or since this date, e.g. 2024-05-01. Durations can be in s, m, h, d or w.
+        #[arg(
+            long = "modified-since",
+            value_name = "DURATION|DATE",
+            value_parser = ModifiedSince::parse
+        )]
+        pub modified_since: Option<ModifiedSince>,
+
+        /// Also point out git notes that aren't on any fetched remote. A remote's notes are only
+        /// known if its fetch refspecs include them, e.g. +refs/notes/*:refs/notes/origin/*.
+        #[arg(long = "check-notes")]
+        pub check_notes: bool,
+
+        /// Also point out tags whose commits aren't on any fetched remote, annotated or not
+        #[arg(long = "check-tags")]
+        pub check_tags: bool,
+
+        /// Also make sure the default branch of the main remote (--preferred-remote, else origin)
+        /// has a local branch that's neither ahead of nor behind it, whatever other branches exist
+        #[arg(long = "check-default-branch")]
+        pub check_default_branch: bool,
+
+        /// Don't look for uncommitted changes and untracked files, only check the branches. This is
+        /// much faster in huge checkouts, but work that was never committed goes unnoticed, so a
+        /// clean report no longer means all the work is safe.
+        #[arg(long = "no-verify-working-tree")]
+        pub no_verify_working_tree: bool,
+
+        /// Check a branch without a configured upstream against the same-named branch on this
+        /// remote, if there is one, before calling it local-only
+        #[arg(long = "preferred-remote", value_name = "NAME")]
+        pub preferred_remote: Option<String>,
+
+        /// Also compare every local branch with the same-named branch on this remote, whatever
+        /// upstream the branch has configured
+        #[arg(long = "compare-remote", value_name = "NAME")]
+        pub compare_remote: Option<String>,
+
+        /// Glob of branch names that may legitimately have no upstream, e.g. 'wip/*'. Can be repeated.
+        /// This only silences the missing-upstream verdict; branches with an upstream are still checked.
+        #[arg(long = "allow-local", value_name = "GLOB")]
+        pub allow_local: Vec<String>,
+
+        /// Only warn about branches that are ahead of their upstream, without failing the run.
+        /// Diverged branches and branches without an upstream are still errors.
+        #[arg(long = "allow-ahead")]
+        pub allow_ahead: bool,
+
+        /// Change how seriously a kind of verdict is taken, e.g. 'untracked=warn'. Can be repeated.
+        /// LEVEL is one of error, warn, ok and ignore, which leaves the verdict out entirely.
+        /// By default ahead, diverged, no_upstream, uncommitted, untracked and no_remotes are errors,
+        /// stash is a warning, and behind is a warning that's only shown with --show-behind (giving
+        /// it a level shows it).
+        #[arg(long = "severity", value_name = "CATEGORY=LEVEL", value_parser = SeverityMap::parse_override)]
+        pub severity: Vec<(Category, SeverityLevel)>,
+
+        /// Also warn about branches whose upstream has commits that haven't been pulled yet
+        #[arg(long = "show-behind")]
+        pub show_behind: bool,
+
+        /// Also list branches on the remotes that no local branch tracks and that haven't been merged
+        #[arg(long = "show-unfetched-remote-branches")]
+        pub show_unfetched_remote_branches: bool,
+
+        /// Only check the currently checked-out branch (or HEAD's commit, if it's detached)
+        #[arg(long = "current-branch-only")]
+        pub current_branch_only: bool,
+
+        /// Skip entries whose names start with a dot, like .cache. This is the default.
+        #[arg(long = "skip-hidden", overrides_with = "include_hidden")]
+        pub skip_hidden: bool,
+
+        /// Check entries whose names start with a dot too
+        #[arg(long = "include-hidden", overrides_with = "skip_hidden")]
+        pub include_hidden: bool,
+
+        /// Check the entries that a .check-gits-ignore file in the scanned directory lists too
+        #[arg(long = "no-ignore-file")]
+        pub no_ignore_file: bool,
+
+        /// Skip repositories that the repository they're nested in ignores, like vendored tools
+        #[arg(long = "skip-ignored")]
+        pub skip_ignored: bool,
+
+        /// Check symlinked entries like any other, instead of skipping them
+        #[arg(long = "follow-symlinks")]
+        pub follow_symlinks: bool,
+
+        /// How to print the report
+        #[arg(long = "format", value_enum, default_value_t = OutputFormat::Text)]
+        pub format: OutputFormat,
+
+        /// The glyphs that start each line of the text report
+        #[arg(long = "theme", value_enum, default_value_t = ThemeName::Emoji)]
+        pub theme: ThemeName,
+
+        /// How to show the dates of the last commits on flagged branches, in strftime style
+        #[arg(
+            long = "date-format",
+            value_name = "FORMAT",
+            default_value = DEFAULT_DATE_FORMAT,
+            value_parser = parse_date_format
+        )]
+        pub date_format: String,
+
+        /// Print paths in full even when lines are wider than the terminal
+        #[arg(long = "no-truncate")]
+        pub no_truncate: bool,
+
+        /// The order to report the entries in
+        #[arg(long = "sort", value_enum, default_value_t = SortOrder::Name)]
+        pub sort: SortOrder,
+
+        /// Show paths relative to the directory being scanned (or the current directory with
+        /// --from-file)
+        #[arg(long = "relative", conflicts_with = "absolute")]
+        pub relative: bool,
+
+        /// Show canonicalized absolute paths, however the directories were given
+        #[arg(long = "absolute")]
+        pub absolute: bool,
+
+        /// Print warnings and errors to stderr and everything else to stdout
+        #[arg(long = "errors-to-stderr")]
+        pub errors_to_stderr: bool,
+
+        /// Check the repositories listed in this file, one path per line, instead of scanning a directory.
+        /// Use '-' to read the list from stdin. Blank lines and lines starting with '#' are ignored.
+        #[arg(long = "from-file", value_name = "PATH")]
+        pub from_file: Option<PathBuf>,
+
+        /// Write the report to this file instead of stdout. Fetch progress still goes to the terminal.
+        #[arg(long = "output", value_name = "PATH")]
+        pub output: Option<PathBuf>,
+
+        /// Add to the --output file instead of overwriting it
+        #[arg(long = "append", requires = "output")]
+        pub append: bool,
+
+        /// List the commits that pushing each branch that's ahead of its upstream would send, and
+        /// where to. Nothing is pushed.
+        #[arg(long = "push-preview")]
+        pub push_preview: bool,
+
+        /// Offer to push each branch that's ahead of its upstream, asking first. Diverged branches are
+        /// only ever reported.
+        #[arg(long = "interactive", conflicts_with = "sort")]
+        pub interactive: bool,
+
+        /// Print nothing while scanning, then just the entries with problems, one line each with
+        /// what's wrong
+        #[arg(long = "only-problems", conflicts_with_all = ["format", "interactive", "sort"])]
+        pub only_problems: bool,
+
+        /// Scan again every this many seconds, replacing the previous report, until interrupted
+        #[arg(
+            long = "watch",
+            value_name = "SECONDS",
+            value_parser = clap::value_parser!(u64).range(1..),
+            conflicts_with_all = ["interactive", "output"]
+        )]
+        pub watch: Option<u64>,
+
+        /// Send a desktop notification at the end if any entry has problems
+        #[arg(long = "notify")]
+        pub notify: bool,
+
+        /// Print a completion script for the given shell and exit
+        #[arg(long = "generate-completions", value_name = "SHELL")]
+        pub generate_completions: Option<Shell>,
+
+        /// Print what each exit code means as JSON and exit
+        #[arg(long = "print-exit-codes")]
+        pub print_exit_codes: bool,
+
+        /// Print the path of each repository that would be checked, one per line, and exit without
+        /// checking any. The same rules as a scan decide which entries are repositories.
+        #[arg(long = "list-repos", conflicts_with_all = ["watch", "interactive"])]
+        pub list_repos: bool,
+
+        /// The directories where the repositories are stored. Defaults to the current working directory.
+        /// A quoted glob like '~/code/*/repos' stands for every directory it matches.
+        #[arg(env = "CHECK_GITS_REPOS_DIR")]
+        pub repos_directories: Vec<PathBuf>,
     }
-    fn new(verbose: bool) -> Self {
-        Self {
-            verbose,
-            messages: Vec::new(),
+
+    pub fn get_args() -> Args {
+        let matches = command().get_matches();
+        let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+        // Only directories given on the command line clash with --from-file. One picked up from
+        // CHECK_GITS_REPOS_DIR is just a default that the list overrides.
+        if args.from_file.is_some() {
+            if matches.value_source("repos_directories") == Some(ValueSource::CommandLine) {
+                Args::command()
+                    .error(
+                        ErrorKind::ArgumentConflict,
+                        "the argument '--from-file <PATH>' cannot be used with '[REPOS_DIRECTORIES]...'",
+                    )
+                    .exit();
+            }
+            args.repos_directories.clear();
         }
+        args
     }
-    fn msg_symlink(path: &std::path::Path) -> String {
-        format!("⚠️ Found symlink: {}. Ignoring this entry, as at the time of making this tool, I have never made symlinks in there, so I don't know what it means semantically.", path.display())
-    }
-    fn log_symlink(&mut self, path: &std::path::Path) {
-        self.messages.push(Self::msg_symlink(path));
-    }
-    fn msg_file(path: &std::path::Path) -> String {
-        format!("❗ Found file: {}. Files are unlikely to be git-pushed; move them somewhere safe if necessary.", path.display())
-    }
-    fn log_file(&mut self, path: &std::path::Path) {
-        self.messages.push(Self::msg_file(path));
-    }
-    fn msg_nongit_dir(path: &std::path::Path, msg: &str) -> String {
-        format!(
-            "❗ {}: {}. This is not a git repository.",
-            msg,
-            path.display()
-        )
-    }
-    fn log_nongit_dir(&mut self, path: &std::path::Path, msg: &str) {
-        self.messages.push(Self::msg_nongit_dir(path, msg));
-    }
-    fn msg_local_only_branch(entry: &fs::DirEntry, local_branch: git2::Branch) -> String {
-        format!(
-            "💥 {}: Local branch {} has no upstream (tracking remote branch)",
-            entry.path().display(),
-            local_branch.name().unwrap().unwrap()
-        )
-    }
-    fn log_local_only_branch(&mut self, entry: &fs::DirEntry, local_branch: git2::Branch) {
-        self.messages
-            .push(Self::msg_local_only_branch(entry, local_branch));
-    }
-    fn msg_general_entry_error(error: Error) -> String {
-        format!("🚨 {}: {}", UNEXPECTED_GENERAL_ENTRY_ERROR, error)
-    }
-    fn log_general_entry_error(&mut self, error: Error) {
-        self.messages.push(Self::msg_general_entry_error(error));
-    }
-    fn msg_general_entry_error_for_entry(entry: &fs::DirEntry, error: Error) -> String {
-        format!(
-            "🚨 Failed for the entry {}: {}",
-            entry.path().display(),
-            error
-        )
-    }
-    fn log_general_entry_error_for_entry(&mut self, entry: &fs::DirEntry, error: Error) {
-        self.messages
-            .push(Self::msg_general_entry_error_for_entry(entry, error));
+
+    /// Compiled up front, so a typo fails before anything is fetched
+    fn parse_remote_regex(value: &str) -> Result<Regex, String> {
+        Regex::new(value).map_err(|error| format!("invalid regex: {}", error))
     }
-    fn msg_remote_not_found(entry: &fs::DirEntry, remote: &str, error: Error) -> String {
-        format!("🚨 {}: Remote {} not found: {}", entry.path().display(), remote, error)
+
+    /// A bad format would only fail once the first date is printed
+    fn parse_date_format(value: &str) -> Result<String, String> {
+        match StrftimeItems::new(value).any(|item| item == Item::Error) {
+            true => Err(format!("invalid strftime format '{}'", value)),
+            false => Ok(value.to_owned()),
+        }
     }
-    fn log_remote_not_found(&mut self, entry: &fs::DirEntry, remote_name: &str, error: Error) {
-        self.messages
-            .push(Self::msg_remote_not_found(entry, remote_name, error));
+
+    /// The exit code legend goes at the end of --help, where CI authors look for it. --version
+    /// also tells which libgit2 is linked, while -V keeps to the one line.
+    fn command() -> clap::Command {
+        let exit_codes: String = super::EXIT_CODES
+            .iter()
+            .map(|(code, _, meaning)| format!("\n  {:<3}  {}", code, meaning))
+            .collect();
+        Args::command()
+            .after_long_help(format!("Exit codes:{}", exit_codes))
+            // Built once per run, so leaking it is cheaper than pulling in clap's `string` feature
+            .long_version(&*long_version().leak())
     }
-    fn msg_unqualified_remote(entry: &fs::DirEntry, remote_name: &str) -> String {
+
+    /// Which transports the linked libgit2 supports decides whether ssh remotes can be fetched
+    /// at all, so bug reports need it
+    fn long_version() -> String {
+        let version = git2::Version::get();
+        let (major, minor, patch) = version.libgit2_version();
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
         format!(
-            "⚠️ {}: Remote {} is not a qualifying remote",
-            entry.path().display(),
-            remote_name
+            "{}\nlibgit2 {}.{}.{} ({}), git2 crate {}\nssh: {}, https: {}, threads: {}",
+            env!("CARGO_PKG_VERSION"),
+            major,
+            minor,
+            patch,
+            if version.vendored() {
+                "vendored"
+            } else {
+                "system"
+            },
+            version.crate_version(),
+            yes_no(version.ssh()),
+            yes_no(version.https()),
+            yes_no(version.threads())
         )
     }
-    fn log_unqualified_remote(&mut self, entry: &fs::DirEntry, remote_name: &str) {
-        self.messages
-            .push(Self::msg_unqualified_remote(entry, remote_name));
-    }
-    fn msg_remote_fetch_failed(
-        entry: &fs::DirEntry,
-        remote_name: &str,
-        error: git2::Error,
-    ) -> String {
-        format!(
-            "🚨 {}: Failed to fetch remote {}: {}",
-            entry.path().display(),
-            remote_name,
-            error
-        )
+
+    pub fn print_completions(shell: Shell) {
+        let mut command = Args::command();
+        let name = command.get_name().to_owned();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
     }
-    fn log_remote_fetch_failed(
-        &mut self,
-        entry: &fs::DirEntry,
-        remote_name: &str,
-        error: git2::Error,
-    ) {
-        self.messages
-            .push(Self::msg_remote_fetch_failed(entry, remote_name, error));
+}
+
+use anyhow::{anyhow, ensure, Context, Result};
+use check_gits::check::{self, check_repo, CheckOptions};
+use check_gits::credentials::{Credentials, HostKeyCheck};
+use check_gits::csv_report::CsvReporter;
+use check_gits::discover::{self, SortOrder};
+use check_gits::error::CheckError;
+use check_gits::fetch_cache::FetchCache;
+use check_gits::fetch_limit::FetchLimit;
+use check_gits::json_report::JsonReporter;
+use check_gits::known_hosts::KnownHosts;
+use check_gits::markdown::MarkdownReporter;
+use check_gits::plain_report::PlainReporter;
+use check_gits::printer::{OutputFormat, PathStyle, Printer, Theme, Verbosity};
+use check_gits::report::{BranchReport, BranchStatus, Finding, RepoReport, ScanSummary};
+use check_gits::reporter::{OutputFile, Reporter};
+use check_gits::sarif::SarifReporter;
+use check_gits::ssh_config::SshConfig;
+use check_gits::text_report::TextReporter;
+use serde_json::json;
+use std::any::Any;
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Keys under ~/.ssh that are tried, in this order, when no --ssh-private-key is given
+const DEFAULT_SSH_KEY_NAMES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// How many of the slowest entries to name at the end of a verbose run
+const SLOWEST_ENTRIES_SHOWN: usize = 3;
+
+/// How often remotes are fetched with --watch, unless --fetch-cooldown says otherwise
+const WATCH_FETCH_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+/// How often a --watch wait checks for Ctrl-C
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exit code when every entry was checked and none had an error
+const EXIT_CLEAN: u8 = 0;
+
+/// Exit code when some entry had an error, e.g. unpushed commits
+const EXIT_PROBLEMS: u8 = 1;
+
+/// Exit code when the tool itself failed, e.g. on bad arguments or an unwritable --output
+const EXIT_TOOL_ERROR: u8 = 2;
+
+/// Exit code when the run was cut short by Ctrl-C, following the shell's 128 + SIGINT convention
+const EXIT_INTERRUPTED: u8 = 130;
+
+/// Every exit code with a name and what it means, for --help and --print-exit-codes
+const EXIT_CODES: [(u8, &str, &str); 4] = [
+    (
+        EXIT_CLEAN,
+        "clean",
+        "every entry was checked and none had an error",
+    ),
+    (
+        EXIT_PROBLEMS,
+        "problems",
+        "some entry had an error, e.g. unpushed commits or uncommitted changes",
+    ),
+    (
+        EXIT_TOOL_ERROR,
+        "tool-error",
+        "check-gits itself failed, e.g. on bad arguments or an unwritable --output",
+    ),
+    (
+        EXIT_INTERRUPTED,
+        "interrupted",
+        "the run was cut short by Ctrl-C",
+    ),
+];
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(exit_code) => exit_code,
+        Err(error) => {
+            // The same as returning the error from main, but without colliding with EXIT_PROBLEMS
+            eprintln!("Error: {:?}", error);
+            ExitCode::from(EXIT_TOOL_ERROR)
+        }
     }
-    fn msg_remote_bad_name(entry: &fs::DirEntry, remote_name_bytes: &[u8]) -> String {
-        format!(
-            "🚨 {}: Remote {} skipped due to invalid utf8",
-            entry.path().display(),
-            String::from_utf8_lossy(remote_name_bytes)
-        )
+}
+
+fn run() -> Result<ExitCode> {
+    // Diagnostics for debugging the tool itself, controlled by RUST_LOG; the report goes
+    // through the Printer
+    env_logger::init();
+    let args = cli::get_args();
+    if let Some(shell) = args.generate_completions {
+        cli::print_completions(shell);
+        return Ok(ExitCode::SUCCESS);
     }
-    fn log_remote_bad_name(&mut self, entry: &fs::DirEntry, remote_name_bytes: &[u8]) {
-        self.messages
-            .push(Self::msg_remote_bad_name(entry, remote_name_bytes));
+    if args.print_exit_codes {
+        print_exit_codes();
+        return Ok(ExitCode::SUCCESS);
     }
-    fn msg_remote_no_name(entry: &fs::DirEntry) -> String {
-        format!(
-            "🚨 {}: A remote was skipped because it was not named",
-            entry.path().display()
-        )
-    }
-    fn log_remote_no_name(&mut self, entry: &fs::DirEntry) {
-        self.messages.push(Self::msg_remote_no_name(entry));
+    let verbosity = Verbosity::from_count(args.verbose);
+    log::debug!("{:?}", args);
+    ensure!(
+        !args.interactive || io::stdin().is_terminal(),
+        "--interactive needs a terminal to ask on"
+    );
+    let repos_directories = if args.repos_directories.is_empty() {
+        vec![std::env::current_dir().context("Failed to get current directory")?]
+    } else {
+        discover::expand_repos_directories(&args.repos_directories, dirs::home_dir().as_deref())?
+    };
+    if args.list_repos {
+        return list_repos(&args, &repos_directories);
     }
-    fn msg_remote_bad_url(entry: &fs::DirEntry, remote_name: &str, url: &[u8]) -> String {
-        format!(
-            "🚨 {}: Remote {} has a bad url: {}",
-            entry.path().display(),
-            remote_name,
-            String::from_utf8_lossy(url)
-        )
+    let ssh_private_keys = if args.ssh_private_keys.is_empty() {
+        let home_dir = dirs::home_dir().context("Failed to get home directory")?;
+        DEFAULT_SSH_KEY_NAMES
+            .iter()
+            .map(|name| home_dir.join(".ssh").join(name))
+            .filter(|ssh_private_key| ssh_private_key.is_file())
+            .collect()
+    } else {
+        for ssh_private_key in &args.ssh_private_keys {
+            let ssh_private_key_metadata = fs::metadata(ssh_private_key).context(format!(
+                "Failed to get metadata for ssh private key: {}",
+                ssh_private_key.display()
+            ))?;
+            ensure!(
+                ssh_private_key_metadata.is_file(),
+                "The ssh private key path is not a file: {}",
+                ssh_private_key.display()
+            );
+        }
+        args.ssh_private_keys.clone()
+    };
+    let ssh_config = match dirs::home_dir() {
+        Some(home_dir) => {
+            let ssh_config_path = home_dir.join(".ssh/config");
+            SshConfig::load(&ssh_config_path, Some(&home_dir)).with_context(|| {
+                format!("Failed to read ssh config: {}", ssh_config_path.display())
+            })?
+        }
+        None => SshConfig::default(),
+    };
+    let host_key_check = if args.insecure_skip_host_verify {
+        HostKeyCheck::Skip
+    } else {
+        let known_hosts_path = match &args.known_hosts {
+            Some(known_hosts_path) => known_hosts_path.clone(),
+            None => dirs::home_dir()
+                .context("Failed to get home directory")?
+                .join(".ssh/known_hosts"),
+        };
+        HostKeyCheck::KnownHosts(KnownHosts::load(&known_hosts_path).with_context(|| {
+            format!("Failed to read known_hosts: {}", known_hosts_path.display())
+        })?)
+    };
+    let mut check_options = CheckOptions::new(
+        Credentials::new(ssh_private_keys, ssh_config)
+            .with_host_key_check(host_key_check)
+            .with_explicit_ssh_keys(!args.ssh_private_keys.is_empty()),
+    );
+    check_options.fetch_timeout = args.fetch_timeout.map(Duration::from_secs);
+    check_options.proxy = args.proxy.clone();
+    check_options.qualifying_url_prefixes.extend(
+        args.allow_schemes
+            .iter()
+            .map(|scheme| scheme.url_prefix().to_owned()),
+    );
+    check_options.qualifying_url_patterns = args.remote_regexes.clone();
+    check_options.fetch_retries = args.fetch_retries;
+    // Repeated scans would otherwise fetch every remote every time
+    let fetch_cooldown = args.fetch_cooldown.unwrap_or(match args.watch {
+        Some(_) => WATCH_FETCH_COOLDOWN.as_secs(),
+        None => 0,
+    });
+    check_options.fetch_cooldown = Duration::from_secs(fetch_cooldown);
+    // Only runs that use the cache pay for reading and writing it
+    let fetch_cache_path = match fetch_cooldown {
+        0 => None,
+        _ => FetchCache::default_path(),
+    };
+    if let Some(fetch_cache_path) = &fetch_cache_path {
+        check_options.fetch_cache =
+            Arc::new(FetchCache::load(fetch_cache_path).with_context(|| {
+                format!(
+                    "Failed to read the fetch cache: {}",
+                    fetch_cache_path.display()
+                )
+            })?);
     }
-    fn log_remote_bad_url(&mut self, entry: &fs::DirEntry, remote_name: &str, url: &[u8]) {
-        self.messages
-            .push(Self::msg_remote_bad_url(entry, remote_name, url));
+    check_options.fetch_jobs = args.fetch_jobs;
+    check_options.branch_jobs = args.branch_jobs;
+    check_options.fetch_limit = Arc::new(FetchLimit::new(args.max_fetch_parallelism));
+    check_options.any_remote = args.any_remote;
+    check_options.current_branch_only = args.current_branch_only;
+    check_options.show_behind = args.show_behind;
+    check_options.allow_ahead = args.allow_ahead;
+    check_options.dry_run = args.dry_run;
+    check_options.push_preview = args.push_preview;
+    check_options.selected_remotes = if args.origin_only {
+        Some(vec!["origin".to_owned()])
+    } else if !args.remotes.is_empty() {
+        Some(args.remotes.clone())
+    } else {
+        None
+    };
+    for (category, level) in &args.severity {
+        check_options.severity_map.set(*category, *level);
     }
-    fn msg_branch_name_error(entry: &fs::DirEntry, error: Error) -> String {
-        format!(
-            "🚨 {}: Failed to get the name of a branch: {}",
-            entry.path().display(),
-            error
+    check_options.show_unfetched_remote_branches = args.show_unfetched_remote_branches;
+    check_options.compare_remote = args.compare_remote.clone();
+    check_options.preferred_remote = args.preferred_remote.clone();
+    check_options.verify_working_tree = !args.no_verify_working_tree;
+    check_options.check_notes = args.check_notes;
+    check_options.check_tags = args.check_tags;
+    check_options.check_default_branch = args.check_default_branch;
+    check_options.modified_since = args.modified_since;
+    // Progress lines go straight to the terminal, which would garble the other formats
+    check_options.show_fetch_progress =
+        verbosity >= Verbosity::Entries && args.format == OutputFormat::Text && !args.only_problems;
+    check_options.theme = args.theme.into();
+    check_options.allowed_local_branches = args
+        .allow_local
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid --allow-local pattern: {}", pattern))
+        })
+        .collect::<Result<_>>()?;
+
+    // Every entry's lines are written as soon as it's done, so an interrupted run leaves a
+    // complete file behind
+    let output: Option<OutputFile> = match &args.output {
+        Some(output_path) => {
+            let file = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(args.append)
+                .truncate(!args.append)
+                .open(output_path)
+                .with_context(|| {
+                    format!("Failed to open output file: {}", output_path.display())
+                })?;
+            Some(Arc::new(Mutex::new(file)))
+        }
+        None => None,
+    };
+    // Piped or saved output is kept whole, since there's no width to fit it to
+    let width = (!args.no_truncate && output.is_none() && io::stdout().is_terminal())
+        .then(terminal_size::terminal_size)
+        .flatten()
+        .map(|(terminal_size::Width(width), _)| usize::from(width));
+
+    // The first Ctrl-C lets the current entry finish so its findings aren't lost; a second one
+    // aborts right away
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_INTERRUPTED.into());
+        }
+    })
+    .context("Failed to install the Ctrl-C handler")?;
+
+    let scan = || {
+        scan(
+            &args,
+            verbosity,
+            &repos_directories,
+            &check_options,
+            fetch_cache_path.as_deref(),
+            output.as_ref(),
+            width,
         )
+    };
+    let Some(interval) = args.watch else {
+        return scan().map(|summary| exit_code(&summary));
+    };
+    loop {
+        if io::stdout().is_terminal() {
+            // Clear the screen and go back to its top left
+            print!("\x1b[2J\x1b[H");
+        }
+        let exit_code = exit_code(&scan()?);
+        // Interrupting the wait is how watching normally ends, so the last scan has the say
+        let next_scan = Instant::now() + Duration::from_secs(interval);
+        while Instant::now() < next_scan {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                return Ok(exit_code);
+            }
+            thread::sleep(WATCH_POLL_INTERVAL);
+        }
     }
-    fn log_branch_name_error(&mut self, entry: &fs::DirEntry, error: Error) {
-        self.messages
-            .push(Self::msg_branch_name_error(entry, error));
+}
+
+/// The "checking 34/142: name" line that shows how far along a quiet scan is. It's redrawn in
+/// place on stderr and erased before anything else is printed, so it never ends up in the report.
+struct ProgressLine {
+    total: usize,
+    width: Option<usize>,
+    /// Whether there's a line to erase
+    shown: bool,
+}
+
+impl ProgressLine {
+    /// Only worth showing when someone is watching, and nothing else is printed while checking
+    fn for_scan(args: &cli::Args, verbosity: Verbosity, total: usize) -> Option<Self> {
+        let shown = verbosity == Verbosity::Normal
+            && !args.quiet
+            && args.format == OutputFormat::Text
+            && io::stdout().is_terminal()
+            && io::stderr().is_terminal();
+        shown.then(|| Self {
+            total,
+            width: terminal_size::terminal_size()
+                .map(|(terminal_size::Width(width), _)| usize::from(width)),
+            shown: false,
+        })
     }
-    fn msg_local_branch_has_no_remote_tracking_branch(
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        error: Error,
-    ) -> String {
-        format!(
-            "💥 {}: Local branch {} has no remote tracking branch: {}",
-            entry.path().display(),
-            branch_name,
-            error
-        )
+
+    fn show(&mut self, number: usize, entry: &Path) {
+        let name = entry
+            .file_name()
+            .unwrap_or(entry.as_os_str())
+            .to_string_lossy();
+        let mut line = format!("checking {}/{}: {}", number, self.total, name);
+        // A line that wraps can't be erased with a carriage return
+        if let Some(width) = self.width {
+            if let Some((end, _)) = line.char_indices().nth(width.saturating_sub(1)) {
+                line.truncate(end);
+            }
+        }
+        eprint!("\r\x1b[2K{}", line);
+        let _ = io::stderr().flush();
+        self.shown = true;
     }
-    fn log_local_branch_has_no_remote_tracking_branch(
-        &mut self,
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        error: Error,
-    ) {
-        self.messages
-            .push(Self::msg_local_branch_has_no_remote_tracking_branch(
-                entry,
-                branch_name,
-                error,
-            ));
-    }
-    fn msg_branch_bad_name(entry: &fs::DirEntry, branch_name_bytes: &[u8]) -> String {
-        format!(
-            "🚨 {}: Branch {} has invalid utf8",
-            entry.path().display(),
-            String::from_utf8_lossy(branch_name_bytes)
-        )
+
+    fn clear(&mut self) {
+        if self.shown {
+            eprint!("\r\x1b[2K");
+            let _ = io::stderr().flush();
+            self.shown = false;
+        }
     }
-    fn log_branch_bad_name(&mut self, entry: &fs::DirEntry, branch_name_bytes: &[u8]) {
-        self.messages
-            .push(Self::msg_branch_bad_name(entry, branch_name_bytes));
+}
+
+/// Checks every entry once, prints the report, and returns the totals
+fn scan(
+    args: &cli::Args,
+    verbosity: Verbosity,
+    repos_directories: &[PathBuf],
+    check_options: &CheckOptions,
+    fetch_cache_path: Option<&Path>,
+    output: Option<&OutputFile>,
+    width: Option<usize>,
+) -> Result<ScanSummary> {
+    let entries = scan_entries(args, repos_directories)?;
+    let mut reporter = reporter(args, verbosity, check_options.theme, output, width);
+    if let Some(interval) = args.watch {
+        let mut printer = Printer::new(verbosity);
+        printer.log_scan_started(chrono::Local::now(), interval);
+        printer.report_to(&mut *reporter);
     }
-    fn msg_general_branch_error(entry: &fs::DirEntry, branch_name: &str, error: Error) -> String {
-        format!(
-            "🚨 {}: An operation on branch {} failed: {}",
-            entry.path().display(),
-            branch_name,
-            error
-        )
+
+    let context = ScanContext {
+        check_options,
+        follow_symlinks: args.follow_symlinks,
+        skip_ignored: args.skip_ignored,
+    };
+    let mut reports = Vec::new();
+    let mut visited = discover::Visited::default();
+    let run_started = Instant::now();
+    let mut timings = Vec::new();
+    // With --sort status the output is held back until every entry's verdict is known
+    let holding = args.sort == SortOrder::Status;
+    let mut held_entries = Vec::new();
+    let mut interrupted = false;
+    let mut failed_fast = false;
+    let mut progress = ProgressLine::for_scan(args, verbosity, entries.len());
+    for (number, entry) in (1..).zip(entries) {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            interrupted = true;
+            break;
+        }
+        let path_style = match &entry {
+            Ok(entry) if args.relative => {
+                // Scanned entries are the immediate children of a repos directory
+                let base = match &args.from_file {
+                    Some(_) => std::env::current_dir().unwrap_or_default(),
+                    None => entry.parent().unwrap_or(Path::new("")).to_path_buf(),
+                };
+                PathStyle::RelativeTo(base)
+            }
+            _ if args.absolute => PathStyle::Absolute,
+            _ => PathStyle::AsGiven,
+        };
+        let mut printer = Printer::new(verbosity)
+            .with_quiet(args.quiet)
+            .with_show_synced(args.show_synced)
+            .with_date_format(args.date_format.clone())
+            .with_path_style(path_style)
+            .with_severity_map(check_options.severity_map.clone());
+        let entry_path = entry.as_ref().ok().cloned();
+        // Starting an entry writes out the one before
+        if let Some(progress) = &mut progress {
+            progress.clear();
+        }
+        if let (Some(entry_path), false) = (&entry_path, holding) {
+            reporter.repo_started(&printer.display_path(entry_path));
+        }
+        if let (Some(progress), Some(entry_path)) = (&mut progress, &entry_path) {
+            progress.show(number, entry_path);
+        }
+
+        let started = Instant::now();
+        let mut report = match entry {
+            // A panic while checking one weird entry shouldn't take the rest of the run down with it
+            Ok(entry) => match panic::catch_unwind(AssertUnwindSafe(|| {
+                handle_entry(&entry, &context, &mut visited, &mut printer)
+            })) {
+                Err(panic) => {
+                    let error = anyhow!(
+                        "Panicked while checking {}: {}",
+                        entry.display(),
+                        panic_message(&*panic)
+                    );
+                    let report = RepoReport::failed(entry.clone(), &error);
+                    printer.log_general_entry_error(error);
+                    report
+                }
+                Ok(Ok(report)) => report,
+                Ok(Err(CheckError::Io(error)))
+                    if error.kind() == io::ErrorKind::PermissionDenied =>
+                {
+                    printer.log_permission_denied(&entry);
+                    let mut report = RepoReport::new(entry.clone());
+                    report.findings.push(Finding::PermissionDenied);
+                    report
+                }
+                Ok(Err(error)) => {
+                    let report = RepoReport::failed(entry.clone(), &error);
+                    printer.log_check_error(&entry, &error);
+                    report
+                }
+            },
+            Err(error) => {
+                let report = RepoReport::failed(PathBuf::new(), &error);
+                printer.log_general_entry_error(error.into());
+                report
+            }
+        };
+        if args.interactive {
+            if let Some(progress) = &mut progress {
+                progress.clear();
+            }
+            offer_head_fixes(&mut report, check_options, &mut printer, &mut *reporter);
+            offer_pushes(&mut report, check_options, &mut printer, &mut *reporter);
+        }
+        // Entries that couldn't even be listed have no path, and took no time worth mentioning
+        if !report.path.as_os_str().is_empty() {
+            let elapsed = started.elapsed();
+            printer.log_entry_elapsed(&report.path, elapsed);
+            timings.push((report.path.clone(), elapsed));
+        }
+        // The problems are listed from the reports at the end instead
+        if args.only_problems {
+            printer.discard();
+        }
+        let stop = args.fail_fast && report.has_problems();
+        if holding {
+            held_entries.push((report.severity(), entry_path, printer, report.clone()));
+        } else {
+            if let Some(progress) = &mut progress {
+                progress.clear();
+            }
+            printer.report_to(&mut *reporter);
+            reporter.repo_finished(&report);
+        }
+        reports.push(report);
+        if stop {
+            failed_fast = true;
+            break;
+        }
     }
-    fn log_general_branch_error(&mut self, entry: &fs::DirEntry, branch_name: &str, error: Error) {
-        self.messages
-            .push(Self::msg_general_branch_error(entry, branch_name, error));
+
+    if let Some(progress) = &mut progress {
+        progress.clear();
     }
-    fn msg_local_branch_ahead_of_upstream(entry: &fs::DirEntry, branch_name: &str) -> String {
-        format!(
-            "🚨 {}: Local branch {} is ahead of the upstream",
-            entry.path().display(),
-            branch_name
-        )
+
+    // Most severe first; entries with the same status stay in name order
+    held_entries.sort_by_key(|(severity, ..)| Reverse(*severity));
+    for (_, entry_path, mut printer, report) in held_entries {
+        if let Some(entry_path) = &entry_path {
+            reporter.repo_started(&printer.display_path(entry_path));
+        }
+        printer.report_to(&mut *reporter);
+        reporter.repo_finished(&report);
     }
-    fn log_local_branch_ahead_of_upstream(&mut self, entry: &fs::DirEntry, branch_name: &str) {
-        self.messages
-            .push(Self::msg_local_branch_ahead_of_upstream(entry, branch_name));
+    let mut printer = Printer::new(verbosity);
+    if let Some(fetch_cache_path) = fetch_cache_path {
+        if let Err(error) = check_options.fetch_cache.save(fetch_cache_path) {
+            printer.log_fetch_cache_not_saved(fetch_cache_path, error.into());
+        }
     }
-    fn msg_local_branch_not_found_in_remote_ancestor(
-        entry: &fs::DirEntry,
-        branch_name: &str,
-    ) -> String {
-        format!(
-            "🚨 {}: Local branch {} is not in the ancestor of the upstream",
-            entry.path().display(),
-            branch_name
-        )
+    timings.sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+    timings.truncate(SLOWEST_ENTRIES_SHOWN);
+    let mut summary = ScanSummary::new(&reports, run_started.elapsed());
+    printer.log_timing_summary(summary.duration, &timings);
+    printer.log_not_modified_total(summary.not_modified);
+    printer.log_problems_by_host(&summary.with_problems_by_host);
+    if args.only_problems {
+        for report in reports.iter().filter(|report| report.has_problems()) {
+            printer.log_repo_problems(&report.path, &report.problems());
+        }
     }
-    fn log_local_branch_not_found_in_remote_ancestor(
-        &mut self,
-        entry: &fs::DirEntry,
-        branch_name: &str,
-    ) {
-        self.messages
-            .push(Self::msg_local_branch_not_found_in_remote_ancestor(
-                entry,
-                branch_name,
-            ));
-    }
-    fn msg_branch_is_synced(entry: &fs::DirEntry, branch_name: &str) -> String {
-        format!(
-            "✅ {}: Local branch {} is synced with the remote",
-            entry.path().display(),
-            branch_name
-        )
+    if args.notify && summary.with_problems > 0 {
+        // Nobody may be watching the terminal, but there may not be a desktop either
+        if let Err(error) = notify_problems(summary.with_problems) {
+            printer.log_notification_failed(error.into());
+        }
     }
-    fn log_branch_is_synced(&mut self, entry: &fs::DirEntry, branch_name: &str) {
-        self.messages
-            .push(Self::msg_branch_is_synced(entry, branch_name));
+    if interrupted {
+        printer.log_interrupted();
+    } else if failed_fast {
+        printer.log_fail_fast();
     }
-    fn msg_entry(entry: &fs::DirEntry) -> String {
-        format!("📝 Looking at the entry {}", entry.path().display())
+    summary.interrupted = interrupted;
+    printer.log_run_summary(&summary);
+    printer.report_to(&mut *reporter);
+    reporter
+        .run_finished(&summary)
+        .context("Failed to write the report")?;
+    Ok(summary)
+}
+
+/// How a scan ends for scripts, see `EXIT_CODES`
+fn exit_code(summary: &ScanSummary) -> ExitCode {
+    if summary.interrupted {
+        ExitCode::from(EXIT_INTERRUPTED)
+    } else if summary.with_problems > 0 {
+        ExitCode::from(EXIT_PROBLEMS)
+    } else {
+        ExitCode::from(EXIT_CLEAN)
     }
-    fn log_entry(&mut self, entry: &fs::DirEntry) {
-        if !self.verbose {
-            return;
+}
+
+/// The entries to check, from --from-file or the repos directories, in --sort order
+fn scan_entries(
+    args: &cli::Args,
+    repos_directories: &[PathBuf],
+) -> Result<Vec<io::Result<PathBuf>>> {
+    let mut entries: Vec<io::Result<PathBuf>> = match &args.from_file {
+        Some(repo_list) => discover::read_repo_list(repo_list)?
+            .into_iter()
+            .map(Ok)
+            .collect(),
+        None => discover::read_repos_directories(
+            repos_directories,
+            !args.include_hidden,
+            !args.no_ignore_file,
+        ),
+    };
+    discover::sort_entries(&mut entries, args.sort);
+    Ok(entries)
+}
+
+/// For --list-repos: the entries that `handle_entry` would check as repositories, without opening
+/// any remote. Entries that can't be read are reported on stderr, so stdout stays a plain list.
+fn list_repos(args: &cli::Args, repos_directories: &[PathBuf]) -> Result<ExitCode> {
+    let mut visited = discover::Visited::default();
+    let mut stdout = io::stdout().lock();
+    for entry in scan_entries(args, repos_directories)? {
+        let path = match entry {
+            Ok(path) => path,
+            Err(error) => {
+                eprintln!("{}", error);
+                continue;
+            }
+        };
+        if path.is_symlink() && !args.follow_symlinks {
+            continue;
         }
-        self.messages.push(Self::msg_entry(entry));
-    }
-    fn msg_entry_is_a_git_repo(entry: &fs::DirEntry) -> String {
-        format!("📝 {}: This is a git repo ✔︎", entry.path().display())
-    }
-    fn log_entry_is_a_git_repo(&mut self, entry: &fs::DirEntry) {
-        if !self.verbose {
-            return;
+        let repo_path = if path.is_file() && discover::is_gitdir_pointer(&path) {
+            path.parent().unwrap_or(Path::new("."))
+        } else {
+            &path
+        };
+        if args.skip_ignored && discover::ignoring_repository(repo_path).is_some() {
+            continue;
         }
-        self.messages.push(Self::msg_entry_is_a_git_repo(entry));
-    }
-    fn msg_remote_fetch_succeeded(entry: &fs::DirEntry, remote_name: &str) -> String {
-        format!(
-            "📝 {}: Synced remote {}",
-            entry.path().display(),
-            remote_name
-        )
-    }
-    fn log_remote_fetch_succeeded(&mut self, entry: &fs::DirEntry, remote_name: &str) {
-        if !self.verbose {
-            return;
+        if visited.first_visit(repo_path).is_some() || git2::Repository::open(repo_path).is_err() {
+            continue;
         }
-        self.messages
-            .push(Self::msg_remote_fetch_succeeded(entry, remote_name));
+        writeln!(stdout, "{}", repo_path.display())?;
     }
-    fn msg_branch_name(entry: &fs::DirEntry, branch_name: &str) -> String {
-        format!(
-            "📝 {}: Looking at branch {}",
-            entry.path().display(),
-            branch_name
-        )
+    Ok(ExitCode::from(EXIT_CLEAN))
+}
+
+/// What handling an entry depends on, resolved once for the whole scan
+struct ScanContext<'a> {
+    check_options: &'a CheckOptions,
+    follow_symlinks: bool,
+    skip_ignored: bool,
+}
+
+/// Handles one entry, which may turn out not to be a repository at all.
+/// Most errors should be handled gracefully and printed by the Printer,
+/// but some errors are propagated up from git2 or the filesystem, and those are printed
+/// by category. For the first case, we return the report after
+/// the Printer prints a message. For the second case, we return the error.
+/// INFO: the return type is NOT a ControlFlow; whether to stop is decided by the loop from the report
+fn handle_entry(
+    path: &Path,
+    context: &ScanContext,
+    visited: &mut discover::Visited,
+    printer: &mut Printer,
+) -> Result<RepoReport, CheckError> {
+    // Only unknown errors should be returned.
+    // "Errors" that can be handled should print a nice UX message and continue
+    printer.log_entry(path);
+    let mut report = RepoReport::new(path.to_path_buf());
+    let symlink_metadata = fs::symlink_metadata(path)?; // This doesn't follow symlinks
+    if symlink_metadata.is_symlink() && !context.follow_symlinks {
+        printer.log_symlink(path);
+        report.findings.push(Finding::Symlink);
+        return Ok(report);
     }
-    fn log_branch_name(&mut self, entry: &fs::DirEntry, branch_name: &str) {
-        if !self.verbose {
-            return;
+    let repo_path = if path.is_file() && discover::is_gitdir_pointer(path) {
+        // A linked worktree or submodule, whose repository is the directory it sits in
+        let repo_path = path.parent().unwrap_or(Path::new("."));
+        printer.log_gitdir_pointer(path, repo_path);
+        repo_path
+    } else if path.is_file() {
+        printer.log_file(path);
+        report.findings.push(Finding::File);
+        return Ok(report);
+    } else {
+        path
+    };
+
+    if context.skip_ignored {
+        if let Some(enclosing_path) = discover::ignoring_repository(repo_path) {
+            printer.log_ignored_by_repository(repo_path, &enclosing_path);
+            report.findings.push(Finding::IgnoredByRepository {
+                path: enclosing_path,
+            });
+            return Ok(report);
         }
-        self.messages
-            .push(Self::msg_branch_name(entry, branch_name));
-    }
-    fn msg_branch_upstream_name(
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        upstream_name: &str,
-    ) -> String {
-        format!(
-            "📝 {}: Branch {} has upstream {}",
-            entry.path().display(),
-            branch_name,
-            upstream_name
-        )
-    }
-    fn log_branch_upstream_name(
-        &mut self,
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        upstream_name: &str,
-    ) {
-        if !self.verbose {
-            return;
-        }
-        self.messages.push(Self::msg_branch_upstream_name(
-            entry,
-            branch_name,
-            upstream_name,
-        ));
-    }
-    fn msg_branch_upstream_remote_name(
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        remote_name: &str,
-    ) -> String {
-        format!(
-            "📝 {}: Branch {} has upstream remote {}",
-            entry.path().display(),
-            branch_name,
-            remote_name
-        )
     }
-    fn log_branch_upstream_remote_name(
-        &mut self,
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        remote_name: &str,
-    ) {
-        if !self.verbose {
-            return;
-        }
-        self.messages.push(Self::msg_branch_upstream_remote_name(
-            entry,
-            branch_name,
-            remote_name,
-        ));
-    }
-    fn msg_branch_remote_not_fetched(
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        remote_name: &str,
-    ) -> String {
-        format!(
-            "🚨 {}: Branch {} has non-fetched remote {}",
-            entry.path().display(),
-            branch_name,
-            remote_name
-        )
-    }
-    fn log_branch_remote_not_fetched(
-        &mut self,
-        entry: &fs::DirEntry,
-        branch_name: &str,
-        remote_name: &str,
-    ) {
-        self.messages.push(Self::msg_branch_remote_not_fetched(
-            entry,
-            branch_name,
-            remote_name,
-        ));
-    }
-    fn simple_log(&mut self, message: &str) {
-        self.messages.push(message.to_string());
+
+    // The same repository can come up under several paths
+    if let Some(first_path) = visited.first_visit(repo_path) {
+        printer.log_duplicate_repo(repo_path, &first_path);
+        report
+            .findings
+            .push(Finding::DuplicateOf { path: first_path });
+        return Ok(report);
     }
+    check_repo(repo_path, context.check_options, printer)
 }
 
-impl Drop for Printer {
-    fn drop(&mut self) {
-        self.flush();
-    }
+/// The exit codes as a JSON array, for scripts that branch on them
+fn print_exit_codes() {
+    let exit_codes: Vec<_> = EXIT_CODES
+        .iter()
+        .map(|(code, name, meaning)| json!({ "code": code, "name": name, "meaning": meaning }))
+        .collect();
+    println!("{}", serde_json::Value::Array(exit_codes));
 }
 
-fn main() -> Result<()> {
-    let args = cli::get_args();
-    if args.verbose {
-        println!("{:?}", args);
+/// Where the run's messages and verdicts go, by `--format`
+fn reporter(
+    args: &cli::Args,
+    verbosity: Verbosity,
+    theme: Theme,
+    output: Option<&OutputFile>,
+    width: Option<usize>,
+) -> Box<dyn Reporter> {
+    let output = output.cloned();
+    match args.format {
+        OutputFormat::Text | OutputFormat::Github => Box::new(
+            TextReporter::new(verbosity)
+                .with_format(args.format)
+                .with_theme(theme)
+                .with_width(width)
+                .with_errors_to_stderr(args.errors_to_stderr)
+                .with_output(output),
+        ),
+        OutputFormat::Markdown => Box::new(MarkdownReporter::new(
+            verbosity >= Verbosity::Entries,
+            args.quiet,
+            output,
+        )),
+        OutputFormat::Sarif => Box::new(SarifReporter::new(output)),
+        OutputFormat::Csv => Box::new(CsvReporter::new(output)),
+        OutputFormat::Json => Box::new(JsonReporter::new(output)),
+        OutputFormat::Plain => Box::new(PlainReporter::new(output)),
     }
-    let repos_directory = if let Some(repos_directory) = args.repos_directory {
-        repos_directory
-    } else {
-        std::env::current_dir().context("Failed to get current directory")?
-    };
-    let ssh_private_key = if let Some(ssh_private_key) = args.ssh_private_key {
-        ssh_private_key
-    } else {
-        let home_dir = dirs::home_dir().context("Failed to get home directory")?;
-        home_dir.join(".ssh/id_rsa")
-    };
-    {
-        let ssh_private_key_metadata = fs::metadata(&ssh_private_key).context(format!(
-            "Failed to get metadata for ssh private key: {}",
-            ssh_private_key.display()
-        ))?;
-        ensure!(
-            ssh_private_key_metadata.is_file(),
-            "The ssh private key path is not a file: {}",
-            ssh_private_key.display()
+}
+
+/// Offers to push each branch that's only ahead of its upstream. Diverged branches need a human to
+/// sort them out, so they're never offered.
+fn offer_pushes(
+    report: &mut RepoReport,
+    options: &CheckOptions,
+    printer: &mut Printer,
+    reporter: &mut dyn Reporter,
+) {
+    for branch in report.branches.iter_mut().filter(|branch| {
+        matches!(
+            branch.status,
+            BranchStatus::Ahead | BranchStatus::AllowedAhead
+        )
+    }) {
+        let upstream = branch.upstream.as_deref().unwrap_or("its upstream");
+        // Branches are only judged ahead once their upstream's remote is known
+        let Some(remote) = branch.remote.as_deref() else {
+            continue;
+        };
+        // The question only makes sense after the entry's verdicts
+        printer.report_to(reporter);
+        reporter.flush();
+        let question = format!(
+            "Push {} commit(s) on branch {} of {} to {}?",
+            branch.ahead,
+            branch.name,
+            report.path.display(),
+            upstream
         );
+        if !confirm(&question) {
+            continue;
+        }
+        match check::push_branch(&report.path, &branch.name, remote, options) {
+            Ok(()) => {
+                printer.log_branch_pushed(&report.path, &branch.name, upstream);
+                branch.status = BranchStatus::UpToDate;
+                branch.ahead = 0;
+            }
+            Err(error) => printer.log_branch_push_failed(&report.path, &branch.name, error),
+        }
     }
+}
 
-    for entry in fs::read_dir(&repos_directory).with_context(|| {
-        format!(
-            "Failed to read projects directory: {}",
-            repos_directory.display()
+/// Offers to put a detached HEAD back on a branch: the branch whose tip it's at, or else a new
+/// branch, so the commits only HEAD points at aren't lost
+fn offer_head_fixes(
+    report: &mut RepoReport,
+    options: &CheckOptions,
+    printer: &mut Printer,
+    reporter: &mut dyn Reporter,
+) {
+    let is_detached_head = |finding: &Finding| {
+        matches!(
+            finding,
+            Finding::DetachedHead { .. }
+                | Finding::DetachedAtBranchTip { .. }
+                | Finding::DetachedHeadNotPushed { .. }
         )
-    })? {
-        let mut printer = Printer::new(args.verbose);
-
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(error) => {
-                printer.log_general_entry_error(error.into());
-                continue;
+    };
+    let Some(index) = report.findings.iter().position(is_detached_head) else {
+        return;
+    };
+    printer.report_to(reporter);
+    reporter.flush();
+    let fixed = match &report.findings[index] {
+        Finding::DetachedAtBranchTip { branch, .. } => {
+            let question = format!(
+                "Check out branch {} in {}, whose tip HEAD is detached at?",
+                branch,
+                report.path.display()
+            );
+            if !confirm(&question) {
+                return;
             }
-        };
-
-        printer.log_entry(&entry);
-
-        // Each entry is handled in a closure to catch errors and print them
-        // Most errors should be handled gracefully and printed by the Printer,
-        // but some errors are propagated up from git2, and those should be printed
-        // by the general entry error printer. For the first case, we return Ok(()) after
-        // the Printer prints a message. For the second case, we return the error.
-        // INFO: the return type is NOT a ControlFlow, because we always want to continue the loop
-        let mut handle_entry = |entry: fs::DirEntry| -> Result<()> {
-            // Only unknown errors should be returned.
-            // "Errors" that can be handled should print a nice UX message and continue
-            let path = entry.path();
-            let symlink_metadata = path.metadata()?; // This doesn't follow symlinks
-            if symlink_metadata.is_symlink() {
-                printer.log_symlink(&path);
-                return Ok(());
-            } else if path.is_file() {
-                printer.log_file(&path);
-                return Ok(());
+            check::attach_head(&report.path, branch)
+                .map(|()| printer.log_head_attached(&report.path, branch))
+        }
+        _ => {
+            let question = format!(
+                "Name of a branch to create at the detached HEAD of {} (empty to skip):",
+                report.path.display()
+            );
+            let branch = ask(&question);
+            if branch.is_empty() {
+                return;
             }
-
-            // Current entry is a directory
-            use git2::{Remote, Repository};
-            let repo = match Repository::open(&path) {
-                Ok(repo) => repo,
-                Err(error) => {
-                    printer.log_nongit_dir(&path, error.message());
-                    return Ok(());
-                }
-            };
-            // Current entry is a git repository
-            printer.log_entry_is_a_git_repo(&entry);
-
-            // TODO: check for uncommitted changes, both unstaged and staged
-
-            // Find all remotes
-            let remote_names = repo.remotes()?;
-            let mut qualifying_remotes: Vec<Remote> = Vec::new();
-            for (remote_name, remote_name_bytes) in
-                std::iter::zip(remote_names.iter(), remote_names.iter_bytes())
-            {
-                let remote_name = match remote_name {
-                    Some(remote) => remote,
-                    None => {
-                        printer.log_remote_bad_name(&entry, remote_name_bytes);
-                        continue;
-                    }
-                };
-                let remote = match repo.find_remote(&remote_name) {
-                    Ok(remote) => remote,
-                    Err(error) => {
-                        printer.log_remote_not_found(&entry, remote_name, error.into());
-                        continue;
-                    }
-                };
-                let url = match remote.url() {
-                    Some(url) => url,
-                    None => {
-                        printer.log_remote_bad_url(&entry, remote_name, remote.url_bytes());
-                        continue;
-                    }
-                };
-                // If the url begins with "https://github.com/", then it is a qualifying remote
-                // TODO: support more urls / make them configurable
-                if url.starts_with("https://github.com/") || url.starts_with("git@github.com:") {
-                    qualifying_remotes.push(remote);
-                } else {
-                    printer.log_unqualified_remote(&entry, remote_name);
+            check::create_branch_at_head(&report.path, &branch).map(|()| {
+                printer.log_head_branch_created(&report.path, &branch);
+                // It's only local so far, and checked out, so it's reported like the check would
+                if !report.findings.contains(&Finding::NoRemotes) {
+                    printer.log_head_branch_no_upstream(&report.path, &branch);
+                    report.findings.push(Finding::HeadBranchNoUpstream {
+                        branch: branch.clone(),
+                    });
                 }
-            }
-
-            let synced_remotes = {
-                // Fetch all qualifying remotes
-                let synced_remotes: Vec<_> = qualifying_remotes.iter_mut().filter_map(|remote| {
-                    let remote_name = remote.name().map(|name| name.to_owned());
-                    let remote_cb = {
-                        let mut remote_cb_builder = git2::RemoteCallbacks::new();
-                        remote_cb_builder.credentials(|user, user_from_url, cred| {
-                            // See https://github.com/rust-lang/git2-rs/issues/329#issuecomment-403318088
-                            let user = user_from_url.unwrap_or(user);
-                            if cred.is_username() {
-                                // TODO: since `cred` is a bitset, figure out if we need to check for other flags
-                                return git2::Cred::username(user);
-                            }
-                            if cred.is_ssh_key() {
-                                git2::Cred::ssh_key(user, None, &ssh_private_key, None)
-                            } else {
-                                // Unimplemented credential type
-                                // TODO: generalize this along with the "qualifying remote" check above
-                                if let Some(remote_name) = &remote_name {
-                                    if remote_name.starts_with("https://") {
-                                        return Err(git2::Error::from_str(&format!("Unimplemented git2 credential type in remote auth callback: {:?}. Consider changing the remote url to use ssh instead.", cred)));
-                                    }
-                                }
-                                Err(git2::Error::from_str(&format!("Unimplemented git2 credential type in remote auth callback: {:?}", cred)))
-                            }
-                        });
-                        remote_cb_builder
-                    };
-                    let mut fetch_opts = git2::FetchOptions::new();
-                    fetch_opts.remote_callbacks(remote_cb);
-
-                    match remote.fetch(&[] as &[&str], Some(&mut fetch_opts), None) {
-                        Ok(_) => {
-                            printer.log_remote_fetch_succeeded(&entry, remote.name().unwrap());
-                            Some(remote)
-                        },
-                        Err(error) => {
-                            printer.log_remote_fetch_failed(&entry, remote.name().unwrap(), error);
-                            None
-                        },
-                    }
-                }).collect(); // Must be eagerly iterated, because `printer` is borrowed mutably
-                synced_remotes
-            };
-
-            // Get all local branches (i.e. not remote-tracking branches) and check
-            // 1. that they have a corresponding remote-tracking branch
-            // 2. that they're not ahead of the remote-tracking branch
-            let branches = repo.branches(Some(git2::BranchType::Local))?;
-            for branch in branches {
-                let (branch, _) = branch?;
-                // Convert a Result<Option<&str, Error> to a Result<String, Error>
-                let branch_name = branch.name().and_then(|maybe_branch_name| {
-                    maybe_branch_name.map_or_else(
-                        || {
-                            branch
-                                .name_bytes()
-                                .map(|slice| String::from_utf8_lossy(slice).to_string())
-                        },
-                        |branch_name| Ok(branch_name.to_owned()),
-                    )
+                report.branches.push(BranchReport {
+                    name: branch,
+                    upstream: None,
+                    remote: None,
+                    status: BranchStatus::NoUpstream,
+                    ahead: 0,
+                    behind: 0,
+                    last_commit: None,
                 });
-                let branch_name = match branch_name {
-                    Ok(branch_name) => {
-                        printer.log_branch_name(&entry, &branch_name);
-                        branch_name
-                    }
-                    Err(error) => {
-                        printer.log_branch_name_error(&entry, error.into());
-                        continue;
-                    }
-                };
-                let remote_tracking_branch = match branch.upstream() {
-                    Ok(remote_tracking_branch) => remote_tracking_branch,
-                    Err(error) => {
-                        printer.log_local_branch_has_no_remote_tracking_branch(
-                            &entry,
-                            &branch_name,
-                            error.into(),
-                        );
-                        continue;
-                    }
-                };
+                report.apply_severity_map(&options.severity_map);
+            })
+        }
+    };
+    match fixed {
+        Ok(()) => report.findings.retain(|finding| !is_detached_head(finding)),
+        Err(error) => printer.log_head_fix_failed(&report.path, error),
+    }
+}
 
-                // Check upstream tracks a synced remote
-                let remote_tracking_branch_fqrefname = match remote_tracking_branch.name() {
-                    Ok(Some(remote_tracking_branch_name)) => {
-                        printer.log_branch_upstream_name(
-                            &entry,
-                            &branch_name,
-                            remote_tracking_branch_name,
-                        );
-                        // The `repo.branch_remote_name` function expects a fully qualified refname
-                        format!("refs/remotes/{}", remote_tracking_branch_name)
-                    }
-                    Ok(None) => {
-                        // TODO: refactor to handle Err from name_bytes()
-                        printer.log_branch_bad_name(
-                            &entry,
-                            remote_tracking_branch.name_bytes().unwrap(),
-                        );
-                        continue;
-                    }
-                    Err(error) => {
-                        printer.log_branch_name_error(&entry, error.into());
-                        continue;
-                    }
-                };
-                let remote_name = match repo.branch_remote_name(&remote_tracking_branch_fqrefname) {
-                    Ok(buf) => match buf.as_str() {
-                        Some(remote_name) => {
-                            printer.log_branch_upstream_remote_name(
-                                &entry,
-                                &branch_name,
-                                remote_name,
-                            );
-                            remote_name.to_owned()
-                        }
-                        None => {
-                            printer.log_remote_bad_name(&entry, &[]);
-                            continue;
-                        }
-                    },
-                    Err(error) => {
-                        printer.log_general_branch_error(
-                            &entry,
-                            &remote_tracking_branch_fqrefname,
-                            error.into(),
-                        );
-                        continue;
-                    }
-                };
-                let has_synced_remote = synced_remotes
-                    .iter()
-                    .any(|remote| remote.name().unwrap() == remote_name);
-                if !has_synced_remote {
-                    printer.log_branch_remote_not_fetched(&entry, &branch_name, &remote_name);
-                    continue;
-                }
+/// Asks on stderr, so stdout stays clean for the report. Anything but yes is a no.
+fn confirm(question: &str) -> bool {
+    matches!(
+        ask(&format!("{} [y/N]", question))
+            .to_ascii_lowercase()
+            .as_str(),
+        "y" | "yes"
+    )
+}
 
-                // Check that the local branch is not ahead of the remote-tracking branch
-                let branch_direct_ref = match branch.get().resolve() {
-                    Ok(direct_ref) => direct_ref,
-                    Err(error) => {
-                        printer.log_general_branch_error(&entry, &branch_name, error.into());
-                        continue;
-                    }
-                };
-                let branch_oid = branch_direct_ref.target().unwrap();
-                let upstream_direct_ref = match remote_tracking_branch.get().resolve() {
-                    Ok(direct_ref) => direct_ref,
-                    Err(error) => {
-                        printer.log_general_branch_error(&entry, &branch_name, error.into());
-                        continue;
-                    }
-                };
-                let upstream_oid = upstream_direct_ref.target().unwrap();
-
-                let mut revwalk = repo.revwalk()?;
-                revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
-                revwalk.push(upstream_oid)?;
-                let local_oid_is_ancestor_of_upstream = revwalk.any(|oid| match oid {
-                    Ok(oid) => oid == branch_oid,
-                    Err(error) => {
-                        printer.log_general_branch_error(&entry, &branch_name, error.into());
-                        false
-                    }
-                });
-                if !local_oid_is_ancestor_of_upstream {
-                    // Either the local branch is ahead of the upstream, or it diverged
-                    let mut revwalk = repo.revwalk()?;
-                    revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
-                    revwalk.push(branch_oid)?;
-                    let upstream_oid_is_ancestor_of_local = revwalk.any(|oid| match oid {
-                        Ok(oid) => oid == upstream_oid,
-                        Err(error) => {
-                            printer.log_general_branch_error(&entry, &branch_name, error.into());
-                            false
-                        }
-                    });
-                    if upstream_oid_is_ancestor_of_local {
-                        printer.log_local_branch_ahead_of_upstream(&entry, &branch_name);
-                    } else {
-                        printer.log_local_branch_not_found_in_remote_ancestor(&entry, &branch_name);
-                    }
-                    continue;
-                }
+/// Asks on stderr for a line of input, trimmed. Nothing could be read is the same as no answer.
+fn ask(question: &str) -> String {
+    eprint!("{} ", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => answer.trim().to_owned(),
+        Err(_) => String::new(),
+    }
+}
 
-                // Local branch is in the ancestor of upstream
-                printer.log_branch_is_synced(&entry, &branch_name);
-            }
+fn notify_problems(entries_with_problems: usize) -> Result<(), notify_rust::error::Error> {
+    notify_rust::Notification::new()
+        .summary("check-gits")
+        .body(&format!(
+            "{} entries have work that isn't safely pushed",
+            entries_with_problems
+        ))
+        .show()?;
+    Ok(())
+}
 
-            Ok(())
-        };
-        if let Err(error) = handle_entry(entry) {
-            // TODO: add current entry as context
-            printer.log_general_entry_error(error);
-        }
+/// The message a panic was raised with, if it was raised with one
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
     }
-    Ok(())
 }
@@ -0,0 +1,189 @@
+//! A Markdown report of a whole run, meant to be pasted into tickets.
+
+use crate::report::{RepoReport, ScanSummary, Severity};
+use crate::reporter::{write_rendered, OutputFile, Reporter};
+use std::fmt::Write;
+use std::io;
+
+/// Collects the reports, for the tables of the whole run once it's done
+pub struct MarkdownReporter {
+    reports: Vec<RepoReport>,
+    /// Also list the clean repositories
+    verbose: bool,
+    /// Leave the report out altogether when nothing needs attention
+    quiet: bool,
+    output: Option<OutputFile>,
+}
+
+impl MarkdownReporter {
+    /// Writes to `output` instead of stdout
+    pub fn new(verbose: bool, quiet: bool, output: Option<OutputFile>) -> Self {
+        Self {
+            reports: Vec::new(),
+            verbose,
+            quiet,
+            output,
+        }
+    }
+}
+
+impl Reporter for MarkdownReporter {
+    fn repo_finished(&mut self, report: &RepoReport) {
+        self.reports.push(report.clone());
+    }
+    fn run_finished(&mut self, _summary: &ScanSummary) -> io::Result<()> {
+        let has_warnings = self
+            .reports
+            .iter()
+            .any(|report| report.severity() >= Severity::Warning);
+        if self.quiet && !has_warnings {
+            return Ok(());
+        }
+        write_rendered(self.output.as_ref(), &render(&self.reports, self.verbose))
+    }
+}
+
+/// Renders one table per group of repositories: problems, warnings and, when `verbose`, clean ones.
+/// Only rows that need attention are listed unless `verbose`.
+pub fn render(reports: &[RepoReport], verbose: bool) -> String {
+    let group = |predicate: fn(Severity) -> bool| -> Vec<&RepoReport> {
+        reports
+            .iter()
+            .filter(|report| predicate(report.severity()))
+            .collect()
+    };
+    let problems = group(|severity| severity >= Severity::Error);
+    let warnings = group(|severity| severity == Severity::Warning);
+    let clean = group(|severity| severity <= Severity::Info);
+
+    let mut out = String::new();
+    writeln!(out, "# check-gits report").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "{} entries checked: {} with problems, {} with warnings, {} clean",
+        reports.len(),
+        problems.len(),
+        warnings.len(),
+        clean.len()
+    )
+    .unwrap();
+    write_group(&mut out, "Problems", &problems, verbose);
+    write_group(&mut out, "Warnings", &warnings, verbose);
+    if verbose {
+        write_group(&mut out, "Clean", &clean, verbose);
+    }
+    out
+}
+
+fn write_group(out: &mut String, title: &str, reports: &[&RepoReport], verbose: bool) {
+    if reports.is_empty() {
+        return;
+    }
+    writeln!(out).unwrap();
+    writeln!(out, "## {} ({})", title, reports.len()).unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "| Repository | Branch | Status |").unwrap();
+    writeln!(out, "| --- | --- | --- |").unwrap();
+    for report in reports {
+        let path = escape(&report.path.display().to_string());
+        for finding in &report.findings {
+            if verbose || report.finding_severity(finding) > Severity::Ok {
+                writeln!(out, "| {} | | {} |", path, escape(&finding.to_string())).unwrap();
+            }
+        }
+        for branch in &report.branches {
+            if verbose || report.branch_severity(branch) > Severity::Ok {
+                writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    path,
+                    escape(&branch.name),
+                    branch.status
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Keeps a cell from breaking out of its table
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{BranchReport, BranchStatus, Finding};
+    use std::path::PathBuf;
+
+    fn report(path: &str, statuses: &[(&str, BranchStatus)]) -> RepoReport {
+        let mut report = RepoReport::new(PathBuf::from(path));
+        for (name, status) in statuses {
+            report.branches.push(BranchReport {
+                name: name.to_string(),
+                upstream: None,
+                remote: None,
+                status: *status,
+                ahead: 0,
+                behind: 0,
+                last_commit: None,
+            });
+        }
+        report
+    }
+
+    #[test]
+    fn groups_repos_by_status() {
+        let mut warned = report("/code/warned", &[("main", BranchStatus::UpToDate)]);
+        warned.findings.push(Finding::UnqualifiedRemote {
+            remote: "mirror".to_owned(),
+        });
+        let reports = vec![
+            report("/code/clean", &[("main", BranchStatus::UpToDate)]),
+            report(
+                "/code/a|b",
+                &[
+                    ("main", BranchStatus::UpToDate),
+                    ("wip", BranchStatus::Ahead),
+                ],
+            ),
+            warned,
+        ];
+        assert_eq!(
+            render(&reports, false),
+            "\
+# check-gits report
+
+3 entries checked: 1 with problems, 1 with warnings, 1 clean
+
+## Problems (1)
+
+| Repository | Branch | Status |
+| --- | --- | --- |
+| /code/a\\|b | wip | ahead of upstream |
+
+## Warnings (1)
+
+| Repository | Branch | Status |
+| --- | --- | --- |
+| /code/warned | | remote mirror is not a qualifying remote |
+"
+        );
+    }
+
+    #[test]
+    fn verbose_lists_clean_repos() {
+        let reports = vec![report("/code/clean", &[("main", BranchStatus::UpToDate)])];
+        assert!(render(&reports, true).ends_with(
+            "\
+## Clean (1)
+
+| Repository | Branch | Status |
+| --- | --- | --- |
+| /code/clean | main | up to date |
+"
+        ));
+    }
+}
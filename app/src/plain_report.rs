@@ -0,0 +1,129 @@
+//! Tab-separated lines of a whole run, one per branch or finding, for diffing one run against
+//! the next.
+//!
+//! Each line is `STATUS\tREPO\tBRANCH\tDETAIL`, and the lines are sorted, so the same state of the
+//! repositories always gives the same output. There's no summary line, since its duration would
+//! differ on every run.
+
+use crate::json_report::severity_name;
+use crate::report::{RepoReport, ScanSummary, Severity};
+use crate::reporter::{write_rendered, OutputFile, Reporter};
+use std::io;
+
+/// Collects the reports, for the sorted lines of the whole run once the run is done
+pub struct PlainReporter {
+    reports: Vec<RepoReport>,
+    output: Option<OutputFile>,
+}
+
+impl PlainReporter {
+    /// Writes to `output` instead of stdout
+    pub fn new(output: Option<OutputFile>) -> Self {
+        Self {
+            reports: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Reporter for PlainReporter {
+    fn repo_finished(&mut self, report: &RepoReport) {
+        self.reports.push(report.clone());
+    }
+    fn run_finished(&mut self, _summary: &ScanSummary) -> io::Result<()> {
+        write_rendered(self.output.as_ref(), &render(&self.reports))
+    }
+}
+
+pub fn render(reports: &[RepoReport]) -> String {
+    // Sorted by repository, branch and detail, so a changed status shows up as a changed line
+    let mut lines: Vec<(String, String, String, Severity)> = Vec::new();
+    for report in reports {
+        let path = report.path.to_string_lossy().into_owned();
+        for finding in &report.findings {
+            lines.push((
+                path.clone(),
+                String::new(),
+                finding.to_string(),
+                report.finding_severity(finding),
+            ));
+        }
+        for branch in &report.branches {
+            let mut detail = branch.status.to_string();
+            if branch.ahead > 0 || branch.behind > 0 {
+                detail.push_str(&format!(
+                    " ({} ahead, {} behind)",
+                    branch.ahead, branch.behind
+                ));
+            }
+            lines.push((
+                path.clone(),
+                branch.name.clone(),
+                detail,
+                report.branch_severity(branch),
+            ));
+        }
+        // Every entry gets at least one line, so nothing checked goes missing from the diff
+        if report.findings.is_empty() && report.branches.is_empty() {
+            lines.push((
+                path,
+                String::new(),
+                "nothing to report".to_owned(),
+                Severity::Ok,
+            ));
+        }
+    }
+    lines.sort();
+    lines
+        .into_iter()
+        .map(|(path, branch, detail, severity)| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                severity_name(severity),
+                path,
+                branch,
+                detail
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::{BranchReport, BranchStatus, Finding};
+    use std::path::PathBuf;
+
+    #[test]
+    fn sorted_tab_separated_lines() {
+        let mut repo = RepoReport::new(PathBuf::from("/code/app"));
+        for (name, status, ahead) in [
+            ("wip", BranchStatus::Ahead, 2),
+            ("main", BranchStatus::UpToDate, 0),
+        ] {
+            repo.branches.push(BranchReport {
+                name: name.to_owned(),
+                upstream: Some(format!("origin/{}", name)),
+                remote: Some("origin".to_owned()),
+                status,
+                ahead,
+                behind: 0,
+                last_commit: None,
+            });
+        }
+        repo.findings.push(Finding::UncommittedChanges { count: 3 });
+        let mut not_a_repo = RepoReport::new(PathBuf::from("/code/notes"));
+        not_a_repo.findings.push(Finding::NotARepository);
+        let clean = RepoReport::new(PathBuf::from("/code/empty"));
+        assert_eq!(
+            render(&[not_a_repo, repo, clean]),
+            "\
+error\t/code/app\t\t3 uncommitted change(s)
+ok\t/code/app\tmain\tup to date
+error\t/code/app\twip\tahead of upstream (2 ahead, 0 behind)
+ok\t/code/empty\t\tnothing to report
+error\t/code/notes\t\tnot a git repository
+"
+        );
+    }
+}
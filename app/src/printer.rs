@@ -0,0 +1,2099 @@
+use crate::credentials::AuthMethod;
+use crate::error::CheckError;
+use crate::report::{
+    BranchStatus, Category, Finding, LastCommit, ScanSummary, Severity, SeverityLevel, SeverityMap,
+};
+use crate::reporter::Reporter;
+use anyhow::Error;
+use chrono::{DateTime, Local};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// GitHub Actions workflow commands, so problems show up as annotations
+    Github,
+    /// A Markdown report of the whole run, printed at the end
+    Markdown,
+    /// A SARIF 2.1.0 log of the whole run, printed at the end
+    Sarif,
+    /// A CSV table with a row per branch or finding, printed at the end
+    Csv,
+    /// JSON Lines, an object per entry, printed at the end
+    Json,
+    /// Sorted tab-separated lines with a status, repository, branch and detail each, printed at
+    /// the end. Unlike the text, they don't change between runs unless the repositories do.
+    Plain,
+}
+
+/// How paths are shown in messages
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// As they were given on the command line
+    #[default]
+    AsGiven,
+    /// Relative to this directory, usually the one being scanned
+    RelativeTo(PathBuf),
+    /// Canonicalized
+    Absolute,
+}
+
+/// The built-in themes
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ThemeName {
+    #[default]
+    Emoji,
+    /// Icons from Nerd Fonts, for terminals that use one
+    Nerdfont,
+    /// Plain ascii tags, for logs and terminals without the fonts
+    Ascii,
+}
+
+/// The glyph each message starts with, by severity
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Heads each repository's messages
+    pub repo: &'static str,
+    pub error: &'static str,
+    pub warning: &'static str,
+    pub info: &'static str,
+    pub ok: &'static str,
+}
+
+impl Theme {
+    pub const EMOJI: Theme = Theme {
+        repo: "📁",
+        error: "🚨",
+        warning: "⚠️",
+        info: "📝",
+        ok: "✅",
+    };
+    pub const NERDFONT: Theme = Theme {
+        repo: "\u{f07b}",
+        error: "\u{f057}",
+        warning: "\u{f071}",
+        info: "\u{f05a}",
+        ok: "\u{f058}",
+    };
+    pub const ASCII: Theme = Theme {
+        repo: "[repo]",
+        error: "[error]",
+        warning: "[warn]",
+        info: "[info]",
+        ok: "[ok]",
+    };
+
+    pub fn glyph(&self, severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => self.error,
+            Severity::Warning => self.warning,
+            Severity::Info => self.info,
+            Severity::Ok => self.ok,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::EMOJI
+    }
+}
+
+impl From<ThemeName> for Theme {
+    fn from(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Emoji => Theme::EMOJI,
+            ThemeName::Nerdfont => Theme::NERDFONT,
+            ThemeName::Ascii => Theme::ASCII,
+        }
+    }
+}
+
+/// Without its glyph, which the reporter adds if it wants one
+struct Message {
+    severity: Severity,
+    text: String,
+}
+
+/// How much to print beyond the problems. Each `-v` goes up a level.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// Every entry, and the fetches that went fine
+    Entries,
+    /// Also how each branch's upstream and remote were worked out
+    Branches,
+    /// Also the ancestry checks behind each branch's verdict
+    Ancestry,
+}
+
+impl Verbosity {
+    pub fn from_count(count: u8) -> Self {
+        match count {
+            0 => Self::Normal,
+            1 => Self::Entries,
+            2 => Self::Branches,
+            _ => Self::Ancestry,
+        }
+    }
+}
+
+pub struct Printer {
+    verbosity: Verbosity,
+    quiet: bool,
+    show_synced: bool,
+    path_style: PathStyle,
+    severity_map: SeverityMap,
+    /// strftime-style, for the dates of commits
+    date_format: String,
+    messages: Vec<Message>,
+}
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+const UNEXPECTED_GENERAL_ENTRY_ERROR: &str = "Something unexpectedly failed for the current entry";
+
+impl Printer {
+    /// Hands the pending messages to `reporter`, in the order they were logged
+    pub fn report_to(&mut self, reporter: &mut dyn Reporter) {
+        for message in self.messages.drain(..) {
+            reporter.finding(message.severity, &message.text);
+        }
+    }
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            quiet: false,
+            show_synced: false,
+            path_style: PathStyle::default(),
+            severity_map: SeverityMap::default(),
+            date_format: DEFAULT_DATE_FORMAT.to_owned(),
+            messages: Vec::new(),
+        }
+    }
+    /// Drop everything below a warning
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+    /// Print the ✅ messages for synced branches even when not verbose
+    pub fn with_show_synced(mut self, show_synced: bool) -> Self {
+        self.show_synced = show_synced;
+        self
+    }
+    pub fn with_path_style(mut self, path_style: PathStyle) -> Self {
+        self.path_style = path_style;
+        self
+    }
+    /// Remap the severities of these categories, dropping the ignored ones
+    pub fn with_severity_map(mut self, severity_map: SeverityMap) -> Self {
+        self.severity_map = severity_map;
+        self
+    }
+    /// How to show the dates of commits, in strftime style. Must be a valid format.
+    pub fn with_date_format(mut self, date_format: String) -> Self {
+        self.date_format = date_format;
+        self
+    }
+    /// How `path` is shown in messages
+    pub fn display_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        match &self.path_style {
+            PathStyle::AsGiven => Cow::Borrowed(path),
+            PathStyle::RelativeTo(base) => match path.strip_prefix(base) {
+                // The base directory itself is better shown as-is than as an empty path
+                Ok(relative) if relative != Path::new("") => Cow::Borrowed(relative),
+                _ => Cow::Borrowed(path),
+            },
+            PathStyle::Absolute => fs::canonicalize(path).map_or(Cow::Borrowed(path), Cow::Owned),
+        }
+    }
+    /// An empty printer with the same settings, to collect messages that are merged in later
+    pub fn buffer(&self) -> Self {
+        Self {
+            verbosity: self.verbosity,
+            quiet: self.quiet,
+            show_synced: self.show_synced,
+            path_style: self.path_style.clone(),
+            severity_map: self.severity_map.clone(),
+            date_format: self.date_format.clone(),
+            messages: Vec::new(),
+        }
+    }
+    /// Moves all of `other`'s pending messages to the end of this printer's
+    pub fn append(&mut self, other: &mut Printer) {
+        self.messages.append(&mut other.messages);
+    }
+    /// Takes the pending messages instead of reporting them, each after its emoji
+    #[cfg(test)]
+    pub(crate) fn take_texts(&mut self) -> Vec<String> {
+        self.messages
+            .drain(..)
+            .map(|message| format!("{} {}", Theme::EMOJI.glyph(message.severity), message.text))
+            .collect()
+    }
+    fn push(&mut self, severity: Severity, text: String) {
+        if self.quiet && severity < Severity::Warning {
+            return;
+        }
+        self.messages.push(Message { severity, text });
+    }
+    /// Like `push`, for a verdict whose severity the user may have remapped
+    fn push_in(&mut self, category: Category, severity: Severity, text: String) {
+        if let Some(mapped) = self
+            .severity_map
+            .resolve(Some(category), severity)
+            .severity()
+        {
+            self.push(mapped, text);
+        }
+    }
+    fn msg_symlink(path: &Path) -> String {
+        format!("Found symlink: {}. Ignoring this entry, as at the time of making this tool, I have never made symlinks in there, so I don't know what it means semantically.", path.display())
+    }
+    pub fn log_symlink(&mut self, path: &Path) {
+        self.push(
+            Severity::Warning,
+            Self::msg_symlink(&self.display_path(path)),
+        );
+    }
+    fn msg_file(path: &Path) -> String {
+        format!("Found file: {}. Files are unlikely to be git-pushed; move them somewhere safe if necessary.", path.display())
+    }
+    pub fn log_file(&mut self, path: &Path) {
+        self.push(Severity::Error, Self::msg_file(&self.display_path(path)));
+    }
+    fn msg_permission_denied(path: &Path) -> String {
+        format!("{}: Skipped: permission denied", path.display())
+    }
+    pub fn log_permission_denied(&mut self, path: &Path) {
+        self.push(
+            Severity::Warning,
+            Self::msg_permission_denied(&self.display_path(path)),
+        );
+    }
+    fn msg_gitdir_pointer(path: &Path, repo_path: &Path) -> String {
+        format!(
+            "{}: This is a .git file pointing at the real git dir, so checking {} instead",
+            path.display(),
+            repo_path.display()
+        )
+    }
+    pub fn log_gitdir_pointer(&mut self, path: &Path, repo_path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_gitdir_pointer(&self.display_path(path), &self.display_path(repo_path)),
+        );
+    }
+    fn msg_duplicate_repo(path: &Path, first_path: &Path) -> String {
+        format!(
+            "{}: Same repository as {}, which is checked instead",
+            path.display(),
+            first_path.display()
+        )
+    }
+    pub fn log_duplicate_repo(&mut self, path: &Path, first_path: &Path) {
+        self.push(
+            Severity::Info,
+            Self::msg_duplicate_repo(&self.display_path(path), &self.display_path(first_path)),
+        );
+    }
+    fn msg_ignored_by_repository(path: &Path, enclosing_path: &Path) -> String {
+        format!(
+            "{}: Ignored by the repository at {}, so it's meant to be local",
+            path.display(),
+            enclosing_path.display()
+        )
+    }
+    /// For --skip-ignored
+    pub fn log_ignored_by_repository(&mut self, path: &Path, enclosing_path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_ignored_by_repository(
+                &self.display_path(path),
+                &self.display_path(enclosing_path),
+            ),
+        );
+    }
+    fn msg_nongit_dir(path: &Path, msg: &str) -> String {
+        format!("{}: {}. This is not a git repository.", msg, path.display())
+    }
+    pub fn log_nongit_dir(&mut self, path: &Path, msg: &str) {
+        self.push(
+            Severity::Error,
+            Self::msg_nongit_dir(&self.display_path(path), msg),
+        );
+    }
+    fn msg_local_only_branch(path: &Path, local_branch: git2::Branch) -> String {
+        format!(
+            "{}: Local branch {} has no upstream (tracking remote branch)",
+            path.display(),
+            String::from_utf8_lossy(local_branch.name_bytes().unwrap_or_default())
+        )
+    }
+    pub fn log_local_only_branch(&mut self, path: &Path, local_branch: git2::Branch) {
+        self.push(
+            Severity::Error,
+            Self::msg_local_only_branch(&self.display_path(path), local_branch),
+        );
+    }
+    fn msg_general_entry_error(error: Error) -> String {
+        format!("{}: {}", UNEXPECTED_GENERAL_ENTRY_ERROR, error)
+    }
+    pub fn log_general_entry_error(&mut self, error: Error) {
+        self.push(Severity::Error, Self::msg_general_entry_error(error));
+    }
+    fn msg_general_entry_error_for_entry(path: &Path, error: Error) -> String {
+        format!("Failed for the entry {}: {}", path.display(), error)
+    }
+    pub fn log_general_entry_error_for_entry(&mut self, path: &Path, error: Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_general_entry_error_for_entry(&self.display_path(path), error),
+        );
+    }
+    fn msg_check_error(path: &Path, error: &CheckError) -> String {
+        match error {
+            CheckError::Io(error) => {
+                format!("{}: Failed to read the entry: {}", path.display(), error)
+            }
+            CheckError::Git(error) => format!(
+                "{}: A git operation unexpectedly failed: {}",
+                path.display(),
+                error
+            ),
+            CheckError::NotARepository(error) => {
+                format!("{}: Not a git repository: {}", path.display(), error)
+            }
+        }
+    }
+    pub fn log_check_error(&mut self, path: &Path, error: &CheckError) {
+        self.push(
+            Severity::Error,
+            Self::msg_check_error(&self.display_path(path), error),
+        );
+    }
+    fn msg_no_remotes(path: &Path) -> String {
+        format!(
+            "{}: Repository has no remotes, so none of its work is pushed anywhere",
+            path.display()
+        )
+    }
+    pub fn log_no_remotes(&mut self, path: &Path) {
+        self.push_in(
+            Category::NoRemotes,
+            Severity::Error,
+            Self::msg_no_remotes(&self.display_path(path)),
+        );
+    }
+    fn msg_remotes_enumeration_failed(path: &Path, error: &git2::Error) -> String {
+        format!(
+            "{}: Couldn't list the remotes, so nothing else was checked: {}",
+            path.display(),
+            error
+        )
+    }
+    pub fn log_remotes_enumeration_failed(&mut self, path: &Path, error: &git2::Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_remotes_enumeration_failed(&self.display_path(path), error),
+        );
+    }
+    fn msg_branches_enumeration_failed(path: &Path, error: &git2::Error) -> String {
+        format!(
+            "{}: Couldn't list the local branches, so none were checked: {}",
+            path.display(),
+            error
+        )
+    }
+    pub fn log_branches_enumeration_failed(&mut self, path: &Path, error: &git2::Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_branches_enumeration_failed(&self.display_path(path), error),
+        );
+    }
+    fn msg_remote_not_found(path: &Path, remote: &str, error: Error) -> String {
+        format!("{}: Remote {} not found: {}", path.display(), remote, error)
+    }
+    pub fn log_remote_not_found(&mut self, path: &Path, remote_name: &str, error: Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_remote_not_found(&self.display_path(path), remote_name, error),
+        );
+    }
+    fn msg_remote_not_selected(path: &Path, remote_name: &str) -> String {
+        format!(
+            "{}: Skipping remote {}, which wasn't selected",
+            path.display(),
+            remote_name
+        )
+    }
+    pub fn log_remote_not_selected(&mut self, path: &Path, remote_name: &str) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_remote_not_selected(&self.display_path(path), remote_name),
+        );
+    }
+    fn msg_unqualified_remote(path: &Path, remote_name: &str, url: &str) -> String {
+        format!(
+            "{}: Remote {} ({}) is not a qualifying remote",
+            path.display(),
+            remote_name,
+            url
+        )
+    }
+    pub fn log_unqualified_remote(&mut self, path: &Path, remote_name: &str, url: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_unqualified_remote(&self.display_path(path), remote_name, url),
+        );
+    }
+    fn msg_would_fetch(path: &Path, remote_name: &str, url: &str) -> String {
+        format!(
+            "{}: Would fetch remote {} ({})",
+            path.display(),
+            remote_name,
+            url
+        )
+    }
+    pub fn log_would_fetch(&mut self, path: &Path, remote_name: &str, url: &str) {
+        self.push(
+            Severity::Info,
+            Self::msg_would_fetch(&self.display_path(path), remote_name, url),
+        );
+    }
+    fn msg_remote_fetch_failed(
+        path: &Path,
+        remote_name: &str,
+        url: Option<&str>,
+        error: git2::Error,
+    ) -> String {
+        let url = url.map(|url| format!(" ({})", url)).unwrap_or_default();
+        format!(
+            "{}: Failed to fetch remote {}{}: {}",
+            path.display(),
+            remote_name,
+            url,
+            error
+        )
+    }
+    /// The url is only shown when verbose
+    pub fn log_remote_fetch_failed(
+        &mut self,
+        path: &Path,
+        remote_name: &str,
+        url: &str,
+        error: git2::Error,
+    ) {
+        let url = (self.verbosity >= Verbosity::Entries).then_some(url);
+        self.push(
+            Severity::Error,
+            Self::msg_remote_fetch_failed(&self.display_path(path), remote_name, url, error),
+        );
+    }
+    fn msg_remote_fetch_timeout(path: &Path, remote_name: &str, timeout: Duration) -> String {
+        format!(
+            "{}: Gave up fetching remote {} after {}s",
+            path.display(),
+            remote_name,
+            timeout.as_secs_f32()
+        )
+    }
+    pub fn log_remote_fetch_timeout(&mut self, path: &Path, remote_name: &str, timeout: Duration) {
+        self.push(
+            Severity::Error,
+            Self::msg_remote_fetch_timeout(&self.display_path(path), remote_name, timeout),
+        );
+    }
+    fn msg_remote_fetch_retry(
+        path: &Path,
+        remote_name: &str,
+        attempt: u32,
+        error: &git2::Error,
+        delay: Duration,
+    ) -> String {
+        format!(
+            "{}: Retrying fetch of remote {} in {}s (attempt {}): {}",
+            path.display(),
+            remote_name,
+            delay.as_secs_f32(),
+            attempt,
+            error
+        )
+    }
+    pub fn log_remote_fetch_retry(
+        &mut self,
+        path: &Path,
+        remote_name: &str,
+        attempt: u32,
+        error: &git2::Error,
+        delay: Duration,
+    ) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_remote_fetch_retry(
+                &self.display_path(path),
+                remote_name,
+                attempt,
+                error,
+                delay,
+            ),
+        );
+    }
+    fn msg_remote_bad_name(path: &Path, remote_name_bytes: &[u8]) -> String {
+        format!(
+            "{}: Remote {} skipped due to invalid utf8",
+            path.display(),
+            String::from_utf8_lossy(remote_name_bytes)
+        )
+    }
+    pub fn log_remote_bad_name(&mut self, path: &Path, remote_name_bytes: &[u8]) {
+        self.push(
+            Severity::Error,
+            Self::msg_remote_bad_name(&self.display_path(path), remote_name_bytes),
+        );
+    }
+    fn msg_remote_no_name(path: &Path) -> String {
+        format!(
+            "{}: A remote was skipped because it was not named",
+            path.display()
+        )
+    }
+    pub fn log_remote_no_name(&mut self, path: &Path) {
+        self.push(
+            Severity::Error,
+            Self::msg_remote_no_name(&self.display_path(path)),
+        );
+    }
+    fn msg_remote_bad_url(path: &Path, remote_name: &str, url: &[u8]) -> String {
+        format!(
+            "{}: Remote {} has a bad url: {}",
+            path.display(),
+            remote_name,
+            String::from_utf8_lossy(url)
+        )
+    }
+    pub fn log_remote_bad_url(&mut self, path: &Path, remote_name: &str, url: &[u8]) {
+        self.push(
+            Severity::Error,
+            Self::msg_remote_bad_url(&self.display_path(path), remote_name, url),
+        );
+    }
+    fn msg_branch_name_error(path: &Path, error: Error) -> String {
+        format!(
+            "{}: Failed to get the name of a branch: {}",
+            path.display(),
+            error
+        )
+    }
+    pub fn log_branch_name_error(&mut self, path: &Path, error: Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_branch_name_error(&self.display_path(path), error),
+        );
+    }
+    fn msg_local_branch_has_no_remote_tracking_branch(
+        path: &Path,
+        branch_name: &str,
+        error: Error,
+    ) -> String {
+        format!(
+            "{}: Local branch {} has no remote tracking branch: {}",
+            path.display(),
+            branch_name,
+            error
+        )
+    }
+    pub fn log_local_branch_has_no_remote_tracking_branch(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        error: Error,
+    ) {
+        self.push_in(
+            Category::NoUpstream,
+            Severity::Error,
+            Self::msg_local_branch_has_no_remote_tracking_branch(
+                &self.display_path(path),
+                branch_name,
+                error,
+            ),
+        );
+    }
+    fn msg_head_branch_no_upstream(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Checked-out branch {} has no upstream, so the work in progress on it isn't pushed anywhere",
+            path.display(),
+            branch_name
+        )
+    }
+    /// An error by default, since it's where the active work is. A `no_upstream` override of `ok`
+    /// still leaves it a warning.
+    pub fn log_head_branch_no_upstream(&mut self, path: &Path, branch_name: &str) {
+        let finding = Finding::HeadBranchNoUpstream {
+            branch: branch_name.to_owned(),
+        };
+        if let Some(severity) = finding.severity_level(&self.severity_map).severity() {
+            self.push(
+                severity,
+                Self::msg_head_branch_no_upstream(&self.display_path(path), branch_name),
+            );
+        }
+    }
+    fn msg_allowed_local_branch(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Local branch {} has no upstream, but is allowed to be local-only",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_allowed_local_branch(&mut self, path: &Path, branch_name: &str) {
+        if self.verbosity < Verbosity::Entries && !self.show_synced {
+            return;
+        }
+        self.push(
+            Severity::Ok,
+            Self::msg_allowed_local_branch(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_branch_bad_name(path: &Path, branch_name_bytes: &[u8]) -> String {
+        format!(
+            "{}: Branch {} has invalid utf8",
+            path.display(),
+            String::from_utf8_lossy(branch_name_bytes)
+        )
+    }
+    pub fn log_branch_bad_name(&mut self, path: &Path, branch_name_bytes: &[u8]) {
+        self.push(
+            Severity::Error,
+            Self::msg_branch_bad_name(&self.display_path(path), branch_name_bytes),
+        );
+    }
+    fn msg_branch_without_commit(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Branch {} doesn't point at a commit",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_branch_without_commit(&mut self, path: &Path, branch_name: &str) {
+        self.push(
+            Severity::Error,
+            Self::msg_branch_without_commit(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_general_branch_error(path: &Path, branch_name: &str, error: Error) -> String {
+        format!(
+            "{}: An operation on branch {} failed: {}",
+            path.display(),
+            branch_name,
+            error
+        )
+    }
+    pub fn log_general_branch_error(&mut self, path: &Path, branch_name: &str, error: Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_general_branch_error(&self.display_path(path), branch_name, error),
+        );
+    }
+    fn msg_local_branch_ahead_of_upstream(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Local branch {} is ahead of the upstream",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_local_branch_ahead_of_upstream(&mut self, path: &Path, branch_name: &str) {
+        self.push_in(
+            Category::Ahead,
+            Severity::Error,
+            Self::msg_local_branch_ahead_of_upstream(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_allowed_ahead_branch(path: &Path, branch_name: &str, ahead: usize) -> String {
+        format!(
+            "{}: Local branch {} has {} commit(s) that aren't pushed yet",
+            path.display(),
+            branch_name,
+            ahead
+        )
+    }
+    pub fn log_allowed_ahead_branch(&mut self, path: &Path, branch_name: &str, ahead: usize) {
+        self.push(
+            Severity::Warning,
+            Self::msg_allowed_ahead_branch(&self.display_path(path), branch_name, ahead),
+        );
+    }
+    fn msg_push_preview(path: &Path, branch_name: &str, refspec: &str, commits: usize) -> String {
+        format!(
+            "{}: Pushing branch {} would send {} commit(s) with {}",
+            path.display(),
+            branch_name,
+            commits,
+            refspec
+        )
+    }
+    fn msg_push_preview_commit(oid: git2::Oid, summary: &str) -> String {
+        format!("\n    {:.7} {}", oid, summary)
+    }
+    /// One line for the push, then one per commit
+    pub fn log_push_preview(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        refspec: &str,
+        commits: &[(git2::Oid, String)],
+    ) {
+        let mut text = Self::msg_push_preview(
+            &self.display_path(path),
+            branch_name,
+            refspec,
+            commits.len(),
+        );
+        for (oid, summary) in commits {
+            text.push_str(&Self::msg_push_preview_commit(*oid, summary));
+        }
+        self.push(Severity::Info, text);
+    }
+    fn msg_branch_pushed(path: &Path, branch_name: &str, upstream_name: &str) -> String {
+        format!(
+            "{}: Pushed branch {} to {}",
+            path.display(),
+            branch_name,
+            upstream_name
+        )
+    }
+    pub fn log_branch_pushed(&mut self, path: &Path, branch_name: &str, upstream_name: &str) {
+        self.push(
+            Severity::Ok,
+            Self::msg_branch_pushed(&self.display_path(path), branch_name, upstream_name),
+        );
+    }
+    fn msg_branch_push_failed(path: &Path, branch_name: &str, error: git2::Error) -> String {
+        format!(
+            "{}: Failed to push branch {}: {}",
+            path.display(),
+            branch_name,
+            error.message()
+        )
+    }
+    pub fn log_branch_push_failed(&mut self, path: &Path, branch_name: &str, error: git2::Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_branch_push_failed(&self.display_path(path), branch_name, error),
+        );
+    }
+    fn msg_branch_diverged(
+        path: &Path,
+        branch_name: &str,
+        ahead: usize,
+        behind: usize,
+        merge_base: Option<git2::Oid>,
+    ) -> String {
+        let forked = match merge_base {
+            Some(merge_base) => format!("since they forked at {:.7}", merge_base),
+            None => "with no history in common".to_owned(),
+        };
+        format!(
+            "{}: Local branch {} has diverged from the upstream, {} commit(s) ahead and {} behind {}",
+            path.display(),
+            branch_name,
+            ahead,
+            behind,
+            forked
+        )
+    }
+    /// `merge_base` is where the branch and its upstream forked, if they share any history
+    pub fn log_branch_diverged(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        ahead: usize,
+        behind: usize,
+        merge_base: Option<git2::Oid>,
+    ) {
+        self.push_in(
+            Category::Diverged,
+            Severity::Error,
+            Self::msg_branch_diverged(
+                &self.display_path(path),
+                branch_name,
+                ahead,
+                behind,
+                merge_base,
+            ),
+        );
+    }
+    fn msg_not_modified_since(
+        path: &Path,
+        last_modified: DateTime<Local>,
+        cutoff: DateTime<Local>,
+        date_format: &str,
+    ) -> String {
+        format!(
+            "{}: Skipped, since it was last modified on {}, before {}",
+            path.display(),
+            last_modified.format(date_format),
+            cutoff.format(date_format)
+        )
+    }
+    /// For --modified-since. The skips are totalled at the end, so each one is only verbose.
+    pub fn log_not_modified_since(
+        &mut self,
+        path: &Path,
+        last_modified: DateTime<Local>,
+        cutoff: DateTime<Local>,
+    ) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_not_modified_since(
+                &self.display_path(path),
+                last_modified,
+                cutoff,
+                &self.date_format,
+            ),
+        );
+    }
+    fn msg_branch_last_commit(
+        path: &Path,
+        branch_name: &str,
+        last_commit: &LastCommit,
+        date_format: &str,
+    ) -> String {
+        format!(
+            "{}: Branch {} was last committed to on {}, by {}",
+            path.display(),
+            branch_name,
+            last_commit
+                .committed_at
+                .with_timezone(&Local)
+                .format(date_format),
+            last_commit.author
+        )
+    }
+    /// Follows the verdict on the branch, so it's left out along with it when that's ignored
+    pub fn log_branch_last_commit(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        status: BranchStatus,
+        last_commit: &LastCommit,
+    ) {
+        let level = self
+            .severity_map
+            .resolve(status.category(), status.severity());
+        if level == SeverityLevel::Ignore {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_last_commit(
+                &self.display_path(path),
+                branch_name,
+                last_commit,
+                &self.date_format,
+            ),
+        );
+    }
+    fn msg_branch_is_synced(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Local branch {} is synced with the remote",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_branch_is_synced(&mut self, path: &Path, branch_name: &str) {
+        if self.verbosity < Verbosity::Entries && !self.show_synced {
+            return;
+        }
+        self.push(
+            Severity::Ok,
+            Self::msg_branch_is_synced(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_branch_behind(path: &Path, branch_name: &str, behind: usize) -> String {
+        format!(
+            "{}: Local branch {} is behind its upstream by {} commit(s) and needs a pull",
+            path.display(),
+            branch_name,
+            behind
+        )
+    }
+    pub fn log_branch_behind(&mut self, path: &Path, branch_name: &str, behind: usize) {
+        self.push_in(
+            Category::Behind,
+            Severity::Warning,
+            Self::msg_branch_behind(&self.display_path(path), branch_name, behind),
+        );
+    }
+    fn msg_branch_matches_remote(path: &Path, branch_name: &str, remote_name: &str) -> String {
+        format!(
+            "{}: Local branch {} is the same as {}/{}",
+            path.display(),
+            branch_name,
+            remote_name,
+            branch_name
+        )
+    }
+    /// For --compare-remote
+    pub fn log_branch_matches_remote(&mut self, path: &Path, branch_name: &str, remote_name: &str) {
+        if self.verbosity < Verbosity::Entries && !self.show_synced {
+            return;
+        }
+        self.push(
+            Severity::Ok,
+            Self::msg_branch_matches_remote(&self.display_path(path), branch_name, remote_name),
+        );
+    }
+    fn msg_branch_differs_from_remote(
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) -> String {
+        format!(
+            "{}: Local branch {} differs from {}/{}, {} commit(s) ahead and {} behind",
+            path.display(),
+            branch_name,
+            remote_name,
+            branch_name,
+            ahead,
+            behind
+        )
+    }
+    /// For --compare-remote
+    pub fn log_branch_differs_from_remote(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_branch_differs_from_remote(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+                ahead,
+                behind,
+            ),
+        );
+    }
+    fn msg_branch_missing_from_remote(path: &Path, branch_name: &str, remote_name: &str) -> String {
+        format!(
+            "{}: Local branch {} isn't on the remote {}",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    /// For --compare-remote
+    pub fn log_branch_missing_from_remote(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_branch_missing_from_remote(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+            ),
+        );
+    }
+    fn msg_compare_remote_not_fetched(path: &Path, remote_name: &str) -> String {
+        format!(
+            "{}: Remote {} wasn't fetched, so the branches weren't compared with it",
+            path.display(),
+            remote_name
+        )
+    }
+    pub fn log_compare_remote_not_fetched(&mut self, path: &Path, remote_name: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_compare_remote_not_fetched(&self.display_path(path), remote_name),
+        );
+    }
+    fn msg_no_compare_remote(path: &Path, remote_name: &str) -> String {
+        format!(
+            "{}: There's no remote {} to compare the branches with",
+            path.display(),
+            remote_name
+        )
+    }
+    pub fn log_no_compare_remote(&mut self, path: &Path, remote_name: &str) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_no_compare_remote(&self.display_path(path), remote_name),
+        );
+    }
+    fn msg_branch_is_synced_with_remote(
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) -> String {
+        format!(
+            "{}: Local branch {} is synced with the remote {}",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    pub fn log_branch_is_synced_with_remote(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        if self.verbosity < Verbosity::Entries && !self.show_synced {
+            return;
+        }
+        self.push(
+            Severity::Ok,
+            Self::msg_branch_is_synced_with_remote(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+            ),
+        );
+    }
+    fn msg_entry(path: &Path) -> String {
+        format!("Looking at the entry {}", path.display())
+    }
+    pub fn log_entry(&mut self, path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(Severity::Info, Self::msg_entry(&self.display_path(path)));
+    }
+    fn msg_entry_is_a_git_repo(path: &Path) -> String {
+        format!("{}: This is a git repo ✔︎", path.display())
+    }
+    pub fn log_entry_is_a_git_repo(&mut self, path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_entry_is_a_git_repo(&self.display_path(path)),
+        );
+    }
+    fn msg_notes_not_pushed(path: &Path, notes_ref: &str) -> String {
+        format!(
+            "{}: Notes {} aren't on any fetched remote. Push them with `git push <remote> {}`, and fetch the remote's notes with a refspec like +refs/notes/*:refs/notes/<remote>/* so they can be checked.",
+            path.display(),
+            notes_ref,
+            notes_ref
+        )
+    }
+    /// For --check-notes
+    pub fn log_notes_not_pushed(&mut self, path: &Path, notes_ref: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_notes_not_pushed(&self.display_path(path), notes_ref),
+        );
+    }
+    fn msg_default_branch_unknown(path: &Path, remote_name: &str) -> String {
+        format!(
+            "{}: Couldn't tell the default branch of remote {}. Set it with `git remote set-head {} --auto`.",
+            path.display(),
+            remote_name,
+            remote_name
+        )
+    }
+    /// For --check-default-branch
+    pub fn log_default_branch_unknown(&mut self, path: &Path, remote_name: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_default_branch_unknown(&self.display_path(path), remote_name),
+        );
+    }
+    fn msg_default_branch_missing(path: &Path, branch_name: &str, remote_name: &str) -> String {
+        format!(
+            "{}: Default branch {}/{} has no local branch {}",
+            path.display(),
+            remote_name,
+            branch_name,
+            branch_name
+        )
+    }
+    /// For --check-default-branch
+    pub fn log_default_branch_missing(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_default_branch_missing(&self.display_path(path), branch_name, remote_name),
+        );
+    }
+    fn msg_default_branch_not_synced(
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) -> String {
+        format!(
+            "{}: Default branch {} is {} commit(s) ahead of and {} behind {}/{}",
+            path.display(),
+            branch_name,
+            ahead,
+            behind,
+            remote_name,
+            branch_name
+        )
+    }
+    /// For --check-default-branch
+    pub fn log_default_branch_not_synced(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_default_branch_not_synced(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+                ahead,
+                behind,
+            ),
+        );
+    }
+    fn msg_tag_not_pushed(path: &Path, tag_name: &str, oid: git2::Oid) -> String {
+        format!(
+            "{}: Tag {} is on commit {}, which isn't on any fetched remote",
+            path.display(),
+            tag_name,
+            oid
+        )
+    }
+    /// For --check-tags
+    pub fn log_tag_not_pushed(&mut self, path: &Path, tag_name: &str, oid: git2::Oid) {
+        self.push(
+            Severity::Warning,
+            Self::msg_tag_not_pushed(&self.display_path(path), tag_name, oid),
+        );
+    }
+    fn msg_working_tree_not_verified(path: &Path) -> String {
+        format!(
+            "{}: The working tree wasn't checked for uncommitted changes, only the branches",
+            path.display()
+        )
+    }
+    /// For --no-verify-working-tree, which asks for this, so it's only verbose
+    pub fn log_working_tree_not_verified(&mut self, path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_working_tree_not_verified(&self.display_path(path)),
+        );
+    }
+    fn msg_bare_repo(path: &Path) -> String {
+        format!(
+            "{}: This is a bare repo, so only its branches are checked",
+            path.display()
+        )
+    }
+    pub fn log_bare_repo(&mut self, path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_bare_repo(&self.display_path(path)),
+        );
+    }
+    fn msg_linked_worktree(path: &Path, main_repo_path: &Path) -> String {
+        format!(
+            "{}: This is a linked worktree of {}, so only its checked-out branch is checked here",
+            path.display(),
+            main_repo_path.display()
+        )
+    }
+    pub fn log_linked_worktree(&mut self, path: &Path, main_repo_path: &Path) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_linked_worktree(&self.display_path(path), &self.display_path(main_repo_path)),
+        );
+    }
+    fn msg_remote_fetch_succeeded(
+        path: &Path,
+        remote_name: &str,
+        url: &str,
+        received_objects: usize,
+        received_bytes: usize,
+    ) -> String {
+        format!(
+            "{}: Synced remote {} from {} ({} object(s), {} received)",
+            path.display(),
+            remote_name,
+            url,
+            received_objects,
+            format_bytes(received_bytes)
+        )
+    }
+    pub fn log_remote_fetch_succeeded(
+        &mut self,
+        path: &Path,
+        remote_name: &str,
+        url: &str,
+        received_objects: usize,
+        received_bytes: usize,
+    ) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_remote_fetch_succeeded(
+                &self.display_path(path),
+                remote_name,
+                url,
+                received_objects,
+                received_bytes,
+            ),
+        );
+    }
+    fn msg_remote_fetch_skipped(path: &Path, remote_name: &str, fetched_ago: Duration) -> String {
+        format!(
+            "{}: Not fetching remote {}, which was fetched {}s ago",
+            path.display(),
+            remote_name,
+            fetched_ago.as_secs()
+        )
+    }
+    pub fn log_remote_fetch_skipped(
+        &mut self,
+        path: &Path,
+        remote_name: &str,
+        fetched_ago: Duration,
+    ) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_remote_fetch_skipped(&self.display_path(path), remote_name, fetched_ago),
+        );
+    }
+    /// Printed straight away by the fetch rather than buffered, so it isn't a `log_` method
+    pub(crate) fn msg_remote_fetch_progress(
+        path: &Path,
+        remote_name: &str,
+        received_objects: usize,
+        total_objects: usize,
+        received_bytes: usize,
+    ) -> String {
+        format!(
+            "{}: Fetching remote {}: {}/{} object(s), {} received",
+            path.display(),
+            remote_name,
+            received_objects,
+            total_objects,
+            format_bytes(received_bytes)
+        )
+    }
+    fn msg_remote_auth_method(
+        path: &Path,
+        remote_name: &str,
+        auth_method: Option<&AuthMethod>,
+    ) -> String {
+        match auth_method {
+            Some(auth_method) => format!(
+                "{}: Remote {} accepted {}",
+                path.display(),
+                remote_name,
+                auth_method
+            ),
+            None => format!(
+                "{}: Remote {} didn't ask for credentials",
+                path.display(),
+                remote_name
+            ),
+        }
+    }
+    /// Which of the keys and credentials a fetch ended up using, to debug multi-account setups
+    pub fn log_remote_auth_method(
+        &mut self,
+        path: &Path,
+        remote_name: &str,
+        auth_method: Option<&AuthMethod>,
+    ) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_remote_auth_method(&self.display_path(path), remote_name, auth_method),
+        );
+    }
+    fn msg_branch_name(path: &Path, branch_name: &str) -> String {
+        format!("{}: Looking at branch {}", path.display(), branch_name)
+    }
+    pub fn log_branch_name(&mut self, path: &Path, branch_name: &str) {
+        if self.verbosity < Verbosity::Branches {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_name(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_branch_preferred_remote_fallback(
+        path: &Path,
+        branch_name: &str,
+        remote_branch_name: &str,
+    ) -> String {
+        format!(
+            "{}: Branch {} has no upstream, so it's checked against {} instead",
+            path.display(),
+            branch_name,
+            remote_branch_name
+        )
+    }
+    /// For --preferred-remote
+    pub fn log_branch_preferred_remote_fallback(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_branch_name: &str,
+    ) {
+        if self.verbosity < Verbosity::Branches {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_preferred_remote_fallback(
+                &self.display_path(path),
+                branch_name,
+                remote_branch_name,
+            ),
+        );
+    }
+    fn msg_branch_upstream_name(path: &Path, branch_name: &str, upstream_name: &str) -> String {
+        format!(
+            "{}: Branch {} has upstream {}",
+            path.display(),
+            branch_name,
+            upstream_name
+        )
+    }
+    pub fn log_branch_upstream_name(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        upstream_name: &str,
+    ) {
+        if self.verbosity < Verbosity::Branches {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_upstream_name(&self.display_path(path), branch_name, upstream_name),
+        );
+    }
+    fn msg_branch_upstream_remote_name(
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) -> String {
+        format!(
+            "{}: Branch {} has upstream remote {}",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    pub fn log_branch_upstream_remote_name(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        if self.verbosity < Verbosity::Branches {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_upstream_remote_name(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+            ),
+        );
+    }
+    fn msg_branch_ancestry(
+        path: &Path,
+        branch_name: &str,
+        upstream_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) -> String {
+        format!(
+            "{}: Branch {} is {} commit(s) ahead of and {} commit(s) behind {}",
+            path.display(),
+            branch_name,
+            ahead,
+            behind,
+            upstream_name
+        )
+    }
+    pub fn log_branch_ancestry(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        upstream_name: &str,
+        ahead: usize,
+        behind: usize,
+    ) {
+        if self.verbosity < Verbosity::Ancestry {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_branch_ancestry(
+                &self.display_path(path),
+                branch_name,
+                upstream_name,
+                ahead,
+                behind,
+            ),
+        );
+    }
+    fn msg_branch_remote_not_fetched(path: &Path, branch_name: &str, remote_name: &str) -> String {
+        format!(
+            "{}: Branch {} has non-fetched remote {}",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    pub fn log_branch_remote_not_fetched(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        self.push(
+            Severity::Error,
+            Self::msg_branch_remote_not_fetched(&self.display_path(path), branch_name, remote_name),
+        );
+    }
+    fn msg_branch_remote_not_selected(path: &Path, branch_name: &str, remote_name: &str) -> String {
+        format!(
+            "{}: Branch {} tracks remote {}, which isn't being checked",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    pub fn log_branch_remote_not_selected(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_branch_remote_not_selected(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+            ),
+        );
+    }
+    fn msg_branch_upstream_remote_not_qualifying(
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) -> String {
+        format!(
+            "{}: Branch {} tracks remote {}, which isn't a qualifying remote, so the branch wasn't checked",
+            path.display(),
+            branch_name,
+            remote_name
+        )
+    }
+    pub fn log_branch_upstream_remote_not_qualifying(
+        &mut self,
+        path: &Path,
+        branch_name: &str,
+        remote_name: &str,
+    ) {
+        self.push(
+            Severity::Warning,
+            Self::msg_branch_upstream_remote_not_qualifying(
+                &self.display_path(path),
+                branch_name,
+                remote_name,
+            ),
+        );
+    }
+    fn msg_uncommitted_changes(path: &Path, count: usize) -> String {
+        format!(
+            "{}: Working tree has {} uncommitted change(s)",
+            path.display(),
+            count
+        )
+    }
+    pub fn log_uncommitted_changes(&mut self, path: &Path, count: usize) {
+        self.push_in(
+            Category::Uncommitted,
+            Severity::Error,
+            Self::msg_uncommitted_changes(&self.display_path(path), count),
+        );
+    }
+    fn msg_unmerged_paths(path: &Path, paths: &[String]) -> String {
+        format!(
+            "{}: Working tree has unresolved conflicts, left by a merge that wasn't finished: {}",
+            path.display(),
+            paths.join(", ")
+        )
+    }
+    pub fn log_unmerged_paths(&mut self, path: &Path, paths: &[String]) {
+        self.push(
+            Severity::Error,
+            Self::msg_unmerged_paths(&self.display_path(path), paths),
+        );
+    }
+    fn msg_untracked_files(path: &Path, count: usize) -> String {
+        format!(
+            "{}: Working tree has {} untracked file(s)",
+            path.display(),
+            count
+        )
+    }
+    pub fn log_untracked_files(&mut self, path: &Path, count: usize) {
+        self.push_in(
+            Category::Untracked,
+            Severity::Error,
+            Self::msg_untracked_files(&self.display_path(path), count),
+        );
+    }
+    fn msg_stashes(path: &Path, count: usize) -> String {
+        format!(
+            "{}: Has {} stash(es), which aren't pushed anywhere",
+            path.display(),
+            count
+        )
+    }
+    pub fn log_stashes(&mut self, path: &Path, count: usize) {
+        self.push_in(
+            Category::Stash,
+            Severity::Warning,
+            Self::msg_stashes(&self.display_path(path), count),
+        );
+    }
+    fn msg_detached_head(path: &Path, oid: git2::Oid) -> String {
+        format!("{}: HEAD is detached at {}", path.display(), oid)
+    }
+    pub fn log_detached_head(&mut self, path: &Path, oid: git2::Oid) {
+        self.push(
+            Severity::Warning,
+            Self::msg_detached_head(&self.display_path(path), oid),
+        );
+    }
+    fn msg_detached_at_branch_tip(path: &Path, oid: git2::Oid, branch_name: &str) -> String {
+        format!(
+            "{}: HEAD is detached at {}, which is the tip of branch {}, so nothing depends on it",
+            path.display(),
+            oid,
+            branch_name
+        )
+    }
+    pub fn log_detached_at_branch_tip(&mut self, path: &Path, oid: git2::Oid, branch_name: &str) {
+        self.push(
+            Severity::Info,
+            Self::msg_detached_at_branch_tip(&self.display_path(path), oid, branch_name),
+        );
+    }
+    fn msg_head_attached(path: &Path, branch_name: &str) -> String {
+        format!("{}: Checked out branch {}", path.display(), branch_name)
+    }
+    pub fn log_head_attached(&mut self, path: &Path, branch_name: &str) {
+        self.push(
+            Severity::Ok,
+            Self::msg_head_attached(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_head_branch_created(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Created branch {} at HEAD and checked it out",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_head_branch_created(&mut self, path: &Path, branch_name: &str) {
+        self.push(
+            Severity::Ok,
+            Self::msg_head_branch_created(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_head_fix_failed(path: &Path, error: git2::Error) -> String {
+        format!(
+            "{}: Failed to attach HEAD to a branch: {}",
+            path.display(),
+            error.message()
+        )
+    }
+    pub fn log_head_fix_failed(&mut self, path: &Path, error: git2::Error) {
+        self.push(
+            Severity::Error,
+            Self::msg_head_fix_failed(&self.display_path(path), error),
+        );
+    }
+    fn msg_detached_head_pushed(path: &Path, oid: git2::Oid, remote_name: &str) -> String {
+        format!(
+            "{}: Detached HEAD {} is contained in the remote {}",
+            path.display(),
+            oid,
+            remote_name
+        )
+    }
+    pub fn log_detached_head_pushed(&mut self, path: &Path, oid: git2::Oid, remote_name: &str) {
+        if self.verbosity < Verbosity::Entries && !self.show_synced {
+            return;
+        }
+        self.push(
+            Severity::Ok,
+            Self::msg_detached_head_pushed(&self.display_path(path), oid, remote_name),
+        );
+    }
+    fn msg_detached_head_not_pushed(path: &Path, oid: git2::Oid) -> String {
+        format!(
+            "{}: Detached HEAD {} is not contained in any fetched remote",
+            path.display(),
+            oid
+        )
+    }
+    pub fn log_detached_head_not_pushed(&mut self, path: &Path, oid: git2::Oid) {
+        self.push(
+            Severity::Error,
+            Self::msg_detached_head_not_pushed(&self.display_path(path), oid),
+        );
+    }
+    fn msg_no_local_branches(path: &Path) -> String {
+        format!(
+            "{}: Repository has no local branches. Take a look at what's in it.",
+            path.display()
+        )
+    }
+    pub fn log_no_local_branches(&mut self, path: &Path) {
+        self.push(
+            Severity::Warning,
+            Self::msg_no_local_branches(&self.display_path(path)),
+        );
+    }
+    fn msg_no_commits_yet(path: &Path, branch_name: Option<&str>) -> String {
+        match branch_name {
+            Some(branch_name) => format!(
+                "{}: Repository has no commits yet on branch {}",
+                path.display(),
+                branch_name
+            ),
+            None => format!("{}: Repository has no commits yet", path.display()),
+        }
+    }
+    pub fn log_no_commits_yet(&mut self, path: &Path, branch_name: Option<&str>) {
+        self.push(
+            Severity::Info,
+            Self::msg_no_commits_yet(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_untracked_remote_branch(path: &Path, branch: &str) -> String {
+        format!(
+            "{}: Remote branch {} isn't tracked by any local branch and has unmerged commits",
+            path.display(),
+            branch
+        )
+    }
+    pub fn log_untracked_remote_branch(&mut self, path: &Path, branch: &str) {
+        self.push(
+            Severity::Info,
+            Self::msg_untracked_remote_branch(&self.display_path(path), branch),
+        );
+    }
+    fn msg_repo_in_progress(path: &Path, operation: &str) -> String {
+        format!(
+            "{}: A git {} is in progress. Don't trust the branch verdicts until it's finished.",
+            path.display(),
+            operation
+        )
+    }
+    pub fn log_repo_in_progress(&mut self, path: &Path, operation: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_repo_in_progress(&self.display_path(path), operation),
+        );
+    }
+    fn msg_shallow_repo(path: &Path) -> String {
+        format!(
+            "{}: This is a shallow clone, so ahead/behind counts may be inaccurate. Run `git fetch --unshallow` to check it properly.",
+            path.display()
+        )
+    }
+    pub fn log_shallow_repo(&mut self, path: &Path) {
+        self.push(
+            Severity::Warning,
+            Self::msg_shallow_repo(&self.display_path(path)),
+        );
+    }
+    fn msg_branch_unverifiable_in_shallow_repo(path: &Path, branch_name: &str) -> String {
+        format!(
+            "{}: Branch {} can't be compared with its upstream, since the history connecting them may just be missing from this shallow clone",
+            path.display(),
+            branch_name
+        )
+    }
+    pub fn log_branch_unverifiable_in_shallow_repo(&mut self, path: &Path, branch_name: &str) {
+        self.push(
+            Severity::Warning,
+            Self::msg_branch_unverifiable_in_shallow_repo(&self.display_path(path), branch_name),
+        );
+    }
+    fn msg_entry_elapsed(path: &Path, elapsed: Duration) -> String {
+        format!(
+            "{}: Checked in {:.1}s",
+            path.display(),
+            elapsed.as_secs_f32()
+        )
+    }
+    pub fn log_entry_elapsed(&mut self, path: &Path, elapsed: Duration) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_entry_elapsed(&self.display_path(path), elapsed),
+        );
+    }
+    fn msg_scan_started(started: DateTime<Local>, interval: u64) -> String {
+        format!(
+            "Scan of {}, repeated every {}s until interrupted",
+            started.format("%Y-%m-%d %H:%M:%S"),
+            interval
+        )
+    }
+    /// Heads each scan of --watch
+    pub fn log_scan_started(&mut self, started: DateTime<Local>, interval: u64) {
+        self.push(Severity::Info, Self::msg_scan_started(started, interval));
+    }
+    fn msg_timing_summary(total: Duration, slowest: &[(PathBuf, Duration)]) -> String {
+        let slowest: Vec<_> = slowest
+            .iter()
+            .map(|(path, elapsed)| format!("{} ({:.1}s)", path.display(), elapsed.as_secs_f32()))
+            .collect();
+        let mut message = format!("Finished in {:.1}s", total.as_secs_f32());
+        if !slowest.is_empty() {
+            message.push_str(&format!(". Slowest: {}", slowest.join(", ")));
+        }
+        message
+    }
+    /// `slowest` are the entries that took the longest, slowest first
+    pub fn log_timing_summary(&mut self, total: Duration, slowest: &[(PathBuf, Duration)]) {
+        if self.verbosity < Verbosity::Entries {
+            return;
+        }
+        self.push(Severity::Info, Self::msg_timing_summary(total, slowest));
+    }
+    fn msg_repo_problems(path: &Path, problems: &[String]) -> String {
+        format!("{}: {}", path.display(), problems.join("; "))
+    }
+    /// One line per repository for --only-problems
+    pub fn log_repo_problems(&mut self, path: &Path, problems: &[String]) {
+        self.push(
+            Severity::Error,
+            Self::msg_repo_problems(&self.display_path(path), problems),
+        );
+    }
+    fn msg_not_modified_total(count: usize) -> String {
+        format!(
+            "Skipped {} repo(s) that weren't modified since --modified-since",
+            count
+        )
+    }
+    pub fn log_not_modified_total(&mut self, count: usize) {
+        if count == 0 {
+            return;
+        }
+        self.push(Severity::Info, Self::msg_not_modified_total(count));
+    }
+    fn msg_problems_by_host(with_problems_by_host: &BTreeMap<String, usize>) -> String {
+        let hosts: Vec<_> = with_problems_by_host
+            .iter()
+            .map(|(host, with_problems)| format!("{}: {}", host, with_problems))
+            .collect();
+        format!("Repos with problems by host: {}", hosts.join("; "))
+    }
+    /// Shows which hosts the unpushed work is headed for, which helps when moving between them
+    pub fn log_problems_by_host(&mut self, with_problems_by_host: &BTreeMap<String, usize>) {
+        if with_problems_by_host
+            .values()
+            .all(|&with_problems| with_problems == 0)
+        {
+            return;
+        }
+        self.push(
+            Severity::Info,
+            Self::msg_problems_by_host(with_problems_by_host),
+        );
+    }
+    fn msg_run_summary(summary: &ScanSummary) -> String {
+        let mut message = match summary.with_problems {
+            0 => format!("None of the {} entries need attention", summary.total),
+            with_problems => format!(
+                "{} of the {} entries need attention",
+                with_problems, summary.total
+            ),
+        };
+        if summary.unpushed_commits > 0 {
+            message.push_str(&format!(
+                ", with {} unpushed commit(s) across {} branch(es) in {} repo(s)",
+                summary.unpushed_commits, summary.unpushed_branches, summary.unpushed_repos
+            ));
+        }
+        message.push_str(&format!(
+            ". Checked in {:.1}s",
+            summary.duration.as_secs_f32()
+        ));
+        message
+    }
+    /// The verdict on the whole run, as the last line. It's kept even when quiet, for logs of
+    /// unattended runs.
+    pub fn log_run_summary(&mut self, summary: &ScanSummary) {
+        let severity = match summary.with_problems {
+            0 => Severity::Ok,
+            _ => Severity::Info,
+        };
+        self.messages.push(Message {
+            severity,
+            text: Self::msg_run_summary(summary),
+        });
+    }
+    /// Drops the pending messages without printing them
+    pub fn discard(&mut self) {
+        self.messages.clear();
+    }
+    fn msg_fetch_cache_not_saved(path: &Path, error: Error) -> String {
+        format!(
+            "Couldn't save when remotes were fetched to {}: {}",
+            path.display(),
+            error
+        )
+    }
+    pub fn log_fetch_cache_not_saved(&mut self, path: &Path, error: Error) {
+        self.push(
+            Severity::Warning,
+            Self::msg_fetch_cache_not_saved(path, error),
+        );
+    }
+    fn msg_notification_failed(error: Error) -> String {
+        format!("Failed to send the desktop notification: {}", error)
+    }
+    pub fn log_notification_failed(&mut self, error: Error) {
+        self.push(Severity::Warning, Self::msg_notification_failed(error));
+    }
+    fn msg_fail_fast() -> String {
+        "Stopping early due to --fail-fast".to_string()
+    }
+    pub fn log_fail_fast(&mut self) {
+        self.push(Severity::Info, Self::msg_fail_fast());
+    }
+    fn msg_interrupted() -> String {
+        "Interrupted, the report is incomplete".to_string()
+    }
+    pub fn log_interrupted(&mut self) {
+        self.push(Severity::Info, Self::msg_interrupted());
+    }
+    pub fn simple_log(&mut self, message: &str) {
+        self.push(Severity::Info, message.to_string());
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+impl Drop for Printer {
+    /// Every message is meant to be reported, taken or discarded first. Messages left behind mean
+    /// a verdict never made it to the output.
+    fn drop(&mut self) {
+        debug_assert!(
+            self.messages.is_empty() || std::thread::panicking(),
+            "Printer dropped with {} message(s) that were never reported",
+            self.messages.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::SeverityLevel;
+
+    #[test]
+    fn formats_byte_counts() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.0 MiB");
+    }
+
+    #[test]
+    fn synced_branches_are_verbose_only_by_default() {
+        let path = Path::new("/code/repo");
+        let mut printer = Printer::new(Verbosity::Normal);
+        printer.log_branch_is_synced(path, "main");
+        printer.log_allowed_local_branch(path, "wip");
+        assert_eq!(printer.take_texts(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn branch_tracing_needs_more_verbosity() {
+        let path = Path::new("/code/repo");
+        let mut printer = Printer::new(Verbosity::Entries);
+        printer.log_branch_name(path, "main");
+        printer.log_branch_ancestry(path, "main", "refs/remotes/origin/main", 0, 2);
+        assert!(printer.take_texts().is_empty());
+
+        let mut printer = Printer::new(Verbosity::Ancestry);
+        printer.log_branch_name(path, "main");
+        printer.log_branch_ancestry(path, "main", "refs/remotes/origin/main", 0, 2);
+        assert_eq!(
+            printer.take_texts(),
+            vec![
+                "📝 /code/repo: Looking at branch main",
+                "📝 /code/repo: Branch main is 0 commit(s) ahead of and 2 commit(s) behind refs/remotes/origin/main",
+            ]
+        );
+    }
+
+    #[test]
+    fn remapped_messages_get_the_new_icon() {
+        let path = Path::new("/code/repo");
+        let mut severity_map = SeverityMap::default();
+        severity_map.set(Category::Untracked, SeverityLevel::Warn);
+        severity_map.set(Category::NoRemotes, SeverityLevel::Ignore);
+        let mut printer = Printer::new(Verbosity::Normal).with_severity_map(severity_map);
+        printer.log_untracked_files(path, 2);
+        printer.log_no_remotes(path);
+        assert_eq!(
+            printer.take_texts(),
+            vec!["⚠️ /code/repo: Working tree has 2 untracked file(s)"]
+        );
+    }
+
+    #[test]
+    fn quiet_keeps_only_problems() {
+        let path = Path::new("/code/repo");
+        let mut printer = Printer::new(Verbosity::Normal).with_quiet(true);
+        printer.log_branch_is_synced(path, "main");
+        printer.log_unqualified_remote(path, "mirror", "https://gitlab.com/me/repo.git");
+        assert_eq!(
+            printer.take_texts(),
+            vec!["⚠️ /code/repo: Remote mirror (https://gitlab.com/me/repo.git) is not a qualifying remote"]
+        );
+    }
+
+    #[test]
+    fn run_summary_leaves_out_the_unpushed_total_when_nothing_is_unpushed() {
+        let mut printer = Printer::new(Verbosity::Normal).with_quiet(true);
+        let mut summary = ScanSummary {
+            total: 4,
+            duration: Duration::from_millis(1300),
+            ..ScanSummary::default()
+        };
+        printer.log_run_summary(&summary);
+        summary.with_problems = 3;
+        summary.unpushed_commits = 47;
+        summary.unpushed_branches = 5;
+        summary.unpushed_repos = 3;
+        printer.log_run_summary(&summary);
+        assert_eq!(
+            printer.take_texts(),
+            vec![
+                "✅ None of the 4 entries need attention. Checked in 1.3s",
+                "📝 3 of the 4 entries need attention, with 47 unpushed commit(s) across 5 branch(es) in 3 repo(s). Checked in 1.3s",
+            ]
+        );
+    }
+
+    #[test]
+    fn problems_by_host_include_clean_hosts() {
+        let mut printer = Printer::new(Verbosity::Normal);
+        let mut by_host = BTreeMap::from([("gitlab.com".to_owned(), 0)]);
+        printer.log_problems_by_host(&by_host);
+        by_host.insert("github.com".to_owned(), 3);
+        printer.log_problems_by_host(&by_host);
+        assert_eq!(
+            printer.take_texts(),
+            vec!["📝 Repos with problems by host: github.com: 3; gitlab.com: 0"]
+        );
+    }
+
+    #[test]
+    fn relative_paths() {
+        let mut printer = Printer::new(Verbosity::Normal)
+            .with_path_style(PathStyle::RelativeTo(PathBuf::from("/home/me/code")));
+        printer.log_no_remotes(Path::new("/home/me/code/repo"));
+        printer.log_no_remotes(Path::new("/srv/other"));
+        assert_eq!(
+            printer.take_texts(),
+            vec![
+                "🚨 repo: Repository has no remotes, so none of its work is pushed anywhere",
+                "🚨 /srv/other: Repository has no remotes, so none of its work is pushed anywhere",
+            ]
+        );
+    }
+}
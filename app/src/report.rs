@@ -0,0 +1,636 @@
+use chrono::{DateTime, FixedOffset};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Everything `check_repo` found out about a single entry.
+///
+/// The `Printer` renders the same information for humans as it goes; this is
+/// the structured form that tests and other consumers can inspect.
+#[derive(Debug, Clone)]
+pub struct RepoReport {
+    pub path: PathBuf,
+    pub findings: Vec<Finding>,
+    pub branches: Vec<BranchReport>,
+    /// The hosts of the qualifying remotes, e.g. `github.com`, each once
+    pub hosts: Vec<String>,
+    /// The user's remapping of the built-in severities
+    pub severity_map: SeverityMap,
+}
+
+impl RepoReport {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            findings: Vec::new(),
+            branches: Vec::new(),
+            hosts: Vec::new(),
+            severity_map: SeverityMap::default(),
+        }
+    }
+
+    /// Drops whatever `severity_map` ignores and remembers it for working out severities
+    pub fn apply_severity_map(&mut self, severity_map: &SeverityMap) {
+        self.findings
+            .retain(|finding| finding.severity_level(severity_map) != SeverityLevel::Ignore);
+        self.branches.retain(|branch| {
+            severity_map.resolve(branch.status.category(), branch.status.severity())
+                != SeverityLevel::Ignore
+        });
+        self.severity_map = severity_map.clone();
+    }
+
+    /// A report for an entry whose check failed unexpectedly
+    pub fn failed(path: PathBuf, error: &dyn fmt::Display) -> Self {
+        let mut report = Self::new(path);
+        report.findings.push(Finding::CheckFailed {
+            message: error.to_string(),
+        });
+        report
+    }
+
+    pub fn branch(&self, name: &str) -> Option<&BranchReport> {
+        self.branches.iter().find(|branch| branch.name == name)
+    }
+
+    /// The most severe of all findings and branch verdicts
+    pub fn severity(&self) -> Severity {
+        self.findings
+            .iter()
+            .map(|finding| self.finding_severity(finding))
+            .chain(
+                self.branches
+                    .iter()
+                    .map(|branch| self.branch_severity(branch)),
+            )
+            .max()
+            .unwrap_or(Severity::Ok)
+    }
+
+    pub fn finding_severity(&self, finding: &Finding) -> Severity {
+        finding
+            .severity_level(&self.severity_map)
+            .severity()
+            .unwrap_or(Severity::Ok)
+    }
+
+    /// A branch that is behind its upstream only counts when the user gave `behind` a severity
+    pub fn branch_severity(&self, branch: &BranchReport) -> Severity {
+        let status_severity = self
+            .severity_map
+            .resolve(branch.status.category(), branch.status.severity())
+            .severity()
+            .unwrap_or(Severity::Ok);
+        let behind_severity = match self.severity_map.get(Category::Behind) {
+            Some(level) if branch.behind > 0 => level.severity().unwrap_or(Severity::Ok),
+            _ => Severity::Ok,
+        };
+        status_severity.max(behind_severity)
+    }
+
+    /// Whether anything in the repository needs a human to act on it
+    pub fn has_problems(&self) -> bool {
+        self.severity() >= Severity::Error
+    }
+
+    /// The branches with commits their upstreams don't have yet. Branches without an upstream
+    /// aren't counted, since there's nothing to count their commits against.
+    pub fn unpushed_branches(&self) -> impl Iterator<Item = &BranchReport> {
+        self.branches.iter().filter(|branch| branch.ahead > 0)
+    }
+
+    /// What needs a human to act on it, described once each
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems: Vec<String> = Vec::new();
+        let findings = self
+            .findings
+            .iter()
+            .filter(|finding| self.finding_severity(finding) >= Severity::Error)
+            .map(ToString::to_string);
+        let branches = self
+            .branches
+            .iter()
+            .filter(|branch| self.branch_severity(branch) >= Severity::Error)
+            .map(|branch| format!("branch {} {}", branch.name, branch.status));
+        for problem in findings.chain(branches) {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+        problems
+    }
+}
+
+/// The totals of a whole scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    /// Entries checked
+    pub total: usize,
+    /// Entries with nothing worse than info to say
+    pub clean: usize,
+    /// Entries with something a human needs to act on
+    pub with_problems: usize,
+    /// Entries by their most severe verdict
+    pub by_severity: BTreeMap<Severity, usize>,
+    pub unpushed_commits: usize,
+    /// Branches with unpushed commits
+    pub unpushed_branches: usize,
+    /// Entries with unpushed commits
+    pub unpushed_repos: usize,
+    /// Repositories skipped for --modified-since
+    pub not_modified: usize,
+    /// Entries with problems, by the hosts of their qualifying remotes. Every host that came up is
+    /// in here, even without problems.
+    pub with_problems_by_host: BTreeMap<String, usize>,
+    pub duration: Duration,
+    /// Whether the scan was cut short by Ctrl-C, so the totals leave some entries out
+    pub interrupted: bool,
+}
+
+impl ScanSummary {
+    pub fn new(reports: &[RepoReport], duration: Duration) -> Self {
+        let mut summary = Self {
+            total: reports.len(),
+            duration,
+            ..Self::default()
+        };
+        for report in reports {
+            let severity = report.severity();
+            *summary.by_severity.entry(severity).or_default() += 1;
+            if severity <= Severity::Info {
+                summary.clean += 1;
+            }
+            if report.has_problems() {
+                summary.with_problems += 1;
+            }
+            for host in &report.hosts {
+                *summary
+                    .with_problems_by_host
+                    .entry(host.clone())
+                    .or_default() += usize::from(report.has_problems());
+            }
+            if report.findings.contains(&Finding::NotModifiedSince) {
+                summary.not_modified += 1;
+            }
+            let unpushed_branches = summary.unpushed_branches;
+            for branch in report.unpushed_branches() {
+                summary.unpushed_commits += branch.ahead;
+                summary.unpushed_branches += 1;
+            }
+            if summary.unpushed_branches > unpushed_branches {
+                summary.unpushed_repos += 1;
+            }
+        }
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Info,
+    Warning,
+    Error,
+}
+
+/// The kinds of verdict whose severity can be remapped
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Ahead,
+    Behind,
+    Diverged,
+    #[value(name = "no_upstream")]
+    NoUpstream,
+    Uncommitted,
+    Untracked,
+    Stash,
+    #[value(name = "no_remotes")]
+    NoRemotes,
+}
+
+/// What a category can be remapped to
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeverityLevel {
+    Error,
+    Warn,
+    Ok,
+    /// Drop the verdict altogether
+    Ignore,
+}
+
+impl SeverityLevel {
+    /// `None` for `Ignore`
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            SeverityLevel::Error => Some(Severity::Error),
+            SeverityLevel::Warn => Some(Severity::Warning),
+            SeverityLevel::Ok => Some(Severity::Ok),
+            SeverityLevel::Ignore => None,
+        }
+    }
+
+    /// `Ok` raised to `Warn`. `Warn` is never raised to `Error`, so a user's `warn` can't fail the
+    /// run, and `Error` and `Ignore` stay as they are.
+    fn raised(self) -> Self {
+        match self {
+            SeverityLevel::Ok => SeverityLevel::Warn,
+            level => level,
+        }
+    }
+
+    fn from_severity(severity: Severity) -> Self {
+        match severity {
+            Severity::Error => SeverityLevel::Error,
+            Severity::Warning => SeverityLevel::Warn,
+            Severity::Ok | Severity::Info => SeverityLevel::Ok,
+        }
+    }
+}
+
+/// Per-category overrides of the built-in severities. Categories without one keep theirs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SeverityMap {
+    overrides: HashMap<Category, SeverityLevel>,
+}
+
+impl SeverityMap {
+    pub fn set(&mut self, category: Category, level: SeverityLevel) {
+        self.overrides.insert(category, level);
+    }
+
+    pub fn get(&self, category: Category) -> Option<SeverityLevel> {
+        self.overrides.get(&category).copied()
+    }
+
+    /// The level for a verdict of `category` whose built-in severity is `severity`
+    pub fn resolve(&self, category: Option<Category>, severity: Severity) -> SeverityLevel {
+        category
+            .and_then(|category| self.get(category))
+            .unwrap_or_else(|| SeverityLevel::from_severity(severity))
+    }
+
+    /// Parses a `category=level` override, e.g. `untracked=warn`
+    pub fn parse_override(value: &str) -> Result<(Category, SeverityLevel), String> {
+        use clap::ValueEnum;
+        let (category, level) = value
+            .split_once('=')
+            .ok_or_else(|| format!("expected CATEGORY=LEVEL, got '{}'", value))?;
+        Ok((
+            Category::from_str(category, true)?,
+            SeverityLevel::from_str(level, true)?,
+        ))
+    }
+}
+
+/// Repository-level observations that aren't tied to a single local branch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    Symlink,
+    File,
+    /// The entry couldn't be read, so it was skipped
+    PermissionDenied,
+    /// Something unexpectedly failed while checking the entry
+    CheckFailed {
+        message: String,
+    },
+    NotARepository,
+    NoRemotes,
+    InvalidRemote,
+    UnqualifiedRemote {
+        remote: String,
+    },
+    RemoteFetchFailed {
+        remote: String,
+    },
+    RemoteFetchTimedOut {
+        remote: String,
+    },
+    UncommittedChanges {
+        count: usize,
+    },
+    /// Paths with unresolved conflicts, e.g. from a merge that was left half-resolved
+    UnmergedPaths {
+        paths: Vec<String>,
+    },
+    UntrackedFiles {
+        count: usize,
+    },
+    /// Stashed changes, which only exist in this clone
+    Stashes {
+        count: usize,
+    },
+    DetachedHead {
+        oid: git2::Oid,
+    },
+    /// HEAD is detached, but at the tip of a local branch, so no commit depends on it
+    DetachedAtBranchTip {
+        oid: git2::Oid,
+        branch: String,
+    },
+    /// HEAD is detached at a commit that no fetched remote contains
+    DetachedHeadNotPushed {
+        oid: git2::Oid,
+    },
+    NoLocalBranches,
+    /// HEAD is on a branch that has no commits yet, as in a freshly initialized repository
+    NoCommitsYet,
+    /// A branch on a remote that no local branch tracks, e.g. `origin/feature`
+    UntrackedRemoteBranch {
+        branch: String,
+    },
+    /// A merge, rebase, etc. was started but not finished, e.g. `rebase`
+    OperationInProgress {
+        operation: String,
+    },
+    /// History is truncated, so commits can look unrelated when they aren't
+    ShallowClone,
+    /// Skipped for --skip-ignored, since the repository it's nested in ignores it
+    IgnoredByRepository {
+        path: PathBuf,
+    },
+    /// The same repository as an entry checked earlier, reached through another name
+    DuplicateOf {
+        path: PathBuf,
+    },
+    /// The checked-out branch, which holds the work in progress, has no upstream. It goes with
+    /// the `no_upstream` category, but stays a warning when the other branches without one are
+    /// mapped to `ok`.
+    HeadBranchNoUpstream {
+        branch: String,
+    },
+    /// For --check-notes, notes that no fetched remote has
+    NotesNotPushed {
+        notes_ref: String,
+    },
+    /// For --check-default-branch, the remote's default branch has no local branch
+    DefaultBranchMissing {
+        branch: String,
+        remote: String,
+    },
+    /// For --check-default-branch, the local default branch isn't where the remote's is
+    DefaultBranchNotSynced {
+        branch: String,
+        remote: String,
+        ahead: usize,
+        behind: usize,
+    },
+    /// For --check-tags, a tag whose commit no fetched remote has
+    TagNotPushed {
+        tag: String,
+    },
+    /// Skipped for --modified-since, since nobody has worked in it lately
+    NotModifiedSince,
+    /// For --compare-remote, a branch whose tip isn't the same on that remote
+    DiffersFromRemote {
+        branch: String,
+        remote: String,
+    },
+    /// For --compare-remote, a branch that the remote doesn't have at all
+    MissingFromRemote {
+        branch: String,
+        remote: String,
+    },
+}
+
+impl Finding {
+    pub fn category(&self) -> Option<Category> {
+        match self {
+            Finding::NoRemotes => Some(Category::NoRemotes),
+            Finding::UncommittedChanges { .. } => Some(Category::Uncommitted),
+            Finding::UntrackedFiles { .. } => Some(Category::Untracked),
+            Finding::Stashes { .. } => Some(Category::Stash),
+            Finding::HeadBranchNoUpstream { .. } => Some(Category::NoUpstream),
+            _ => None,
+        }
+    }
+
+    /// The level under `severity_map`
+    pub fn severity_level(&self, severity_map: &SeverityMap) -> SeverityLevel {
+        let level = severity_map.resolve(self.category(), self.severity());
+        match self {
+            Finding::HeadBranchNoUpstream { .. } => level.raised(),
+            _ => level,
+        }
+    }
+
+    /// The built-in severity, before any `SeverityMap`
+    pub fn severity(&self) -> Severity {
+        match self {
+            Finding::UntrackedRemoteBranch { .. }
+            | Finding::DuplicateOf { .. }
+            | Finding::IgnoredByRepository { .. }
+            | Finding::NotModifiedSince
+            | Finding::DetachedAtBranchTip { .. }
+            | Finding::NoCommitsYet => Severity::Info,
+            Finding::Symlink
+            | Finding::PermissionDenied
+            | Finding::UnqualifiedRemote { .. }
+            | Finding::DetachedHead { .. }
+            | Finding::NoLocalBranches
+            | Finding::OperationInProgress { .. }
+            | Finding::ShallowClone
+            | Finding::DiffersFromRemote { .. }
+            | Finding::MissingFromRemote { .. }
+            | Finding::NotesNotPushed { .. }
+            | Finding::TagNotPushed { .. }
+            | Finding::DefaultBranchMissing { .. }
+            | Finding::DefaultBranchNotSynced { .. }
+            | Finding::Stashes { .. } => Severity::Warning,
+            Finding::File
+            | Finding::CheckFailed { .. }
+            | Finding::NotARepository
+            | Finding::NoRemotes
+            | Finding::InvalidRemote
+            | Finding::RemoteFetchFailed { .. }
+            | Finding::RemoteFetchTimedOut { .. }
+            | Finding::UncommittedChanges { .. }
+            | Finding::UnmergedPaths { .. }
+            | Finding::UntrackedFiles { .. }
+            | Finding::DetachedHeadNotPushed { .. }
+            | Finding::HeadBranchNoUpstream { .. } => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Symlink => write!(f, "symlink, skipped"),
+            Finding::File => write!(f, "file outside of any repository"),
+            Finding::PermissionDenied => write!(f, "skipped: permission denied"),
+            Finding::CheckFailed { message } => write!(f, "check failed: {}", message),
+            Finding::NotARepository => write!(f, "not a git repository"),
+            Finding::NoRemotes => write!(f, "no remotes"),
+            Finding::InvalidRemote => write!(f, "invalid remote"),
+            Finding::UnqualifiedRemote { remote } => {
+                write!(f, "remote {} is not a qualifying remote", remote)
+            }
+            Finding::RemoteFetchFailed { remote } => write!(f, "failed to fetch remote {}", remote),
+            Finding::RemoteFetchTimedOut { remote } => {
+                write!(f, "timed out fetching remote {}", remote)
+            }
+            Finding::UncommittedChanges { count } => write!(f, "{} uncommitted change(s)", count),
+            Finding::UnmergedPaths { paths } => write!(f, "unmerged paths: {}", paths.join(", ")),
+            Finding::UntrackedFiles { count } => write!(f, "{} untracked file(s)", count),
+            Finding::Stashes { count } => write!(f, "{} stash(es)", count),
+            Finding::DetachedHead { oid } => write!(f, "HEAD detached at {}", oid),
+            Finding::DetachedAtBranchTip { oid, branch } => {
+                write!(f, "HEAD detached at {}, the tip of branch {}", oid, branch)
+            }
+            Finding::DetachedHeadNotPushed { oid } => {
+                write!(f, "detached HEAD {} is not on any remote", oid)
+            }
+            Finding::NoLocalBranches => write!(f, "no local branches"),
+            Finding::NoCommitsYet => write!(f, "no commits yet"),
+            Finding::UntrackedRemoteBranch { branch } => {
+                write!(
+                    f,
+                    "remote branch {} isn't tracked by any local branch",
+                    branch
+                )
+            }
+            Finding::OperationInProgress { operation } => write!(f, "{} in progress", operation),
+            Finding::ShallowClone => write!(f, "shallow clone, ancestry may be inaccurate"),
+            Finding::DuplicateOf { path } => write!(f, "duplicate of {}", path.display()),
+            Finding::IgnoredByRepository { path } => {
+                write!(
+                    f,
+                    "ignored by the repository at {}, skipped",
+                    path.display()
+                )
+            }
+            Finding::HeadBranchNoUpstream { branch } => {
+                write!(f, "checked-out branch {} has no upstream", branch)
+            }
+            Finding::NotesNotPushed { notes_ref } => {
+                write!(f, "notes {} aren't on any fetched remote", notes_ref)
+            }
+            Finding::DefaultBranchMissing { branch, remote } => {
+                write!(
+                    f,
+                    "default branch {}/{} has no local branch",
+                    remote, branch
+                )
+            }
+            Finding::DefaultBranchNotSynced {
+                branch,
+                remote,
+                ahead,
+                behind,
+            } => write!(
+                f,
+                "default branch {} is {} ahead and {} behind {}/{}",
+                branch, ahead, behind, remote, branch
+            ),
+            Finding::TagNotPushed { tag } => {
+                write!(f, "tag {} isn't on any fetched remote", tag)
+            }
+            Finding::NotModifiedSince => write!(f, "not modified lately, skipped"),
+            Finding::DiffersFromRemote { branch, remote } => {
+                write!(f, "branch {} differs from {}/{}", branch, remote, branch)
+            }
+            Finding::MissingFromRemote { branch, remote } => {
+                write!(f, "branch {} isn't on remote {}", branch, remote)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchReport {
+    pub name: String,
+    /// Shorthand name of the upstream, e.g. `origin/main`
+    pub upstream: Option<String>,
+    pub remote: Option<String>,
+    pub status: BranchStatus,
+    /// Commits on the branch that aren't on the upstream. Only counted when compared against one.
+    pub ahead: usize,
+    /// Commits on the upstream that aren't on the branch. Only counted when compared against one.
+    pub behind: usize,
+    /// The tip commit, for branches that are ahead, diverged or without an upstream
+    pub last_commit: Option<LastCommit>,
+}
+
+/// When the tip of a branch was committed, and by whom, to tell active work from abandoned work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastCommit {
+    /// In the committer's time zone
+    pub committed_at: DateTime<FixedOffset>,
+    pub author: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BranchStatus {
+    /// The local tip is the upstream's tip
+    UpToDate,
+    /// The local tip is in the ancestry of the upstream, so the work is safe but not pulled yet
+    Behind,
+    /// The upstream is in the ancestry of the local tip
+    Ahead,
+    /// Ahead, but unpushed commits are expected
+    AllowedAhead,
+    /// Neither tip is in the ancestry of the other
+    Diverged,
+    /// Looks diverged, but in a shallow clone, where the missing history may well connect the tips
+    Unverifiable,
+    NoUpstream,
+    /// No upstream, but the branch is allowed to be local-only
+    AllowedLocal,
+    /// The upstream lives on a remote that wasn't (successfully) fetched
+    RemoteNotFetched,
+    /// The upstream lives on a remote that was left out with --remotes
+    RemoteNotSelected,
+    /// The upstream lives on a remote whose url isn't qualifying, so it was never fetched
+    RemoteNotQualifying,
+    /// Some git operation failed while looking at the branch
+    Failed,
+}
+
+impl BranchStatus {
+    /// `AllowedAhead` has none, since --allow-ahead already says how to treat it
+    pub fn category(self) -> Option<Category> {
+        match self {
+            BranchStatus::Ahead => Some(Category::Ahead),
+            BranchStatus::Diverged => Some(Category::Diverged),
+            BranchStatus::NoUpstream => Some(Category::NoUpstream),
+            _ => None,
+        }
+    }
+
+    /// The built-in severity, before any `SeverityMap`
+    pub fn severity(self) -> Severity {
+        match self {
+            BranchStatus::UpToDate | BranchStatus::Behind => Severity::Ok,
+            BranchStatus::AllowedLocal => Severity::Info,
+            BranchStatus::AllowedAhead
+            | BranchStatus::Unverifiable
+            | BranchStatus::RemoteNotSelected
+            | BranchStatus::RemoteNotQualifying => Severity::Warning,
+            BranchStatus::Ahead
+            | BranchStatus::Diverged
+            | BranchStatus::NoUpstream
+            | BranchStatus::RemoteNotFetched
+            | BranchStatus::Failed => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for BranchStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            BranchStatus::UpToDate => "up to date",
+            BranchStatus::Behind => "behind upstream",
+            BranchStatus::Ahead => "ahead of upstream",
+            BranchStatus::AllowedAhead => "ahead of upstream (allowed)",
+            BranchStatus::Diverged => "diverged from upstream",
+            BranchStatus::Unverifiable => "can't tell if diverged (shallow clone)",
+            BranchStatus::NoUpstream => "no upstream",
+            BranchStatus::AllowedLocal => "local-only (allowed)",
+            BranchStatus::RemoteNotFetched => "upstream remote not fetched",
+            BranchStatus::RemoteNotSelected => "upstream remote not checked",
+            BranchStatus::RemoteNotQualifying => "upstream remote not qualifying",
+            BranchStatus::Failed => "check failed",
+        })
+    }
+}
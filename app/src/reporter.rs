@@ -0,0 +1,38 @@
+//! Where a run's messages and verdicts end up. `main` picks an implementation by `--format`.
+
+use crate::report::{RepoReport, ScanSummary, Severity};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A report file shared by every reporter of the run
+pub type OutputFile = Arc<Mutex<File>>;
+
+/// Gets told about each entry as it's checked, then about the whole run once it's done
+pub trait Reporter {
+    /// `path` is shown as it is in the entry's messages
+    fn repo_started(&mut self, _path: &Path) {}
+    /// A message about the entry that was last started, or about the run when none is
+    fn finding(&mut self, _severity: Severity, _message: &str) {}
+    fn repo_finished(&mut self, report: &RepoReport);
+    fn run_finished(&mut self, summary: &ScanSummary) -> io::Result<()>;
+    /// Writes out whatever is held back, e.g. before asking a question
+    fn flush(&mut self) {}
+}
+
+/// Writes a report rendered at the end of the run to `output`, or stdout
+pub fn write_rendered(output: Option<&OutputFile>, rendered: &str) -> io::Result<()> {
+    match output {
+        Some(output) => {
+            let mut file = output.lock().unwrap_or_else(|error| error.into_inner());
+            file.write_all(rendered.as_bytes())?;
+            file.flush()
+        }
+        None => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(rendered.as_bytes())?;
+            stdout.flush()
+        }
+    }
+}
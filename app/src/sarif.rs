@@ -0,0 +1,248 @@
+//! A minimal SARIF 2.1.0 log of a whole run, for code-scanning dashboards.
+//!
+//! Every finding and every branch verdict worth mentioning becomes a result located at its
+//! repository. Only the parts of SARIF that upload tools insist on are filled in, plus the run's
+//! summary in the run's property bag.
+
+use crate::json_report::summary_object;
+use crate::report::{BranchStatus, Finding, RepoReport, ScanSummary, Severity};
+use crate::reporter::{write_rendered, OutputFile, Reporter};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::Path;
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Collects the reports, for a SARIF log of the whole run once the run is done
+pub struct SarifReporter {
+    reports: Vec<RepoReport>,
+    output: Option<OutputFile>,
+}
+
+impl SarifReporter {
+    /// Writes to `output` instead of stdout
+    pub fn new(output: Option<OutputFile>) -> Self {
+        Self {
+            reports: Vec::new(),
+            output,
+        }
+    }
+}
+
+impl Reporter for SarifReporter {
+    fn repo_finished(&mut self, report: &RepoReport) {
+        self.reports.push(report.clone());
+    }
+    fn run_finished(&mut self, summary: &ScanSummary) -> io::Result<()> {
+        // Always written, since an empty log still tells the dashboard the run was clean
+        write_rendered(self.output.as_ref(), &render(&self.reports, summary))
+    }
+}
+
+pub fn render(reports: &[RepoReport], summary: &ScanSummary) -> String {
+    let mut rule_ids = BTreeSet::new();
+    let mut results = Vec::new();
+    for report in reports {
+        for finding in &report.findings {
+            let severity = report.finding_severity(finding);
+            if severity > Severity::Ok {
+                rule_ids.insert(finding_rule_id(finding));
+                results.push(result(
+                    finding_rule_id(finding),
+                    severity,
+                    &finding.to_string(),
+                    &report.path,
+                ));
+            }
+        }
+        for branch in &report.branches {
+            let severity = report.branch_severity(branch);
+            if severity > Severity::Ok {
+                rule_ids.insert(branch_rule_id(branch.status));
+                results.push(result(
+                    branch_rule_id(branch.status),
+                    severity,
+                    &format!("branch {}: {}", branch.name, branch.status),
+                    &report.path,
+                ));
+            }
+        }
+    }
+    let rules: Vec<Value> = rule_ids.into_iter().map(|id| json!({ "id": id })).collect();
+    let log = json!({
+        "version": "2.1.0",
+        "$schema": SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+            "properties": { "summary": summary_object(summary) },
+        }],
+    });
+    format!("{:#}\n", log)
+}
+
+fn result(rule_id: &str, severity: Severity, message: &str, path: &Path) -> Value {
+    json!({
+        "ruleId": rule_id,
+        "level": level(severity),
+        "message": { "text": message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": { "uri": path_uri(path) }
+            }
+        }],
+    })
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Ok | Severity::Info => "note",
+    }
+}
+
+/// A uri reference for `path`: absolute paths become `file://` uris, relative ones stay relative
+fn path_uri(path: &Path) -> String {
+    let mut uri = String::new();
+    if path.has_root() {
+        uri.push_str("file://");
+    }
+    for byte in path.to_string_lossy().replace('\\', "/").bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+pub(crate) fn finding_rule_id(finding: &Finding) -> &'static str {
+    match finding {
+        Finding::Symlink => "symlink",
+        Finding::File => "file",
+        Finding::PermissionDenied => "permission-denied",
+        Finding::CheckFailed { .. } => "check-failed",
+        Finding::NotARepository => "not-a-repository",
+        Finding::NotModifiedSince => "not-modified-since",
+        Finding::NotesNotPushed { .. } => "notes-not-pushed",
+        Finding::TagNotPushed { .. } => "tag-not-pushed",
+        Finding::DefaultBranchMissing { .. } => "default-branch-missing",
+        Finding::DefaultBranchNotSynced { .. } => "default-branch-not-synced",
+        Finding::NoRemotes => "no-remotes",
+        Finding::InvalidRemote => "invalid-remote",
+        Finding::UnqualifiedRemote { .. } => "unqualified-remote",
+        Finding::RemoteFetchFailed { .. } => "remote-fetch-failed",
+        Finding::RemoteFetchTimedOut { .. } => "remote-fetch-timed-out",
+        Finding::UncommittedChanges { .. } => "uncommitted-changes",
+        Finding::UnmergedPaths { .. } => "unmerged-paths",
+        Finding::UntrackedFiles { .. } => "untracked-files",
+        Finding::Stashes { .. } => "stashes",
+        Finding::DetachedHead { .. } => "detached-head",
+        Finding::DetachedAtBranchTip { .. } => "detached-at-branch-tip",
+        Finding::DetachedHeadNotPushed { .. } => "detached-head-not-pushed",
+        Finding::NoLocalBranches => "no-local-branches",
+        Finding::NoCommitsYet => "no-commits-yet",
+        Finding::UntrackedRemoteBranch { .. } => "untracked-remote-branch",
+        Finding::OperationInProgress { .. } => "operation-in-progress",
+        Finding::ShallowClone => "shallow-clone",
+        Finding::DuplicateOf { .. } => "duplicate",
+        Finding::IgnoredByRepository { .. } => "ignored-by-repository",
+        Finding::HeadBranchNoUpstream { .. } => "head-branch-no-upstream",
+        Finding::DiffersFromRemote { .. } => "differs-from-remote",
+        Finding::MissingFromRemote { .. } => "missing-from-remote",
+    }
+}
+
+pub(crate) fn branch_rule_id(status: BranchStatus) -> &'static str {
+    match status {
+        BranchStatus::UpToDate => "branch-up-to-date",
+        BranchStatus::Behind => "branch-behind",
+        BranchStatus::Ahead => "branch-ahead",
+        BranchStatus::AllowedAhead => "branch-allowed-ahead",
+        BranchStatus::Diverged => "branch-diverged",
+        BranchStatus::Unverifiable => "branch-unverifiable",
+        BranchStatus::NoUpstream => "branch-no-upstream",
+        BranchStatus::AllowedLocal => "branch-allowed-local",
+        BranchStatus::RemoteNotFetched => "branch-remote-not-fetched",
+        BranchStatus::RemoteNotSelected => "branch-remote-not-selected",
+        BranchStatus::RemoteNotQualifying => "branch-remote-not-qualifying",
+        BranchStatus::Failed => "branch-failed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::BranchReport;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn results_for_findings_and_branches() {
+        let mut report = RepoReport::new(PathBuf::from("/code/my repo"));
+        report.findings.push(Finding::NoRemotes);
+        report.branches.push(BranchReport {
+            name: "main".to_owned(),
+            upstream: None,
+            remote: None,
+            status: BranchStatus::UpToDate,
+            ahead: 0,
+            behind: 0,
+            last_commit: None,
+        });
+        report.branches.push(BranchReport {
+            name: "wip".to_owned(),
+            upstream: Some("origin/wip".to_owned()),
+            remote: Some("origin".to_owned()),
+            status: BranchStatus::Ahead,
+            ahead: 1,
+            behind: 0,
+            last_commit: None,
+        });
+        let reports = [report];
+        let summary = ScanSummary::new(&reports, Duration::ZERO);
+        let log: Value = serde_json::from_str(&render(&reports, &summary)).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["properties"]["summary"]["with_problems"], 1);
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([{ "id": "branch-ahead" }, { "id": "no-remotes" }])
+        );
+        assert_eq!(
+            run["results"],
+            json!([
+                {
+                    "ruleId": "no-remotes",
+                    "level": "error",
+                    "message": { "text": "no remotes" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "file:///code/my%20repo" }
+                        }
+                    }],
+                },
+                {
+                    "ruleId": "branch-ahead",
+                    "level": "error",
+                    "message": { "text": "branch wip: ahead of upstream" },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": "file:///code/my%20repo" }
+                        }
+                    }],
+                },
+            ])
+        );
+    }
+}
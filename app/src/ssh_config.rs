@@ -0,0 +1,378 @@
+//! Just enough of `ssh_config(5)` to pick the identity, user and real host name for a host.
+//!
+//! libgit2 talks to ssh servers through libssh2, which never reads `~/.ssh/config` itself.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default)]
+pub struct SshConfig {
+    blocks: Vec<HostBlock>,
+}
+
+/// The options from one `Host` (or `Match`) section
+#[derive(Clone, Debug)]
+struct HostBlock {
+    /// `None` for `Match` sections, which aren't supported and so never apply
+    patterns: Option<Vec<String>>,
+    identity_files: Vec<PathBuf>,
+    user: Option<String>,
+    host_name: Option<String>,
+}
+
+/// The options that apply to a particular host
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostConfig {
+    pub identity_files: Vec<PathBuf>,
+    pub user: Option<String>,
+    /// The host to actually connect to, when the one asked for is an alias
+    pub host_name: Option<String>,
+}
+
+impl SshConfig {
+    /// Reads the config at `path`. A missing file is the same as an empty one.
+    pub fn load(path: &Path, home_dir: Option<&Path>) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(Self::parse(&contents, home_dir)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn parse(contents: &str, home_dir: Option<&Path>) -> Self {
+        // Options before the first `Host` line apply to every host
+        let mut blocks = vec![HostBlock::new(Some(vec!["*".to_owned()]))];
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // Both `Keyword value` and `Keyword=value` are allowed
+            let Some((keyword, value)) = line.split_once(|c: char| c.is_whitespace() || c == '=')
+            else {
+                continue;
+            };
+            let value = value
+                .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+                .trim();
+            let value = unquote(value);
+            match keyword.to_ascii_lowercase().as_str() {
+                "host" => blocks.push(HostBlock::new(Some(
+                    value.split_whitespace().map(str::to_owned).collect(),
+                ))),
+                "match" => blocks.push(HostBlock::new(None)),
+                "identityfile" => {
+                    let block = blocks.last_mut().unwrap();
+                    block.identity_files.push(expand_tilde(value, home_dir));
+                }
+                "user" => {
+                    let block = blocks.last_mut().unwrap();
+                    block.user.get_or_insert_with(|| value.to_owned());
+                }
+                "hostname" => {
+                    let block = blocks.last_mut().unwrap();
+                    block.host_name.get_or_insert_with(|| value.to_owned());
+                }
+                _ => {}
+            }
+        }
+        Self { blocks }
+    }
+
+    /// Combines every section matching `host`. Like ssh, the first `User` and `HostName` win
+    /// and all `IdentityFile`s are kept in order. A `%h` in `HostName` stands for `host`.
+    pub fn resolve(&self, host: &str) -> HostConfig {
+        let mut resolved = HostConfig::default();
+        for block in self.blocks.iter().filter(|block| block.matches(host)) {
+            if resolved.user.is_none() {
+                resolved.user = block.user.clone();
+            }
+            if resolved.host_name.is_none() {
+                resolved.host_name = block
+                    .host_name
+                    .as_ref()
+                    .map(|host_name| host_name.replace("%h", host));
+            }
+            resolved
+                .identity_files
+                .extend(block.identity_files.iter().cloned());
+        }
+        resolved
+    }
+}
+
+impl HostBlock {
+    fn new(patterns: Option<Vec<String>>) -> Self {
+        Self {
+            patterns,
+            identity_files: Vec::new(),
+            user: None,
+            host_name: None,
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        let Some(patterns) = &self.patterns else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let matches = |pattern: &str| {
+            glob::Pattern::new(&pattern.to_ascii_lowercase())
+                .is_ok_and(|pattern| pattern.matches(&host))
+        };
+        // A matching negated pattern rules the section out regardless of the others
+        let negated = patterns
+            .iter()
+            .filter_map(|pattern| pattern.strip_prefix('!'))
+            .any(matches);
+        !negated
+            && patterns
+                .iter()
+                .filter(|pattern| !pattern.starts_with('!'))
+                .any(|pattern| matches(pattern))
+    }
+}
+
+/// Extracts the host from both `ssh://[user@]host[:port]/path` and scp-like `[user@]host:path` urls
+pub fn host_of_url(url: &str) -> Option<&str> {
+    let host = match url.split_once("://") {
+        Some((_, rest)) => {
+            let authority = rest.split('/').next()?;
+            let host_and_port = authority.rsplit('@').next()?;
+            host_and_port.split(':').next()?
+        }
+        None => {
+            // Without a colon (or with a slash before it) it's a local path
+            let (authority, _) = url.split_once(':')?;
+            if authority.contains('/') {
+                return None;
+            }
+            authority.rsplit('@').next()?
+        }
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// The port in an `ssh://` url, if it has one. The scp-like form can't name one.
+pub fn port_of_url(url: &str) -> Option<u16> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split('/').next()?;
+    let host_and_port = authority.rsplit('@').next()?;
+    host_and_port.split_once(':')?.1.parse().ok()
+}
+
+/// `url` with its host swapped for `host`, in either of the forms `host_of_url` understands
+pub fn with_host(url: &str, host: &str) -> Option<String> {
+    let old_host = host_of_url(url)?;
+    // The host is the first thing after the user, if any, in both forms
+    let start = match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let authority = rest.split('/').next()?;
+            scheme.len() + 3 + authority.rfind('@').map_or(0, |at| at + 1)
+        }
+        None => url.split_once(':')?.0.rfind('@').map_or(0, |at| at + 1),
+    };
+    Some(format!(
+        "{}{}{}",
+        &url[..start],
+        host,
+        &url[start + old_host.len()..]
+    ))
+}
+
+/// The flags of `ssh(1)` that take a value
+const SSH_FLAGS_WITH_VALUES: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// The identities and user that an ssh command line like git's `core.sshCommand` passes to ssh,
+/// through `-i`, `-l` and `-o`. Everything else on it is left to the real ssh, which libgit2 never
+/// runs.
+pub fn parse_ssh_command(command: &str, home_dir: Option<&Path>) -> HostConfig {
+    let mut parsed = HostConfig::default();
+    let mut words = command.split_whitespace().map(unquote);
+    while let Some(word) = words.next() {
+        let Some(flag) = word.strip_prefix('-').and_then(|flag| flag.chars().next()) else {
+            continue;
+        };
+        if !SSH_FLAGS_WITH_VALUES.contains(flag) {
+            continue;
+        }
+        // The value can be attached, as in `-i~/.ssh/key`, or be the next word
+        let value = match &word[1 + flag.len_utf8()..] {
+            "" => words.next().unwrap_or_default(),
+            attached => attached,
+        };
+        match flag {
+            'i' => parsed.identity_files.push(expand_tilde(value, home_dir)),
+            'l' => {
+                parsed.user.get_or_insert_with(|| value.to_owned());
+            }
+            'o' => {
+                let Some((keyword, value)) = value.split_once('=') else {
+                    continue;
+                };
+                match keyword.to_ascii_lowercase().as_str() {
+                    "identityfile" => parsed.identity_files.push(expand_tilde(value, home_dir)),
+                    "user" => {
+                        parsed.user.get_or_insert_with(|| value.to_owned());
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    parsed
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+fn expand_tilde(path: &str, home_dir: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~/"), home_dir) {
+        (Some(rest), Some(home_dir)) => home_dir.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+IdentityFile ~/.ssh/id_default
+
+Host github-work github.com
+    User git
+    IdentityFile ~/.ssh/id_work
+    HostName github.com
+
+Host *.example.com !internal.example.com
+    User=\"deploy\"
+    IdentityFile=/keys/example
+
+Match host github.com
+    IdentityFile ~/.ssh/never
+
+Host *
+    User fallback
+";
+
+    fn config() -> SshConfig {
+        SshConfig::parse(CONFIG, Some(Path::new("/home/me")))
+    }
+
+    #[test]
+    fn first_user_wins_and_identities_accumulate() {
+        assert_eq!(
+            config().resolve("GitHub.com"),
+            HostConfig {
+                identity_files: vec![
+                    PathBuf::from("/home/me/.ssh/id_default"),
+                    PathBuf::from("/home/me/.ssh/id_work"),
+                ],
+                user: Some("git".to_owned()),
+                host_name: Some("github.com".to_owned()),
+            }
+        );
+    }
+
+    #[test]
+    fn aliases_resolve_to_their_host_name() {
+        assert_eq!(
+            config().resolve("github-work").host_name.as_deref(),
+            Some("github.com")
+        );
+        let config = SshConfig::parse("Host *.internal\n    HostName %h.example.com\n", None);
+        assert_eq!(
+            config.resolve("git.internal").host_name.as_deref(),
+            Some("git.internal.example.com")
+        );
+        assert_eq!(config.resolve("github.com").host_name, None);
+    }
+
+    #[test]
+    fn wildcards_and_negation() {
+        assert_eq!(
+            config().resolve("git.example.com").user.as_deref(),
+            Some("deploy")
+        );
+        assert_eq!(
+            config().resolve("internal.example.com"),
+            HostConfig {
+                identity_files: vec![PathBuf::from("/home/me/.ssh/id_default")],
+                user: Some("fallback".to_owned()),
+                host_name: None,
+            }
+        );
+    }
+
+    #[test]
+    fn ssh_commands() {
+        assert_eq!(
+            parse_ssh_command(
+                "ssh -i ~/.ssh/id_work -o IdentitiesOnly=yes -oUser=git -l other",
+                Some(Path::new("/home/me"))
+            ),
+            HostConfig {
+                identity_files: vec![PathBuf::from("/home/me/.ssh/id_work")],
+                user: Some("git".to_owned()),
+                host_name: None,
+            }
+        );
+        assert_eq!(
+            parse_ssh_command("ssh -v -i/keys/a -p 22 -o IdentityFile=/keys/b", None)
+                .identity_files,
+            vec![PathBuf::from("/keys/a"), PathBuf::from("/keys/b")]
+        );
+    }
+
+    #[test]
+    fn hosts_of_urls() {
+        assert_eq!(
+            host_of_url("git@github.com:org/repo.git"),
+            Some("github.com")
+        );
+        assert_eq!(host_of_url("github.com:org/repo.git"), Some("github.com"));
+        assert_eq!(
+            host_of_url("ssh://git@ssh.github.com:443/org/repo.git"),
+            Some("ssh.github.com")
+        );
+        assert_eq!(
+            host_of_url("https://github.com/org/repo.git"),
+            Some("github.com")
+        );
+        assert_eq!(host_of_url("/srv/git/repo.git"), None);
+    }
+
+    #[test]
+    fn ports_of_urls() {
+        assert_eq!(
+            port_of_url("ssh://git@ssh.github.com:443/org/repo.git"),
+            Some(443)
+        );
+        assert_eq!(port_of_url("ssh://git@github.com/org/repo.git"), None);
+        assert_eq!(port_of_url("git@github.com:org/repo.git"), None);
+    }
+
+    #[test]
+    fn swapping_hosts() {
+        assert_eq!(
+            with_host("git@github-work:org/repo.git", "github.com").as_deref(),
+            Some("git@github.com:org/repo.git")
+        );
+        assert_eq!(
+            with_host("github-work:org/repo.git", "github.com").as_deref(),
+            Some("github.com:org/repo.git")
+        );
+        assert_eq!(
+            with_host("ssh://git@github-work:443/org/repo.git", "ssh.github.com").as_deref(),
+            Some("ssh://git@ssh.github.com:443/org/repo.git")
+        );
+        assert_eq!(with_host("/srv/git/repo.git", "github.com"), None);
+    }
+}
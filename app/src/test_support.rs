@@ -0,0 +1,126 @@
+//! Throwaway repositories for exercising the checks against real git state.
+
+use crate::check::CheckOptions;
+use crate::credentials::Credentials;
+use git2::{Oid, Repository, RepositoryInitOptions, Signature};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A bare "remote" and a local clone of it whose `main` tracks `origin/main`.
+///
+/// Both live in the same temporary directory, which is deleted on drop.
+pub struct Fixture {
+    pub dir: TempDir,
+    pub remote: Repository,
+    pub local: Repository,
+}
+
+impl Fixture {
+    /// Builds the synced baseline: one commit, pushed, with the upstream configured.
+    pub fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        let remote = Repository::init_opts(
+            dir.path().join("remote.git"),
+            RepositoryInitOptions::new().bare(true).initial_head("main"),
+        )
+        .unwrap();
+        let local = Repository::init_opts(
+            dir.path().join("local"),
+            RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        local
+            .remote("origin", remote.path().to_str().unwrap())
+            .unwrap();
+        commit(&local, "initial");
+        push(&local, "origin", "main");
+        fetch(&local, "origin");
+        local
+            .find_branch("main", git2::BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("origin/main"))
+            .unwrap();
+        Self { dir, remote, local }
+    }
+
+    /// Creates another bare repository and registers it as a remote of the local clone
+    pub fn add_remote(&self, name: &str) -> Repository {
+        let remote = Repository::init_opts(
+            self.dir.path().join(format!("{name}.git")),
+            RepositoryInitOptions::new().bare(true).initial_head("main"),
+        )
+        .unwrap();
+        self.local
+            .remote(name, remote.path().to_str().unwrap())
+            .unwrap();
+        remote
+    }
+
+    pub fn local_path(&self) -> PathBuf {
+        self.local.workdir().unwrap().to_path_buf()
+    }
+
+    /// Options that treat every remote inside the fixture directory as qualifying
+    pub fn options(&self) -> CheckOptions {
+        let mut options = CheckOptions::new(Credentials::default());
+        options.qualifying_url_prefixes = vec![self.dir.path().to_str().unwrap().to_owned()];
+        options
+    }
+
+    /// Pushes a new commit to the remote's `main` from a separate clone, as a collaborator would
+    pub fn push_from_elsewhere(&self, message: &str) -> Oid {
+        let elsewhere = Repository::clone(
+            self.remote.path().to_str().unwrap(),
+            self.dir.path().join(format!("elsewhere-{}", slug(message))),
+        )
+        .unwrap();
+        let oid = commit(&elsewhere, message);
+        push(&elsewhere, "origin", "main");
+        oid
+    }
+}
+
+/// Commits a new file on HEAD of a non-bare repository, going through the index like `git commit` would
+pub fn commit(repo: &Repository, message: &str) -> Oid {
+    let file_name = format!("{}.txt", slug(message));
+    fs::write(repo.workdir().unwrap().join(&file_name), message).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new(&file_name)).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("check-gits", "check-gits@example.com").unwrap();
+    let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )
+    .unwrap()
+}
+
+pub fn push(repo: &Repository, remote: &str, branch: &str) {
+    let refspec = format!("refs/heads/{branch}:refs/heads/{branch}");
+    repo.find_remote(remote)
+        .unwrap()
+        .push(&[refspec.as_str()], None)
+        .unwrap();
+}
+
+pub fn fetch(repo: &Repository, remote: &str) {
+    repo.find_remote(remote)
+        .unwrap()
+        .fetch(&[] as &[&str], None, None)
+        .unwrap();
+}
+
+fn slug(message: &str) -> String {
+    message
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
@@ -0,0 +1,413 @@
+//! Human-readable lines, or GitHub Actions workflow commands, written as each entry is done.
+
+use crate::printer::{OutputFormat, Theme, Verbosity};
+use crate::report::{RepoReport, ScanSummary, Severity};
+use crate::reporter::{OutputFile, Reporter};
+use std::borrow::Cow;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR, MAIN_SEPARATOR_STR};
+
+/// Without its glyph, which comes from the theme when it's written
+struct Message {
+    severity: Severity,
+    text: String,
+}
+
+pub struct TextReporter {
+    verbosity: Verbosity,
+    /// `Text` or `Github`
+    format: OutputFormat,
+    errors_to_stderr: bool,
+    theme: Theme,
+    /// Lines are kept to this many columns by shortening their paths
+    width: Option<usize>,
+    /// Written instead of stdout
+    output: Option<OutputFile>,
+    /// The entry whose messages these are, which they're grouped under
+    entry: Option<PathBuf>,
+    /// Whether the entry's header has been written to stdout and stderr yet
+    headers_written: [bool; 2],
+    messages: Vec<Message>,
+}
+
+impl TextReporter {
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self {
+            verbosity,
+            format: OutputFormat::Text,
+            errors_to_stderr: false,
+            theme: Theme::default(),
+            width: None,
+            output: None,
+            entry: None,
+            headers_written: [false; 2],
+            messages: Vec::new(),
+        }
+    }
+    /// Only `Text` and `Github` are written as they come
+    pub fn with_format(mut self, format: OutputFormat) -> Self {
+        self.format = format;
+        self
+    }
+    /// Send warnings and errors to stderr, leaving stdout for everything else
+    pub fn with_errors_to_stderr(mut self, errors_to_stderr: bool) -> Self {
+        self.errors_to_stderr = errors_to_stderr;
+        self
+    }
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+    /// Shorten the path a line starts with when the line is wider than `width`
+    pub fn with_width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+    /// Write to `output` instead of stdout. Whatever goes to stderr still does.
+    pub fn with_output(mut self, output: Option<OutputFile>) -> Self {
+        self.output = output;
+        self
+    }
+    fn write_messages(
+        &mut self,
+        stdout: &mut impl Write,
+        stderr: &mut impl Write,
+    ) -> io::Result<()> {
+        let entry = match &self.entry {
+            Some(entry) if self.format == OutputFormat::Text => entry.clone(),
+            _ => return self.write_ungrouped(stdout, stderr),
+        };
+        // A repository with nothing to point out gets a single line, unless asked for the details
+        let nothing_to_point_out = self
+            .messages
+            .iter()
+            .all(|message| message.severity == Severity::Ok);
+        if nothing_to_point_out
+            && self.verbosity == Verbosity::Normal
+            && self.headers_written == [false; 2]
+        {
+            self.headers_written = [true; 2];
+            return writeln!(stdout, "{}", self.entry_line(self.theme.ok, &entry));
+        }
+        let prefix = format!("{}: ", entry.display());
+        let mut headers_written = self.headers_written;
+        for message in self.messages.iter() {
+            let to_stderr = self.errors_to_stderr && message.severity >= Severity::Warning;
+            let out: &mut dyn Write = if to_stderr { stderr } else { stdout };
+            // Each stream gets the header before its first message
+            if !headers_written[usize::from(to_stderr)] {
+                headers_written[usize::from(to_stderr)] = true;
+                writeln!(out, "{}", self.entry_line(self.theme.repo, &entry))?;
+            }
+            let line = match message.text.strip_prefix(&prefix) {
+                Some(text) => format!("{} {}", self.theme.glyph(message.severity), text),
+                None => self.themed(message),
+            };
+            writeln!(out, "  {}", line.replace('\n', "\n  "))?;
+        }
+        self.headers_written = headers_written;
+        Ok(())
+    }
+    fn write_ungrouped(&self, stdout: &mut impl Write, stderr: &mut impl Write) -> io::Result<()> {
+        for message in self.messages.iter() {
+            let out: &mut dyn Write =
+                if self.errors_to_stderr && message.severity >= Severity::Warning {
+                    stderr
+                } else {
+                    stdout
+                };
+            match self.format {
+                OutputFormat::Github => writeln!(out, "{}", self.github_annotation(message))?,
+                _ => writeln!(out, "{}", self.themed(message))?,
+            }
+        }
+        Ok(())
+    }
+    /// The entry's path after `glyph`, shortened to fit if need be
+    fn entry_line(&self, glyph: &str, entry: &Path) -> String {
+        let path = entry.display().to_string();
+        let path = match self.width {
+            Some(width) if glyph_width(glyph) + 1 + path.chars().count() > width => {
+                shorten_path(&path, width.saturating_sub(glyph_width(glyph) + 1))
+            }
+            _ => path,
+        };
+        format!("{} {}", glyph, path)
+    }
+    fn themed(&self, message: &Message) -> String {
+        let glyph = self.theme.glyph(message.severity);
+        let text = match self.width {
+            Some(width) => {
+                fit_to_width(&message.text, width.saturating_sub(glyph_width(glyph) + 1))
+            }
+            None => Cow::Borrowed(message.text.as_str()),
+        };
+        format!("{} {}", glyph, text)
+    }
+    /// Problems become `::error::`/`::warning::` commands; anything else is printed as text
+    fn github_annotation(&self, message: &Message) -> String {
+        let command = match message.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Ok | Severity::Info => return self.themed(message),
+        };
+        // The annotation has its own icon, so there's no glyph
+        // See https://github.com/actions/toolkit/blob/main/packages/core/src/command.ts
+        let text = message
+            .text
+            .replace('%', "%25")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+        format!("::{}::{}", command, text)
+    }
+}
+
+impl Reporter for TextReporter {
+    fn repo_started(&mut self, path: &Path) {
+        self.flush();
+        self.entry = Some(path.to_path_buf());
+        self.headers_written = [false; 2];
+    }
+    fn finding(&mut self, severity: Severity, message: &str) {
+        self.messages.push(Message {
+            severity,
+            text: message.to_owned(),
+        });
+    }
+    fn repo_finished(&mut self, _report: &RepoReport) {
+        self.flush();
+        self.entry = None;
+    }
+    fn run_finished(&mut self, _summary: &ScanSummary) -> io::Result<()> {
+        self.flush();
+        Ok(())
+    }
+    /// Writes out all pending messages in one go
+    fn flush(&mut self) {
+        if self.messages.is_empty() {
+            return;
+        }
+        // Holding the locks for the whole batch keeps this entry's lines together
+        let output_file = self.output.clone();
+        let output = output_file
+            .as_ref()
+            .map(|output| output.lock().unwrap_or_else(|error| error.into_inner()));
+        let mut stdout: BufWriter<Box<dyn Write + '_>> = BufWriter::new(match &output {
+            Some(file) => Box::new(&**file),
+            None => Box::new(io::stdout().lock()),
+        });
+        let mut stderr = BufWriter::new(io::stderr().lock());
+        // There's nowhere left to report a failure to write to stdout (e.g. a closed pipe)
+        let _ = self
+            .write_messages(&mut stdout, &mut stderr)
+            .and_then(|()| stdout.flush())
+            .and_then(|()| stderr.flush());
+        self.messages.clear();
+    }
+}
+
+/// Emoji take up two columns, and the ascii tags take up as many as they have characters
+fn glyph_width(glyph: &str) -> usize {
+    if glyph.is_ascii() {
+        glyph.len()
+    } else {
+        2
+    }
+}
+
+/// Shortens the path that `text` starts with (as in `<path>: <what happened>`) so that the text's
+/// first line fits in `width` columns. What happened is always shown in full.
+fn fit_to_width(text: &str, width: usize) -> Cow<'_, str> {
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.chars().count() <= width {
+        return Cow::Borrowed(text);
+    }
+    let Some((path, rest)) = text.split_once(": ") else {
+        return Cow::Borrowed(text);
+    };
+    if !path.contains(MAIN_SEPARATOR) || path.contains('\n') {
+        return Cow::Borrowed(text);
+    }
+    let rest_width = first_line.chars().count() - path.chars().count();
+    let path = shorten_path(path, width.saturating_sub(rest_width));
+    Cow::Owned(format!("{}: {}", path, rest))
+}
+
+/// Shortens `path` to `width` columns if it can, by writing the home directory as `~` and then
+/// replacing components in the middle with `...`. The first and last components always stay.
+fn shorten_path(path: &str, width: usize) -> String {
+    let home = dirs::home_dir().and_then(|home| home.to_str().map(str::to_owned));
+    let path = match home.as_deref().and_then(|home| path.strip_prefix(home)) {
+        Some(in_home) if in_home.is_empty() || in_home.starts_with(MAIN_SEPARATOR) => {
+            format!("~{}", in_home)
+        }
+        _ => path.to_owned(),
+    };
+    let components: Vec<&str> = path.split(MAIN_SEPARATOR).collect();
+    let mut shortened = path.clone();
+    // Drop ever more components, keeping as many at the start as at the end
+    for dropped in 1..components.len().saturating_sub(1) {
+        if shortened.chars().count() <= width {
+            break;
+        }
+        let kept = components.len() - dropped;
+        let back = (kept / 2).max(1);
+        let front = kept - back;
+        shortened = components[..front]
+            .iter()
+            .chain(&["..."])
+            .chain(&components[components.len() - back..])
+            .copied()
+            .collect::<Vec<_>>()
+            .join(MAIN_SEPARATOR_STR);
+    }
+    shortened
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::printer::{Printer, ThemeName};
+
+    /// What `reporter` writes to stdout and stderr for the messages `log` puts in a printer
+    fn written(reporter: &mut TextReporter, log: impl FnOnce(&mut Printer)) -> (String, String) {
+        let mut printer = Printer::new(Verbosity::Normal).with_show_synced(true);
+        log(&mut printer);
+        printer.report_to(reporter);
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        reporter.write_messages(&mut out, &mut err).unwrap();
+        reporter.messages.clear();
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[test]
+    fn github_annotations_for_problems_only() {
+        let path = Path::new("/code/repo");
+        let mut reporter = TextReporter::new(Verbosity::Normal).with_format(OutputFormat::Github);
+        let (out, _) = written(&mut reporter, |printer| {
+            printer.log_local_branch_ahead_of_upstream(path, "main");
+            printer.log_unqualified_remote(path, "mirror", "https://gitlab.com/me/repo.git");
+            printer.log_branch_is_synced(path, "dev");
+        });
+        assert_eq!(
+            out,
+            "::error::/code/repo: Local branch main is ahead of the upstream\n\
+             ::warning::/code/repo: Remote mirror (https://gitlab.com/me/repo.git) is not a qualifying remote\n\
+             ✅ /code/repo: Local branch dev is synced with the remote\n"
+        );
+    }
+
+    #[test]
+    fn writes_messages_in_order() {
+        let path = Path::new("/code/repo");
+        let (out, err) = written(&mut TextReporter::new(Verbosity::Normal), |printer| {
+            printer.log_no_remotes(path);
+            printer.log_entry(path); // verbose only
+            printer.log_branch_is_synced(path, "main");
+        });
+        assert!(err.is_empty());
+        assert_eq!(
+            out,
+            "🚨 /code/repo: Repository has no remotes, so none of its work is pushed anywhere\n\
+             ✅ /code/repo: Local branch main is synced with the remote\n"
+        );
+    }
+
+    #[test]
+    fn routes_problems_to_stderr() {
+        let path = Path::new("/code/repo");
+        let mut reporter = TextReporter::new(Verbosity::Normal).with_errors_to_stderr(true);
+        let (out, err) = written(&mut reporter, |printer| {
+            printer.log_unqualified_remote(path, "mirror", "https://gitlab.com/me/repo.git");
+            printer.log_branch_is_synced(path, "main");
+            printer.log_local_branch_ahead_of_upstream(path, "wip");
+        });
+        assert_eq!(
+            out,
+            "✅ /code/repo: Local branch main is synced with the remote\n"
+        );
+        assert_eq!(
+            err,
+            "⚠️ /code/repo: Remote mirror (https://gitlab.com/me/repo.git) is not a qualifying remote\n\
+             🚨 /code/repo: Local branch wip is ahead of the upstream\n"
+        );
+    }
+
+    #[test]
+    fn shortens_the_middle_of_long_paths() {
+        assert_eq!(
+            shorten_path("/srv/code/clients/acme/backend/repo", 24),
+            "/srv/.../backend/repo"
+        );
+        assert_eq!(shorten_path("/srv/code/repo", 24), "/srv/code/repo");
+        // As short as it gets
+        assert_eq!(shorten_path("/srv/code/clients/repo", 4), "/.../repo");
+        assert_eq!(
+            fit_to_width("/srv/code/clients/acme/repo: Remote mirror is gone", 36),
+            "/srv/.../repo: Remote mirror is gone"
+        );
+        assert_eq!(
+            fit_to_width("/srv/code/repo: Remote mirror is gone", 80),
+            "/srv/code/repo: Remote mirror is gone"
+        );
+    }
+
+    #[test]
+    fn groups_messages_under_the_entry() {
+        let path = Path::new("/code/repo");
+        let mut reporter = TextReporter::new(Verbosity::Normal);
+        reporter.repo_started(path);
+        let (out, _) = written(&mut reporter, |printer| {
+            printer.log_branch_is_synced(path, "main");
+            printer.log_local_branch_ahead_of_upstream(path, "wip");
+        });
+        assert_eq!(
+            out,
+            "📁 /code/repo\n\
+             \x20 ✅ Local branch main is synced with the remote\n\
+             \x20 🚨 Local branch wip is ahead of the upstream\n"
+        );
+    }
+
+    #[test]
+    fn collapses_entries_without_problems() {
+        let path = Path::new("/code/repo");
+        let mut reporter = TextReporter::new(Verbosity::Normal);
+        reporter.repo_started(path);
+        let (out, _) = written(&mut reporter, |printer| {
+            printer.log_branch_is_synced(path, "main");
+            printer.log_branch_is_synced(path, "dev");
+        });
+        assert_eq!(out, "✅ /code/repo\n");
+    }
+
+    #[test]
+    fn ascii_theme() {
+        let path = Path::new("/code/repo");
+        let mut reporter = TextReporter::new(Verbosity::Normal).with_theme(ThemeName::Ascii.into());
+        let (out, _) = written(&mut reporter, |printer| {
+            printer.log_no_remotes(path);
+            printer.log_branch_is_synced(path, "main");
+        });
+        assert_eq!(
+            out,
+            "[error] /code/repo: Repository has no remotes, so none of its work is pushed anywhere\n\
+             [ok] /code/repo: Local branch main is synced with the remote\n"
+        );
+    }
+
+    #[test]
+    fn github_annotations_escape_newlines() {
+        let message = Message {
+            severity: Severity::Error,
+            text: "50% done\nsecond line".to_owned(),
+        };
+        assert_eq!(
+            TextReporter::new(Verbosity::Normal).github_annotation(&message),
+            "::error::50%25 done%0Asecond line"
+        );
+    }
+}